@@ -1,43 +1,202 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::{ffi::c_void, os::windows::ffi::OsStrExt, path::{Path, PathBuf}, sync::Arc, thread, time::Duration};
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU32, AtomicU64, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::{
+    ffi::c_void,
+    os::windows::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
 
 use arboard::Clipboard;
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
 use chrono::{Duration as ChronoDuration, Local, TimeZone};
 use image::{ImageBuffer, Rgba};
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use parking_lot::Mutex;
-use rusqlite::{params, Connection, OptionalExtension};
+use regex::Regex;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
 use serde::{Deserialize, Serialize};
+use similar::TextDiff;
 use simplelog::{ColorChoice, ConfigBuilder, LevelFilter, TermLogger, TerminalMode};
-use tauri::{AppHandle, Manager, State};
-use tauri::GlobalShortcutManager;
 use std::io;
-use std::io::Cursor;
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Write};
+use tauri::GlobalShortcutManager;
+use tauri::{AppHandle, Manager, State};
 use thiserror::Error;
-use windows::Win32::System::DataExchange::GetClipboardSequenceNumber;
-use windows::Win32::UI::Input::KeyboardAndMouse::{SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYEVENTF_KEYUP, VIRTUAL_KEY, KEYBD_EVENT_FLAGS, VK_CONTROL, VK_SHIFT, VK_V};
-use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId, GetWindowTextW, GetWindowTextLengthW, HICON, ICONINFO};
-use windows::Win32::Foundation::CloseHandle;
-use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_NAME_FORMAT, QueryFullProcessImageNameW};
-use windows::Win32::UI::Shell::{SHGetFileInfoW, SHGFI_DISPLAYNAME, SHGFI_ICON, SHGFI_LARGEICON, SHFILEINFOW};
-use windows::Win32::Graphics::Gdi::{GetObjectW, BITMAP, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, GetDIBits, DIB_RGB_COLORS, GetDC, ReleaseDC, DeleteObject, HBITMAP};
-use windows::Win32::UI::WindowsAndMessaging::{GetIconInfo, DestroyIcon};
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+use windows::core::{implement, w, PCWSTR, PWSTR};
+use windows::ApplicationModel::DataTransfer::{
+    Clipboard as WinRtClipboard, ClipboardHistoryItemsResultStatus, DataPackageView,
+    StandardDataFormats,
+};
+use windows::Graphics::Imaging::{BitmapPixelFormat, SoftwareBitmap};
+use windows::Media::Ocr::OcrEngine;
+use windows::Storage::Streams::{DataReader, DataWriter};
+use windows::Win32::Foundation::{
+    CloseHandle, GlobalFree, HANDLE, HGLOBAL, LPARAM, LRESULT, WPARAM,
+};
+use windows::Win32::Foundation::{
+    BOOL, DATA_S_SAMEFORMATETC, DRAGDROP_S_CANCEL, DRAGDROP_S_DROP, DRAGDROP_S_USEDEFAULTCURSORS,
+    DV_E_FORMATETC, E_NOTIMPL, OLE_E_ADVISENOTSUPPORTED, S_OK,
+};
+use windows::Win32::Graphics::Gdi::{
+    DeleteObject, GetDC, GetDIBits, GetObjectW, ReleaseDC, BITMAP, BITMAPINFO, BITMAPINFOHEADER,
+    BI_RGB, DIB_RGB_COLORS, HBITMAP, RGBQUAD,
+};
+use windows::Win32::Graphics::Gdi::{
+    GetMonitorInfoW, MonitorFromWindow, MONITORINFOEXW, MONITOR_DEFAULTTONULL,
+};
 use windows::Win32::Storage::FileSystem::FILE_FLAGS_AND_ATTRIBUTES;
-use windows::core::{PWSTR, PCWSTR};
+use windows::Win32::System::Com::{
+    IAdviseSink, IDataObject, IDataObject_Impl, IEnumFORMATETC, IEnumSTATDATA, DATADIR_GET,
+    DVASPECT_CONTENT, FORMATETC, STGMEDIUM, STGMEDIUM_0, TYMED_HGLOBAL,
+};
+use windows::Win32::System::DataExchange::{
+    CloseClipboard, EmptyClipboard, EnumClipboardFormats, GetClipboardData,
+    GetClipboardFormatNameW, GetClipboardOwner, GetClipboardSequenceNumber, OpenClipboard,
+    RegisterClipboardFormatW, SetClipboardData,
+};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::Memory::{
+    GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE,
+};
+use windows::Win32::System::Ole::{
+    DoDragDrop, IDropSource, IDropSource_Impl, OleInitialize, OleUninitialize, DROPEFFECT,
+    DROPEFFECT_COPY, DROPEFFECT_MOVE,
+};
+use windows::Win32::System::SystemServices::{MK_LBUTTON, MODIFIERKEYS_FLAGS};
+use windows::Win32::System::Threading::{
+    GetCurrentThreadId, OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_FORMAT,
+    PROCESS_QUERY_LIMITED_INFORMATION,
+};
+use windows::Win32::UI::HiDpi::GetDpiForWindow;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetAsyncKeyState, SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS,
+    KEYEVENTF_KEYUP, VIRTUAL_KEY, VK_C, VK_CONTROL, VK_INSERT, VK_LWIN, VK_MENU, VK_RWIN, VK_SHIFT,
+    VK_V, VK_X,
+};
+use windows::Win32::UI::Shell::SHCreateStdEnumFmtEtc;
+use windows::Win32::UI::Shell::{
+    SHGetFileInfoW, ShellExecuteW, SHFILEINFOW, SHGFI_DISPLAYNAME, SHGFI_ICON, SHGFI_LARGEICON,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CallNextHookEx, CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW,
+    PostThreadMessageW, RegisterClassW, SetWindowsHookExW, TranslateMessage, UnhookWindowsHookEx,
+    HC_ACTION, KBDLLHOOKSTRUCT, MSG, WH_KEYBOARD_LL, WINDOW_EX_STYLE, WINDOW_STYLE, WM_ENDSESSION,
+    WM_KEYDOWN, WM_QUIT, WM_SYSKEYDOWN, WNDCLASSW,
+};
+use windows::Win32::UI::WindowsAndMessaging::{DestroyIcon, GetIconInfo, SW_SHOWNORMAL};
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetForegroundWindow, GetWindowTextLengthW, GetWindowTextW, GetWindowThreadProcessId, HICON,
+    ICONINFO,
+};
 
 static SETTINGS_DEFAULT: Lazy<Settings> = Lazy::new(|| Settings {
     max_history: 1000,
     record_images: true,
     hotkey: "Ctrl+Shift+V".to_string(),
     blacklist: vec![],
+    quick_paste_on_double_press: false,
+    restore_clipboard_after_paste: false,
+    strip_trailing_newline_on_paste: false,
+    thumb_max_dim: default_thumb_max_dim(),
+    ignore_private_windows: false,
+    max_image_pixels: default_max_image_pixels(),
+    trash_retention_days: default_trash_retention_days(),
+    capture_on_copy_only: false,
+    list_limit: default_list_limit(),
+    normalize_whitespace_on_capture: false,
+    lossless_images: false,
+    plain_paste_hotkey: String::new(),
+    webp_thumbnails: false,
+    capture_window_titles: false,
+    detect_entities: false,
+    clear_on_exit: false,
+    clear_pinned_on_exit: false,
+    classify_capture_source: false,
+    paste_image_format: default_paste_image_format(),
+    min_capture_interval_ms: 0,
+    pinned_hotkey_modifier: String::new(),
+    skip_blank_images: false,
+    blank_image_tolerance: default_blank_image_tolerance(),
+    default_plain_paste: false,
+    paste_mode_hotkey: String::new(),
+    duplicate_policy: default_duplicate_policy(),
+    pinned_display: default_pinned_display(),
+    record_unknown_formats: false,
+    max_text_items: 0,
+    max_image_items: 0,
+    app_name_map_path: String::new(),
+    rdp_clipboard_retry: true,
+    ignored_formats: vec![],
+    capture_preference: default_capture_preference(),
+    prefer_clipboard_owner: false,
+    capture_transforms: Vec::new(),
+    store_images_on_disk: false,
+    same_app_paste: default_same_app_paste(),
 });
 
 static SKIP_UNTIL_MS: AtomicU64 = AtomicU64::new(0);
+static WATCHER_STOP: AtomicBool = AtomicBool::new(false);
+// 进入退出流程后置位，之后不再允许 restart_watcher 重新启动监听
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+static WATCHER_THREAD: Lazy<Mutex<Option<thread::JoinHandle<()>>>> = Lazy::new(|| Mutex::new(None));
+// 监听线程每轮循环更新的心跳、最近一次保存成功的时间与最近看到的剪贴板序列号，供 get_capture_health 排查漏记
+static WATCHER_HEARTBEAT_MS: AtomicU64 = AtomicU64::new(0);
+static LAST_CAPTURE_MS: AtomicU64 = AtomicU64::new(0);
+static LAST_SEEN_SEQ: AtomicU64 = AtomicU64::new(0);
+// 注销/关机时窗口过程里拿不到 tauri 状态，启动时存一份
+static SESSION_STATE: OnceCell<AppState> = OnceCell::new();
+// 没有 AppHandle 参数的深层函数（如记录使用次数）要发事件时使用
+static APP_HANDLE: OnceCell<AppHandle> = OnceCell::new();
+// 外存图片所在目录（与数据库同级的 images），在 ensure_db 里确定
+static IMAGES_DIR: OnceCell<PathBuf> = OnceCell::new();
+// 呼出窗口前的前台应用路径，窗口打开后前台变成 Pastify 自己
+static PREVIOUS_APP_PATH: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+// 用户提供的应用名映射（键已转小写），由 app_name_map_path 指定的文件加载
+static APP_NAME_MAP: Lazy<Mutex<std::collections::HashMap<String, String>>> =
+    Lazy::new(Default::default);
+static LAST_HOTKEY_MS: AtomicU64 = AtomicU64::new(0);
+// 启动时快捷键注册失败的信息；前端可能晚于事件加载，通过 get_hotkey_error 补取，设置新快捷键成功后清空
+static HOTKEY_ERROR: Lazy<Mutex<Option<HotkeyError>>> = Lazy::new(|| Mutex::new(None));
+static NEXT_IO_OP: AtomicU64 = AtomicU64::new(1);
+// 最近一次 stream_history 的 id，新的请求开始后旧的推送线程自行停止
+static LATEST_HISTORY_STREAM: AtomicU64 = AtomicU64::new(0);
+// 未删除条目数的估计值，只会偏大；-1 表示未知，下次插入时重新 COUNT
+static LIVE_ITEM_COUNT: AtomicI64 = AtomicI64::new(-1);
+// 只读（演示/共享）模式：启动时由 PASTIFY_READ_ONLY=1 开启，运行期间不变
+static READ_ONLY: Lazy<bool> = Lazy::new(|| {
+    std::env::var("PASTIFY_READ_ONLY").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+});
+static COPY_KEY_AT_MS: AtomicU64 = AtomicU64::new(0);
+// 键盘钩子线程 id；0 表示未运行，u32::MAX 表示正在启动
+static COPY_HOOK_THREAD: AtomicU32 = AtomicU32::new(0);
+const COPY_KEY_WINDOW_MS: u64 = 1500;
+// 同一来源在该时间内重复写入相同图片时只累加使用次数
+const IMAGE_REPEAT_WINDOW_MS: i64 = 5000;
+const WATCH_INTERVAL_MS: u64 = 250;
+const WATCH_MAX_BACKOFF_MS: u64 = 8000;
+// 监听循环 panic 后等待多久再重启
+const WATCHER_RESTART_DELAY_MS: u64 = 1000;
+// 远程桌面剪贴板读到空内容时的重试次数与间隔
+const RDP_RETRY_ATTEMPTS: u32 = 3;
+const RDP_RETRY_DELAY_MS: u64 = 300;
+// 有新写入时，监听线程最多隔这么久做一次被动 checkpoint
+const WAL_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+// 监听线程检查单条到期时间的间隔
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+// 约 4MB（按 4KB 页计），超过后由提交写入的连接自动 checkpoint
+const WAL_AUTOCHECKPOINT_PAGES: i64 = 1000;
+const QUICK_PASTE_WINDOW_MS: u64 = 800;
+// 标准剪贴板格式编号
+const CF_DIB: u32 = 8;
+const CF_DIBV5: u32 = 17;
 
 #[derive(Debug, Error)]
 enum AppError {
@@ -51,7 +210,33 @@ enum AppError {
     Other(String),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+impl AppError {
+    /// 前端据此区分读剪贴板失败与写库失败
+    fn kind(&self) -> &'static str {
+        match self {
+            AppError::Db(_) => "db",
+            AppError::Io(_) => "io",
+            AppError::Clipboard(_) => "clipboard",
+            AppError::Other(_) => "other",
+        }
+    }
+}
+
+/// 启动时快捷键注册失败的原因；fallback 为改用的默认快捷键（仅本次运行有效，不写入设置）
+#[derive(Debug, Serialize, Clone)]
+struct HotkeyError {
+    message: String,
+    fallback: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct WatchError {
+    kind: &'static str,
+    message: String,
+    failures: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 struct ClipboardItem {
     id: i64,
     content_type: String,
@@ -60,9 +245,28 @@ struct ClipboardItem {
     source_app: Option<String>,
     source_path: Option<String>,
     source_icon: Option<Vec<u8>>, // png bytes
+    source_url: Option<String>,
     created_at: i64,
     is_pinned: bool,
     usage_count: i64,
+    detected_format: Option<String>,
+    table_rows: Option<i64>,
+    table_cols: Option<i64>,
+    thumb_data: Option<Vec<u8>>,     // downscaled png bytes
+    detected_entity: Option<String>, // email / phone / iban
+    source_monitor: Option<String>,  // 截图时前台窗口所在显示器，仅图片记录
+    source_dpi: Option<i64>,
+    capture_source: Option<String>, // user / auto，未开启 classify_capture_source 时为空
+    label: Option<String>,          // 列表中代替内容预览显示的标题
+    derived_from: Option<i64>,      // OCR 等由其他条目生成时记录来源条目 id
+    auto_delete_after_uses: Option<i64>, // 粘贴/复制累计达到该次数后彻底删除
+    expires_at: Option<i64>,        // 到期时间（毫秒），到期后彻底删除，置顶条目同样生效
+    raw_text: Option<String>,       // 规整空白前的原文，仅在与 text_content 不同时保存
+    dib_data: Option<Vec<u8>>,      // 原始 DIB，仅 lossless_images 开启时保存
+    drop_effect: Option<i64>, // 从资源管理器复制文件时的 Preferred DropEffect（1 复制、2 移动），其余条目为空
+    image_hash: Option<i64>,  // 图片的 64 位 dHash，用于查找相似图片
+    image_path: Option<String>, // 外存图片的相对路径，此时 image_data 只在 load_image_file 后才有
+    image_size: Option<i64>,  // 外存图片的文件字节数，列表和排序不必读文件
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -73,9 +277,23 @@ pub struct ClipboardDto {
     image_thumb: Option<String>,
     source_app: Option<String>,
     source_icon: Option<String>,
+    source_url: Option<String>,
     created_at: i64,
+    created_at_iso: Option<String>, // created_at 的 RFC3339 表示，本地时区、毫秒精度
     is_pinned: bool,
     usage_count: i64,
+    size_bytes: i64,
+    detected_format: Option<String>,
+    table_rows: Option<i64>,
+    table_cols: Option<i64>,
+    detected_entity: Option<String>,
+    source_monitor: Option<String>,
+    source_dpi: Option<i64>,
+    capture_source: Option<String>,
+    label: Option<String>,
+    derived_from: Option<i64>,
+    auto_delete_after_uses: Option<i64>,
+    expires_at: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -84,13 +302,379 @@ pub struct Settings {
     record_images: bool,
     hotkey: String,
     blacklist: Vec<String>,
+    /// 窗口已聚焦时短时间内再次按下快捷键，直接粘贴最新一条并隐藏窗口
+    #[serde(default)]
+    quick_paste_on_double_press: bool,
+    /// 粘贴后恢复用户原本的剪贴板内容
+    #[serde(default)]
+    restore_clipboard_after_paste: bool,
+    /// 粘贴文本时去掉末尾一个换行，存储内容不变
+    #[serde(default)]
+    strip_trailing_newline_on_paste: bool,
+    /// 图片缩略图最长边（像素），范围 64~1024
+    #[serde(default = "default_thumb_max_dim")]
+    thumb_max_dim: u32,
+    /// 前台为浏览器隐私窗口时不记录（基于窗口标题的启发式）
+    #[serde(default)]
+    ignore_private_windows: bool,
+    /// 超过该像素数的剪贴板图片不记录，避免超大图片占满内存
+    #[serde(default = "default_max_image_pixels")]
+    max_image_pixels: u64,
+    /// 回收站保留天数，超过后彻底删除
+    #[serde(default = "default_trash_retention_days")]
+    trash_retention_days: i64,
+    /// 仅在用户按下 Ctrl+C / Ctrl+X / Ctrl+Insert 后才记录，忽略后台程序改写剪贴板
+    #[serde(default)]
+    capture_on_copy_only: bool,
+    /// get_history 单次返回的默认条数，避免一次性给 webview 过大的数据
+    #[serde(default = "default_list_limit")]
+    list_limit: i64,
+    /// 记录文本前合并行内连续空白、去掉行尾空格，原文另存一份
+    #[serde(default)]
+    normalize_whitespace_on_capture: bool,
+    /// 额外保存原始 CF_DIBV5/CF_DIB 数据，粘贴时原样写回，占用空间更大
+    #[serde(default)]
+    lossless_images: bool,
+    /// 直接以纯文本粘贴最新一条的快捷键，留空表示不启用
+    #[serde(default)]
+    plain_paste_hotkey: String,
+    /// 缩略图改用无损 WebP 编码，减小 get_history 经 IPC 传输的数据量
+    #[serde(default)]
+    webp_thumbnails: bool,
+    /// 来源显示为“窗口标题 (应用名)”；标题可能含文档名，默认关闭只记应用名
+    #[serde(default)]
+    capture_window_titles: bool,
+    /// 整段文本恰好是邮箱、电话或 IBAN 时打上 detected_entity 标记
+    #[serde(default)]
+    detect_entities: bool,
+    /// 退出、注销或关机时彻底清空未置顶的历史（含回收站），不可恢复
+    #[serde(default)]
+    clear_on_exit: bool,
+    /// 配合 clear_on_exit，连置顶条目一起清空
+    #[serde(default)]
+    clear_pinned_on_exit: bool,
+    /// 借助键盘钩子把每条记录标记为用户复制（user）或后台程序改写（auto）
+    #[serde(default)]
+    classify_capture_source: bool,
+    /// 粘贴图片时写入剪贴板的格式：preserve（原样）、png（PNG 并附带 DIB）、bmp（仅 DIB，不含透明度）
+    #[serde(default = "default_paste_image_format")]
+    paste_image_format: String,
+    /// 剪贴板变化后等待多久没有新变化再读取（毫秒），合并程序分多步写入的中间状态；0 表示立即读取
+    #[serde(default)]
+    min_capture_interval_ms: u64,
+    /// 置顶条目快捷粘贴的修饰键（如 Ctrl+Alt），加数字 1~9 粘贴列表中第 N 个置顶条目；留空不注册
+    #[serde(default)]
+    pinned_hotkey_modifier: String,
+    /// 跳过纯色或全透明的图片（部分程序操作中会短暂放一张空白位图）
+    #[serde(default)]
+    skip_blank_images: bool,
+    /// 判定纯色时各通道允许的最大差值（0~255）
+    #[serde(default = "default_blank_image_tolerance")]
+    blank_image_tolerance: u8,
+    /// 选中条目时默认按纯文本粘贴（Ctrl+Enter 则按原格式），双击快捷键快速粘贴同样遵循
+    #[serde(default)]
+    default_plain_paste: bool,
+    /// 切换默认粘贴方式（纯文本/原格式）的全局快捷键，留空不注册
+    #[serde(default)]
+    paste_mode_hotkey: String,
+    /// 重复内容的处理：bump（默认，历史中已有则移到最前）、ignore（与最新一条相同则不记录）、always_new（每次都新增）
+    #[serde(default = "default_duplicate_policy")]
+    duplicate_policy: String,
+    /// 置顶条目的排列方式：top 置顶在前，separate 单独分组返回，inline 与其他条目按时间混排
+    #[serde(default = "default_pinned_display")]
+    pinned_display: String,
+    /// 文本和图片都读不到但剪贴板里有其他格式时，记录一条只含格式名称的占位条目
+    #[serde(default)]
+    record_unknown_formats: bool,
+    /// 文本类条目（含路径）的单独上限，0 表示只受 max_history 约束；两个上限同时生效，先到先清理
+    #[serde(default)]
+    max_text_items: i64,
+    /// 图片条目的单独上限，0 表示不单独限制
+    #[serde(default)]
+    max_image_items: i64,
+    /// 自定义应用名映射文件（JSON，键为 exe 文件名不含扩展名，值为显示名称），优先于内置映射；留空不加载
+    #[serde(default)]
+    app_name_map_path: String,
+    /// 远程桌面（rdpclip）转发的剪贴板可能延迟就绪，读到空内容时短暂重试几次
+    #[serde(default = "default_true")]
+    rdp_clipboard_retry: bool,
+    /// 剪贴板里只有这些格式时不读取（名称不区分大小写，预定义格式写作 CF_UNICODETEXT 等），用于屏蔽频繁写入私有格式的后台程序
+    #[serde(default)]
+    ignored_formats: Vec<String>,
+    /// 文本和图片同时存在时记录哪一种：text 只记文本，image 只记图片，both 两者各记一条（图片在前、文本为最新）
+    #[serde(default = "default_capture_preference")]
+    capture_preference: String,
+    /// 来源优先取剪贴板所有者窗口（GetClipboardOwner）的进程，与前台窗口不同且能解析时采用，否则仍用前台窗口
+    #[serde(default)]
+    prefer_clipboard_owner: bool,
+    /// 记录文本前依次执行的处理：trim（去掉首尾空白）、collapse_whitespace（规整空白）、strip_tracking_params（去掉网址中的 utm_* 等跟踪参数）；不认识的名称记录警告后跳过
+    #[serde(default)]
+    capture_transforms: Vec<String>,
+    /// 新记录的图片写到数据目录下的 images/<id>.png，库里只存相对路径；已有图片用 externalize_images 迁出
+    #[serde(default)]
+    store_images_on_disk: bool,
+    /// 粘贴目标就是条目的来源应用时：off 不处理，plain 文本条目改为纯文本粘贴，record 只计数并发出 clipboard://same-app-paste 事件
+    #[serde(default = "default_same_app_paste")]
+    same_app_paste: String,
+}
+
+fn default_thumb_max_dim() -> u32 {
+    256
+}
+
+fn default_max_image_pixels() -> u64 {
+    50_000_000
+}
+
+fn default_trash_retention_days() -> i64 {
+    7
+}
+
+fn default_list_limit() -> i64 {
+    500
+}
+
+fn default_paste_image_format() -> String {
+    "preserve".into()
+}
+
+fn default_blank_image_tolerance() -> u8 {
+    8
+}
+
+fn default_duplicate_policy() -> String {
+    "bump".into()
+}
+
+fn default_pinned_display() -> String {
+    "top".into()
+}
+
+fn default_capture_preference() -> String {
+    "text".into()
 }
 
-#[derive(Debug)]
-#[derive(Clone)]
+fn default_same_app_paste() -> String {
+    "off".into()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone)]
 struct AppState {
     db_path: PathBuf,
     settings: Arc<Mutex<Settings>>,
+    stats: Arc<CaptureStats>,
+    // 未删除条目按时间从新到旧的前若干条（可能不足上限），前端重连后据此重建列表顶部，不必查库
+    recent: Arc<Mutex<std::collections::VecDeque<ClipboardDto>>>,
+}
+
+const RECENT_CACHE_CAPACITY: usize = 20;
+
+impl AppState {
+    /// 新增或改动过的条目放回缓存；缓存为空时先查库确认它是最新的一条才放
+    fn place_recent(&self, conn: &Connection, dto: ClipboardDto) {
+        let newest = self.recent.lock().is_empty()
+            && conn
+                .query_row(
+                    "SELECT NOT EXISTS(SELECT 1 FROM clipboard_items WHERE deleted_at IS NULL AND created_at > ?1)",
+                    params![dto.created_at],
+                    |row| row.get(0),
+                )
+                .unwrap_or(false);
+        place_recent(&mut self.recent.lock(), dto, newest);
+    }
+
+    /// 按库里的当前状态更新一条：已删除或不存在的移出，否则重新读出后放回
+    fn refresh_recent(&self, conn: &Connection, id: i64) {
+        let live = conn
+            .query_row(
+                "SELECT deleted_at IS NULL FROM clipboard_items WHERE id = ?1",
+                params![id],
+                |row| row.get::<_, bool>(0),
+            )
+            .optional();
+        match live {
+            Ok(Some(true)) => match fetch_item(conn, id) {
+                Ok(item) => self.place_recent(conn, to_dto(item)),
+                Err(err) => self.drop_recent(err),
+            },
+            Ok(_) => self.forget_recent(&[id]),
+            Err(err) => self.drop_recent(err),
+        }
+    }
+
+    fn forget_recent(&self, ids: &[i64]) {
+        self.recent
+            .lock()
+            .retain(|cached| !ids.contains(&cached.id));
+    }
+
+    /// 批量改动后直接从库里重新取最新的若干条
+    fn reload_recent(&self, conn: &Connection) {
+        let loaded = conn
+            .prepare(&format!(
+                "SELECT {ITEM_COLUMNS} FROM clipboard_items WHERE deleted_at IS NULL ORDER BY created_at DESC LIMIT ?1"
+            ))
+            .and_then(|mut stmt| {
+                stmt.query_map(params![RECENT_CACHE_CAPACITY as i64], item_from_row)?
+                    .map(|item| item.map(to_dto))
+                    .collect::<rusqlite::Result<std::collections::VecDeque<_>>>()
+            });
+        match loaded {
+            Ok(recent) => *self.recent.lock() = recent,
+            Err(err) => self.drop_recent(err),
+        }
+    }
+
+    /// 读库失败时清空：空缓存总是对的，只是前端要自己查一次
+    fn drop_recent(&self, err: impl std::fmt::Display) {
+        log::warn!("recent cache cleared: {err}");
+        self.recent.lock().clear();
+    }
+}
+
+/// 保持缓存是按 created_at 从新到旧的连续前几条：只有不早于缓存里最旧一条的才放进去，
+/// 否则连同旧副本一起移出，避免中间缺了库里实际存在的条目
+fn place_recent(
+    recent: &mut std::collections::VecDeque<ClipboardDto>,
+    dto: ClipboardDto,
+    newest: bool,
+) {
+    recent.retain(|cached| cached.id != dto.id);
+    let fits = match recent.back() {
+        Some(oldest) => dto.created_at >= oldest.created_at,
+        None => newest,
+    };
+    if fits {
+        let pos = recent
+            .iter()
+            .position(|cached| cached.created_at <= dto.created_at)
+            .unwrap_or(recent.len());
+        recent.insert(pos, dto);
+        recent.truncate(RECENT_CACHE_CAPACITY);
+    }
+}
+
+/// 写库的函数大多拿不到 State，经 APP_HANDLE 找到它；界面还没启动时（如测试）为 None
+fn app_state() -> Option<State<'static, AppState>> {
+    APP_HANDLE.get()?.try_state::<AppState>()
+}
+
+/// 自启动以来的采集统计，只计数不影响采集行为
+#[derive(Debug, Default)]
+struct CaptureStats {
+    reads: AtomicU64,
+    total_us: AtomicU64,
+    min_us: AtomicU64, // 0 表示还没有样本
+    max_us: AtomicU64,
+    saved: AtomicU64,
+    repeated_images: AtomicU64,
+    skipped_own_write: AtomicU64,
+    skipped_not_copy: AtomicU64,
+    skipped_ignored_format: AtomicU64,
+    skipped_blacklist: AtomicU64,
+    skipped_private: AtomicU64,
+    skipped_empty: AtomicU64,
+    skipped_duplicate: AtomicU64,
+    errors: AtomicU64,
+    source_from_foreground: AtomicU64,
+    source_from_owner: AtomicU64,
+    watcher_restarts: AtomicU64,
+    same_app_pastes: AtomicU64,
+}
+
+impl CaptureStats {
+    fn record_read(&self, elapsed: Duration) {
+        let us = (elapsed.as_micros() as u64).max(1);
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        self.total_us.fetch_add(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+        let _ = self
+            .min_us
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |cur| {
+                (cur == 0 || us < cur).then_some(us)
+            });
+    }
+
+    fn bump(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SourceApp {
+    app: String,
+    icon: Option<String>,
+    count: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct Diagnostics {
+    reads: u64,
+    min_ms: f64,
+    avg_ms: f64,
+    max_ms: f64,
+    saved: u64,
+    repeated_images: u64,
+    skipped_own_write: u64,
+    skipped_not_copy: u64,
+    skipped_ignored_format: u64,
+    skipped_blacklist: u64,
+    skipped_private: u64,
+    skipped_empty: u64,
+    skipped_duplicate: u64,
+    errors: u64,
+    // 来源由前台窗口还是剪贴板所有者确定的次数
+    source_from_foreground: u64,
+    source_from_owner: u64,
+    // 监听循环因 panic 被自动重启的次数
+    watcher_restarts: u64,
+    // 粘贴目标与条目来源是同一应用的次数（same_app_paste 关闭时不统计）
+    same_app_pastes: u64,
+}
+
+/// 导出文件每行一条，图片以 base64 存储
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedItem {
+    content_type: String,
+    text_content: Option<String>,
+    image_data: Option<String>,
+    source_app: Option<String>,
+    source_url: Option<String>,
+    created_at: i64,
+    /// 仅供阅读，导入时以 created_at 为准
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at_iso: Option<String>,
+    #[serde(default)]
+    is_pinned: bool,
+    #[serde(default)]
+    usage_count: i64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct IoProgress {
+    op_id: u64,
+    kind: &'static str,
+    processed: usize,
+    total: usize,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct IoDone {
+    op_id: u64,
+    kind: &'static str,
+    count: usize,
+    /// 无法解析或数据损坏而跳过的条数
+    skipped: usize,
+    error: Option<String>,
+}
+
+enum ClipboardSnapshot {
+    Text(String),
+    Image(arboard::ImageData<'static>),
 }
 
 #[derive(Clone, Debug)]
@@ -98,6 +682,10 @@ struct ProcessInfo {
     display: String,
     path: String,
     icon_png: Option<Vec<u8>>,
+    window_title: Option<String>,
+    monitor: Option<String>,
+    dpi: Option<u32>,
+    from_owner: bool, // 由剪贴板所有者窗口而非前台窗口确定
 }
 
 fn init_logger() {
@@ -110,8 +698,147 @@ fn init_logger() {
     );
 }
 
-fn ensure_db(db_path: &PathBuf) -> Result<(), AppError> {
+/// 所有读写统一经此打开连接，带 busy_timeout 以免并发写入时立即报 "database is locked"
+fn open_db(db_path: &Path) -> rusqlite::Result<Connection> {
     let conn = Connection::open(db_path)?;
+    conn.busy_timeout(Duration::from_millis(3000))?;
+    conn.pragma_update(None, "wal_autocheckpoint", WAL_AUTOCHECKPOINT_PAGES)?;
+    Ok(conn)
+}
+
+/// 所有修改历史或设置的命令开头调用
+fn ensure_not_read_only() -> Result<(), String> {
+    if *READ_ONLY {
+        Err("只读模式下不能修改历史或设置".into())
+    } else {
+        Ok(())
+    }
+}
+
+#[tauri::command]
+fn is_read_only() -> bool {
+    *READ_ONLY
+}
+
+fn bump_usage(conn: &mut Connection, id: i64) -> rusqlite::Result<()> {
+    // 只读模式下照常粘贴，只是不记使用次数
+    if *READ_ONLY {
+        return Ok(());
+    }
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    tx.execute(
+        "UPDATE clipboard_items SET usage_count = usage_count + 1 WHERE id = ?1",
+        params![id],
+    )?;
+    // 阅后即焚：用满次数后直接删除，不进回收站
+    let burned = delete_rows(
+        &tx,
+        "id = ?1 AND auto_delete_after_uses IS NOT NULL AND usage_count >= auto_delete_after_uses",
+        params![id],
+    )?;
+    tx.commit()?;
+    remove_image_files(&burned, false);
+    let burned = !burned.is_empty();
+    if let Some(state) = app_state() {
+        state.refresh_recent(conn, id);
+    }
+    if burned {
+        LIVE_ITEM_COUNT.store(-1, Ordering::SeqCst);
+        if let Some(app) = APP_HANDLE.get() {
+            let _ = app.emit_all("clipboard://removed", id);
+        }
+    }
+    Ok(())
+}
+
+/// 设置条目用几次后自动删除；uses 为空表示取消
+#[tauri::command]
+fn set_autodelete(
+    state: State<AppState>,
+    id: i64,
+    uses: Option<i64>,
+) -> Result<ClipboardDto, String> {
+    ensure_not_read_only()?;
+    if uses.is_some_and(|n| n < 1) {
+        return Err("使用次数至少为 1".into());
+    }
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    // 从设置时起重新计数，已有的使用次数不算在内
+    let updated = conn
+        .execute(
+            "UPDATE clipboard_items SET auto_delete_after_uses = usage_count + ?1 WHERE id = ?2 AND deleted_at IS NULL",
+            params![uses, id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err("条目不存在".into());
+    }
+    let dto = to_dto(fetch_item(&conn, id).map_err(|e| e.to_string())?);
+    state.place_recent(&conn, dto.clone());
+    Ok(dto)
+}
+
+/// 设置条目的到期时间（毫秒时间戳），到期后由监听线程彻底删除；置顶条目也不例外。expires_at 为空表示取消
+#[tauri::command]
+fn set_expiry(
+    state: State<AppState>,
+    id: i64,
+    expires_at: Option<i64>,
+) -> Result<ClipboardDto, String> {
+    ensure_not_read_only()?;
+    if expires_at.is_some_and(|ms| ms <= chrono::Utc::now().timestamp_millis()) {
+        return Err("到期时间需晚于当前时间".into());
+    }
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let updated = conn
+        .execute(
+            "UPDATE clipboard_items SET expires_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+            params![expires_at, id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err("条目不存在".into());
+    }
+    let dto = to_dto(fetch_item(&conn, id).map_err(|e| e.to_string())?);
+    state.place_recent(&conn, dto.clone());
+    Ok(dto)
+}
+
+/// 彻底删除已到期的条目（含回收站里的），返回被删除的 id
+fn purge_expired(db_path: &Path) -> Result<Vec<i64>, AppError> {
+    let conn = open_db(db_path)?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut stmt =
+        conn.prepare("DELETE FROM clipboard_items WHERE expires_at IS NOT NULL AND expires_at <= ?1 RETURNING id, image_path")?;
+    let removed = stmt
+        .query_map(params![now], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(i64, Option<String>)>, _>>()?;
+    let ids = removed
+        .into_iter()
+        .map(|(id, image_path)| {
+            if let Some(rel) = image_path {
+                remove_image_file(&rel, false);
+            }
+            id
+        })
+        .collect::<Vec<_>>();
+    if !ids.is_empty() {
+        LIVE_ITEM_COUNT.store(-1, Ordering::SeqCst);
+        if let Some(state) = app_state() {
+            state.forget_recent(&ids);
+        }
+    }
+    Ok(ids)
+}
+
+fn ensure_db(db_path: &Path) -> Result<(), AppError> {
+    let _ = IMAGES_DIR.set(db_path.with_file_name("images"));
+    let conn = open_db(db_path)?;
+    // WAL 模式写在库文件里，设置一次即可；读写互不阻塞，监听线程写入时前端查询不必等待
+    let mode: String = conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))?;
+    if !mode.eq_ignore_ascii_case("wal") {
+        log::warn!("journal_mode stays {mode}");
+    }
     conn.execute_batch(
         "CREATE TABLE IF NOT EXISTS clipboard_items (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -121,21 +848,48 @@ fn ensure_db(db_path: &PathBuf) -> Result<(), AppError> {
             source_app TEXT,
             source_path TEXT,
             source_icon BLOB,
+            source_url TEXT,
             created_at INTEGER NOT NULL,
             is_pinned INTEGER DEFAULT 0,
-            usage_count INTEGER DEFAULT 0
+            usage_count INTEGER DEFAULT 0,
+            detected_format TEXT,
+            table_rows INTEGER,
+            table_cols INTEGER,
+            thumb_data BLOB,
+            deleted_at INTEGER,
+            raw_text TEXT,
+            dib_data BLOB,
+            drop_effect INTEGER,
+            detected_entity TEXT,
+            source_monitor TEXT,
+            source_dpi INTEGER,
+            capture_source TEXT,
+            label TEXT,
+            derived_from INTEGER,
+            image_hash INTEGER,
+            auto_delete_after_uses INTEGER,
+            expires_at INTEGER,
+            image_path TEXT,
+            checksum INTEGER,
+            image_size INTEGER
         );
         CREATE INDEX IF NOT EXISTS idx_clipboard_created_at ON clipboard_items(created_at DESC);
         CREATE TABLE IF NOT EXISTS settings (
             key TEXT PRIMARY KEY,
             value TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS named_buffers (
+            name TEXT PRIMARY KEY,
+            item_id INTEGER NOT NULL
         );",
     )?;
 
     ensure_schema_updates(&conn)?;
 
     let settings_json: Option<String> = conn
-        .query_row("SELECT value FROM settings WHERE key = 'app'", [], |row| row.get(0))
+        .query_row("SELECT value FROM settings WHERE key = 'app'", [], |row| {
+            row.get(0)
+        })
         .optional()?;
     if settings_json.is_none() {
         let json = serde_json::to_string(&*SETTINGS_DEFAULT).unwrap();
@@ -147,14 +901,14 @@ fn ensure_db(db_path: &PathBuf) -> Result<(), AppError> {
     Ok(())
 }
 
-fn load_settings(db_path: &PathBuf) -> Result<Settings, AppError> {
-    let conn = Connection::open(db_path)?;
-    let json: String = conn.query_row(
-        "SELECT value FROM settings WHERE key = 'app'",
-        [],
-        |row| row.get(0),
-    )?;
-    let mut settings: Settings = serde_json::from_str(&json).unwrap_or_else(|_| SETTINGS_DEFAULT.clone());
+fn load_settings(db_path: &Path) -> Result<Settings, AppError> {
+    let conn = open_db(db_path)?;
+    let json: String =
+        conn.query_row("SELECT value FROM settings WHERE key = 'app'", [], |row| {
+            row.get(0)
+        })?;
+    let mut settings: Settings =
+        serde_json::from_str(&json).unwrap_or_else(|_| SETTINGS_DEFAULT.clone());
     // 图片记录始终开启
     if !settings.record_images {
         settings.record_images = true;
@@ -163,8 +917,8 @@ fn load_settings(db_path: &PathBuf) -> Result<Settings, AppError> {
     Ok(settings)
 }
 
-fn save_settings(db_path: &PathBuf, settings: &Settings) -> Result<(), AppError> {
-    let conn = Connection::open(db_path)?;
+fn save_settings(db_path: &Path, settings: &Settings) -> Result<(), AppError> {
+    let conn = open_db(db_path)?;
     let json = serde_json::to_string(settings).unwrap();
     conn.execute(
         "INSERT OR REPLACE INTO settings(key, value) VALUES('app', ?1)",
@@ -173,62 +927,271 @@ fn save_settings(db_path: &PathBuf, settings: &Settings) -> Result<(), AppError>
     Ok(())
 }
 
-fn enforce_limit(db_path: &PathBuf, max: i64) -> Result<(), AppError> {
-    let conn = Connection::open(db_path)?;
-    conn.execute(
-        "DELETE FROM clipboard_items
-         WHERE id NOT IN (
-            SELECT id FROM clipboard_items ORDER BY is_pinned DESC, created_at DESC LIMIT ?1
-         ) AND is_pinned = 0",
-        params![max],
-    )?;
+// 超出条数上限时要清理的条目（?1 为上限），enforce_limit 与 preview_pruning 共用
+const OVER_LIMIT_WHERE: &str = "id NOT IN (
+    SELECT id FROM clipboard_items WHERE deleted_at IS NULL ORDER BY is_pinned DESC, created_at DESC LIMIT ?1
+ ) AND is_pinned = 0 AND deleted_at IS NULL";
+// 早于截止时间的未置顶条目（?2 为截止时间戳）
+const EXPIRED_WHERE: &str = "created_at < ?2 AND is_pinned = 0 AND deleted_at IS NULL";
+
+/// 某一类条目超出单独上限时要清理的部分，同样按置顶优先、新到旧保留；参数都是整数，直接拼入 SQL
+fn over_type_limit_where(image: bool, limit: i64) -> String {
+    let kind = if image {
+        "content_type = 'image'"
+    } else {
+        "content_type != 'image'"
+    };
+    format!(
+        "id NOT IN (
+    SELECT id FROM clipboard_items WHERE deleted_at IS NULL AND {kind} ORDER BY is_pinned DESC, created_at DESC LIMIT {limit}
+ ) AND {kind} AND is_pinned = 0 AND deleted_at IS NULL"
+    )
+}
+
+/// 已设置的分类上限对应的清理条件，未设置时为空
+fn type_limit_wheres(settings: &Settings) -> Vec<String> {
+    [
+        (false, settings.max_text_items),
+        (true, settings.max_image_items),
+    ]
+    .into_iter()
+    .filter(|(_, limit)| *limit > 0)
+    .map(|(image, limit)| over_type_limit_where(image, limit))
+    .collect()
+}
+
+fn enforce_limit(db_path: &Path, max: i64) -> Result<(), AppError> {
+    let conn = open_db(db_path)?;
+    delete_items(&conn, OVER_LIMIT_WHERE, params![max], false)?;
     Ok(())
 }
 
+/// 预演清理：返回按给定上限、保留天数以及当前分类上限会被删除的条目 id（新到旧），不做任何删除
+#[tauri::command]
+fn preview_pruning(
+    state: State<AppState>,
+    max: i64,
+    max_age_days: Option<i64>,
+) -> Result<Vec<i64>, String> {
+    if max < 1 {
+        return Err("历史上限需大于 0".into());
+    }
+    let cutoff = match max_age_days {
+        Some(days) if days > 0 => {
+            (chrono::Utc::now() - ChronoDuration::days(days)).timestamp_millis()
+        }
+        Some(_) => return Err("保留天数需大于 0".into()),
+        None => i64::MIN,
+    };
+    let mut where_sql = format!("({OVER_LIMIT_WHERE}) OR ({EXPIRED_WHERE})");
+    for extra in type_limit_wheres(&state.settings.lock()) {
+        where_sql.push_str(&format!(" OR ({extra})"));
+    }
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id FROM clipboard_items WHERE {where_sql} ORDER BY created_at DESC"
+        ))
+        .map_err(|e| e.to_string())?;
+    let ids = stmt
+        .query_map(params![max, cutoff], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<i64>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(ids)
+}
+
+/// 条目数超过上限时才执行清理，避免每次记录都跑一遍 DELETE；分类上限在总上限之后各自清理。
+/// 返回本次是否执行了总上限清理
+fn enforce_limit_if_needed(
+    conn: &Connection,
+    db_path: &Path,
+    settings: &Settings,
+) -> Result<bool, AppError> {
+    let max = settings.max_history;
+    let count = if LIVE_ITEM_COUNT.load(Ordering::SeqCst) < 0 {
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM clipboard_items WHERE deleted_at IS NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        LIVE_ITEM_COUNT.store(count, Ordering::SeqCst);
+        count
+    } else {
+        LIVE_ITEM_COUNT.fetch_add(1, Ordering::SeqCst) + 1
+    };
+    let pruned = count > max;
+    if pruned {
+        enforce_limit(db_path, max)?;
+        // 置顶条目不参与清理，清理后的条数重新统计
+        LIVE_ITEM_COUNT.store(-1, Ordering::SeqCst);
+    }
+    for where_sql in type_limit_wheres(settings) {
+        if delete_items(conn, &where_sql, [], false)? > 0 {
+            LIVE_ITEM_COUNT.store(-1, Ordering::SeqCst);
+        }
+    }
+    Ok(pruned)
+}
+
+type Migration = fn(&Connection) -> Result<(), AppError>;
+
+// 按顺序追加，第 N 项把库升级到版本 N；每一步都必须可重复执行
+const MIGRATIONS: &[Migration] = &[
+    |conn| add_missing_columns(conn, &[("source_path", "TEXT"), ("source_icon", "BLOB")]),
+    |conn| add_missing_columns(conn, &[("source_url", "TEXT")]),
+    |conn| {
+        add_missing_columns(
+            conn,
+            &[
+                ("detected_format", "TEXT"),
+                ("table_rows", "INTEGER"),
+                ("table_cols", "INTEGER"),
+            ],
+        )
+    },
+    |conn| add_missing_columns(conn, &[("thumb_data", "BLOB")]),
+    |conn| add_missing_columns(conn, &[("deleted_at", "INTEGER")]),
+    |conn| add_missing_columns(conn, &[("raw_text", "TEXT")]),
+    |conn| add_missing_columns(conn, &[("dib_data", "BLOB")]),
+    |conn| add_missing_columns(conn, &[("detected_entity", "TEXT")]),
+    |conn| {
+        add_missing_columns(
+            conn,
+            &[("source_monitor", "TEXT"), ("source_dpi", "INTEGER")],
+        )
+    },
+    |conn| add_missing_columns(conn, &[("capture_source", "TEXT")]),
+    |conn| add_missing_columns(conn, &[("label", "TEXT")]),
+    |conn| add_missing_columns(conn, &[("derived_from", "INTEGER")]),
+    |conn| add_missing_columns(conn, &[("image_hash", "INTEGER")]),
+    |conn| add_missing_columns(conn, &[("auto_delete_after_uses", "INTEGER")]),
+    |conn| {
+        add_missing_columns(conn, &[("expires_at", "INTEGER")])?;
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_clipboard_expires_at ON clipboard_items(expires_at) WHERE expires_at IS NOT NULL",
+        )?;
+        Ok(())
+    },
+    |conn| add_missing_columns(conn, &[("drop_effect", "INTEGER")]),
+    |conn| add_missing_columns(conn, &[("image_path", "TEXT")]),
+    |conn| add_missing_columns(conn, &[("checksum", "INTEGER")]),
+    |conn| {
+        add_missing_columns(conn, &[("image_size", "INTEGER")])?;
+        // 之前迁出的图片补记文件大小；没有缩略图的小图顺带留一份给列表，见 externalize_image
+        let rows = conn
+            .prepare("SELECT id, image_path, thumb_data IS NULL FROM clipboard_items WHERE image_path IS NOT NULL AND image_size IS NULL")?
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, bool>(2)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        for (id, rel, no_thumb) in rows {
+            if no_thumb {
+                let Some(bytes) = read_image_file(&rel) else {
+                    continue;
+                };
+                conn.execute(
+                    "UPDATE clipboard_items SET image_size = ?1, thumb_data = ?2 WHERE id = ?3",
+                    params![bytes.len() as i64, bytes, id],
+                )?;
+            } else if let Some(meta) =
+                image_file_path(&rel).and_then(|path| std::fs::metadata(path).ok())
+            {
+                conn.execute(
+                    "UPDATE clipboard_items SET image_size = ?1 WHERE id = ?2",
+                    params![meta.len() as i64, id],
+                )?;
+            }
+        }
+        Ok(())
+    },
+];
+
+/// 当前版本号记在 settings 表的 schema_version，每一步迁移与版本号更新在同一事务内提交
 fn ensure_schema_updates(conn: &Connection) -> Result<(), AppError> {
-    let mut has_path = false;
-    let mut has_icon = false;
+    let current: i64 = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = 'schema_version'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let latest = MIGRATIONS.len() as i64;
+    if current > latest {
+        log::warn!("schema version {current} is newer than supported {latest}");
+        return Ok(());
+    }
+    for (idx, migrate) in MIGRATIONS.iter().enumerate() {
+        let version = idx as i64 + 1;
+        if version <= current {
+            continue;
+        }
+        let tx = conn.unchecked_transaction()?;
+        migrate(&tx)?;
+        tx.execute(
+            "INSERT OR REPLACE INTO settings(key, value) VALUES('schema_version', ?1)",
+            params![version.to_string()],
+        )?;
+        tx.commit()?;
+        log::info!("schema migrated to v{version}");
+    }
+    Ok(())
+}
+
+fn add_missing_columns(conn: &Connection, columns: &[(&str, &str)]) -> Result<(), AppError> {
+    let mut existing = Vec::new();
     let mut stmt = conn.prepare("PRAGMA table_info(clipboard_items)")?;
     let mut rows = stmt.query([])?;
     while let Some(row) = rows.next()? {
         let name: String = row.get(1)?;
-        match name.as_str() {
-            "source_path" => has_path = true,
-            "source_icon" => has_icon = true,
-            _ => {}
-        }
-    }
-    if !has_path {
-        conn.execute("ALTER TABLE clipboard_items ADD COLUMN source_path TEXT", [])?;
+        existing.push(name);
     }
-    if !has_icon {
-        conn.execute("ALTER TABLE clipboard_items ADD COLUMN source_icon BLOB", [])?;
+    for (column, ty) in columns {
+        if !existing.iter().any(|c| c == column) {
+            conn.execute(
+                &format!("ALTER TABLE clipboard_items ADD COLUMN {column} {ty}"),
+                [],
+            )?;
+        }
     }
     Ok(())
 }
 
 fn build_process_info(path: &str) -> ProcessInfo {
+    ProcessInfo {
+        display: display_name_for_path(path),
+        path: path.to_string(),
+        icon_png: extract_icon_png(path),
+        window_title: None,
+        monitor: None,
+        dpi: None,
+        from_owner: false,
+    }
+}
+
+/// 可执行文件的友好名称（不含窗口标题）
+fn display_name_for_path(path: &str) -> String {
     let base = Path::new(path)
         .file_stem()
         .and_then(|s| s.to_str())
         .unwrap_or("?");
+    if let Some(name) = APP_NAME_MAP.lock().get(&base.to_lowercase()) {
+        return name.clone();
+    }
     let friendly_raw = friendly_name_from_path(path);
     let friendly = normalize_display_name(&friendly_raw);
-    let display = if friendly.trim().is_empty() {
+    if friendly.trim().is_empty() {
         map_known_app_name(base)
     } else if friendly.eq_ignore_ascii_case(base) {
         map_known_app_name(base)
     } else {
         friendly
-    };
-    let icon_png = extract_icon_png(path);
-    ProcessInfo {
-        display,
-        path: path.to_string(),
-        icon_png,
     }
 }
 
+/// 标题含未配对的代理项时返回 None：标题会拼进来源名参与黑名单匹配，宁可不带标题也不用替换字符
 fn window_title(hwnd: windows::Win32::Foundation::HWND) -> Option<String> {
     unsafe {
         let len = GetWindowTextLengthW(hwnd);
@@ -241,7 +1204,7 @@ fn window_title(hwnd: windows::Win32::Foundation::HWND) -> Option<String> {
             return None;
         }
         buf.truncate(written as usize);
-        Some(String::from_utf16_lossy(&buf))
+        String::from_utf16(&buf).ok()
     }
 }
 
@@ -260,8 +1223,12 @@ fn friendly_name_from_path(path: &str) -> String {
             SHGFI_DISPLAYNAME,
         );
         let name_u16 = sfi.szDisplayName;
-        let nul = name_u16.iter().position(|&c| c == 0).unwrap_or(name_u16.len());
-        String::from_utf16_lossy(&name_u16[..nul])
+        let nul = name_u16
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(name_u16.len());
+        // 解码失败时返回空串，由调用方退回到 exe 文件名
+        String::from_utf16(&name_u16[..nul]).unwrap_or_default()
     }
 }
 
@@ -277,6 +1244,31 @@ fn normalize_display_name(name: &str) -> String {
     n
 }
 
+/// 读取映射文件并替换当前的自定义映射，返回条目数；路径为空时清空映射
+fn load_app_name_map(path: &str) -> Result<usize, String> {
+    let map: std::collections::HashMap<String, String> = if path.trim().is_empty() {
+        Default::default()
+    } else {
+        let json = std::fs::read_to_string(path.trim())
+            .map_err(|e| format!("无法读取应用名映射文件：{e}"))?;
+        let raw: std::collections::HashMap<String, String> =
+            serde_json::from_str(&json).map_err(|e| format!("应用名映射文件格式错误：{e}"))?;
+        raw.into_iter()
+            .map(|(exe, name)| {
+                let exe = exe.trim().to_lowercase();
+                (
+                    exe.strip_suffix(".exe").unwrap_or(&exe).to_string(),
+                    name.trim().to_string(),
+                )
+            })
+            .filter(|(exe, name)| !exe.is_empty() && !name.is_empty())
+            .collect()
+    };
+    let count = map.len();
+    *APP_NAME_MAP.lock() = map;
+    Ok(count)
+}
+
 fn map_known_app_name(base: &str) -> String {
     let lower = base.to_lowercase();
     let mapped = match lower.as_str() {
@@ -326,62 +1318,23 @@ fn hicon_to_png(icon: HICON) -> Option<Vec<u8>> {
 
         let color: HBITMAP = info.hbmColor;
         let mask: HBITMAP = info.hbmMask;
-        let mut bmp = BITMAP::default();
-        if GetObjectW(color, std::mem::size_of::<BITMAP>() as i32, Some(&mut bmp as *mut _ as *mut c_void)) == 0 {
-            let _ = DeleteObject(color);
-            let _ = DeleteObject(mask);
-            return None;
-        }
-
-        let width = bmp.bmWidth;
-        let height = bmp.bmHeight;
-        if width == 0 || height == 0 {
+        let pixels = if color.is_invalid() {
+            monochrome_icon_rgba(mask)
+        } else {
+            color_bitmap_rgba(color).map(|(width, height, mut pixels, has_alpha)| {
+                if !has_alpha {
+                    apply_icon_mask(mask, width, height, &mut pixels);
+                }
+                (width, height, pixels)
+            })
+        };
+        if !color.is_invalid() {
             let _ = DeleteObject(color);
-            let _ = DeleteObject(mask);
-            return None;
         }
-
-        let hdc = GetDC(None);
-        let mut bi = BITMAPINFO {
-            bmiHeader: BITMAPINFOHEADER {
-                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
-                biWidth: width,
-                biHeight: -height, // top-down
-                biPlanes: 1,
-                biBitCount: 32,
-                biCompression: BI_RGB.0 as u32,
-                biSizeImage: 0,
-                biXPelsPerMeter: 0,
-                biYPelsPerMeter: 0,
-                biClrUsed: 0,
-                biClrImportant: 0,
-            },
-            bmiColors: [Default::default(); 1],
-        };
-
-        let mut pixels = vec![0u8; (width * height * 4) as usize];
-        let res = GetDIBits(
-            hdc,
-            color,
-            0,
-            height as u32,
-            Some(pixels.as_mut_ptr() as *mut c_void),
-            &mut bi,
-            DIB_RGB_COLORS,
-        );
-        let _ = ReleaseDC(None, hdc);
-        let _ = DeleteObject(color);
         let _ = DeleteObject(mask);
-        if res == 0 {
-            return None;
-        }
-
-        // GetDIBits returns BGRA; swap B/R to RGBA for image crate
-        for px in pixels.chunks_exact_mut(4) {
-            px.swap(0, 2);
-        }
+        let (width, height, pixels) = pixels?;
 
-        let img = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width as u32, height as u32, pixels)?;
+        let img = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width, height, pixels)?;
         let mut cursor = Cursor::new(Vec::new());
         image::DynamicImage::ImageRgba8(img)
             .write_to(&mut cursor, image::ImageOutputFormat::Png)
@@ -390,625 +1343,6312 @@ fn hicon_to_png(icon: HICON) -> Option<Vec<u8>> {
     }
 }
 
-fn process_info_from_foreground() -> Option<ProcessInfo> {
-    unsafe {
-        let hwnd = GetForegroundWindow();
-        if hwnd.0 == 0 {
-            return None;
-        }
-        let mut pid = 0u32;
-        // signature expects Option<*mut u32>
-        GetWindowThreadProcessId(hwnd, Some(&mut pid));
-        if pid == 0 {
-            return None;
-        }
-        let handle = match OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) {
-            Ok(h) => h,
-            Err(_) => return None,
-        };
+/// 读出图标颜色位图的 RGBA 像素及其是否自带 alpha。原位图不是 32 位（16/24 位等）时没有 alpha 通道，先按不透明处理
+unsafe fn color_bitmap_rgba(color: HBITMAP) -> Option<(u32, u32, Vec<u8>, bool)> {
+    let mut bmp = BITMAP::default();
+    if GetObjectW(
+        color,
+        std::mem::size_of::<BITMAP>() as i32,
+        Some(&mut bmp as *mut _ as *mut c_void),
+    ) == 0
+    {
+        return None;
+    }
+    let width = u32::try_from(bmp.bmWidth).ok().filter(|w| *w > 0)?;
+    let height = u32::try_from(bmp.bmHeight).ok().filter(|h| *h > 0)?;
+    let mut pixels = read_bitmap_bgra(color, width, height)?;
+    let has_alpha = bgra_to_rgba(&mut pixels, bmp.bmBitsPixel);
+    Some((width, height, pixels, has_alpha))
+}
 
-        let mut buf = [0u16; 260];
-        let mut len = buf.len() as u32;
-        let result = QueryFullProcessImageNameW(
-            handle,
-            PROCESS_NAME_FORMAT(0),
-            PWSTR(buf.as_mut_ptr()),
-            &mut len,
-        );
-        let _ = CloseHandle(handle);
-        if result.is_err() || len == 0 {
-            return None;
+/// GetDIBits 读出的 BGRA 原地转为 RGBA，返回是否自带 alpha。只有原位图为 32 位且 alpha 不全为 0 才算，
+/// 否则第四个字节是填充或全透明，一律改为不透明
+fn bgra_to_rgba(pixels: &mut [u8], bits_per_pixel: u16) -> bool {
+    let has_alpha = bits_per_pixel == 32 && pixels.chunks_exact(4).any(|px| px[3] != 0);
+    for px in pixels.chunks_exact_mut(4) {
+        px.swap(0, 2);
+        if !has_alpha {
+            px[3] = 255;
         }
+    }
+    has_alpha
+}
 
-        let path = String::from_utf16_lossy(&buf[..len as usize]);
-        if path.is_empty() {
-            return None;
-        }
+/// 用 AND 掩码补透明度，读不到掩码时保持不透明
+unsafe fn apply_icon_mask(mask: HBITMAP, width: u32, height: u32, pixels: &mut [u8]) {
+    if let Some(bits) = read_bitmap_mono(mask, width, height) {
+        apply_mask_bits(&bits, width, pixels);
+    }
+}
+
+/// 1 位位图每行补齐到 4 字节
+fn mono_stride(width: u32) -> usize {
+    (width as usize).div_ceil(32) * 4
+}
 
-        let title = window_title(hwnd);
-        let mut info = build_process_info(&path);
-        if let Some(t) = title {
-            // combine window title with app name for more context (e.g., webpage title)
-            if !t.trim().is_empty() && t != info.display {
-                info.display = format!("{} ({})", t, info.display);
+/// 把 1 位 AND 掩码（自上而下，高位在左，行按 mono_stride 对齐）写进 RGBA 的 alpha：
+/// 位为 1 的像素透明，其余不变。掩码行数不够时后面的行保持原样
+fn apply_mask_bits(mask: &[u8], width: u32, pixels: &mut [u8]) {
+    let stride = mono_stride(width);
+    for (row, bits) in pixels
+        .chunks_exact_mut(width as usize * 4)
+        .zip(mask.chunks_exact(stride))
+    {
+        for (x, px) in row.chunks_exact_mut(4).enumerate() {
+            if bits[x / 8] & (0x80 >> (x % 8)) != 0 {
+                px[3] = 0;
             }
         }
-        Some(info)
     }
 }
 
-fn read_clipboard(db_path: &PathBuf, state: &AppState) -> Result<Option<ClipboardDto>, AppError> {
-    let settings = state.settings.lock().clone();
-    let proc_info = process_info_from_foreground();
-    if let Some(app) = &proc_info {
-        if settings.blacklist.iter().any(|b| b.eq_ignore_ascii_case(&app.display)) {
-            return Ok(None);
+/// 单色图标的掩码转 RGBA：下半 XOR 位图的位为 1 是白色、为 0 是黑色，再由上半 AND 掩码决定透明
+fn monochrome_mask_rgba(raw: &[u8], width: u32, height: u32) -> Option<Vec<u8>> {
+    let half = mono_stride(width).checked_mul(height as usize)?;
+    let and_mask = raw.get(..half)?;
+    let xor = raw.get(half..half * 2)?;
+    let mut pixels = vec![0u8; (width as usize * 4).checked_mul(height as usize)?];
+    let opaque_colors = xor.chunks_exact(mono_stride(width));
+    for (row, bits) in pixels
+        .chunks_exact_mut(width as usize * 4)
+        .zip(opaque_colors)
+    {
+        for (x, px) in row.chunks_exact_mut(4).enumerate() {
+            let value = if bits[x / 8] & (0x80 >> (x % 8)) != 0 {
+                255
+            } else {
+                0
+            };
+            px.copy_from_slice(&[value, value, value, 255]);
         }
     }
+    apply_mask_bits(and_mask, width, &mut pixels);
+    Some(pixels)
+}
 
-    let mut clipboard = Clipboard::new().map_err(|e| AppError::Clipboard(format!("{e}")))?;
-    if let Ok(text) = clipboard.get_text() {
-        let trimmed = text.trim();
-        if trimmed.is_empty() {
-            return Ok(None);
-        }
-        let item = ClipboardItem {
-            id: 0,
-            content_type: "text".into(),
-            text_content: Some(text.clone()),
-            image_data: None,
-            source_app: proc_info.as_ref().map(|p| p.display.clone()),
-            source_path: proc_info.as_ref().map(|p| p.path.clone()),
-            source_icon: proc_info.and_then(|p| p.icon_png),
-            created_at: chrono::Utc::now().timestamp_millis(),
-            is_pinned: false,
-            usage_count: 0,
-        };
-        if !is_duplicate(db_path, &item)? {
-            let saved = insert_item(db_path, item, settings.max_history)?;
-            return Ok(Some(saved));
-        }
-        return Ok(None);
+/// 单色图标没有颜色位图，掩码高度为图标的两倍：上半是 AND 掩码，下半是 XOR（颜色）位图
+unsafe fn monochrome_icon_rgba(mask: HBITMAP) -> Option<(u32, u32, Vec<u8>)> {
+    let mut bmp = BITMAP::default();
+    if GetObjectW(
+        mask,
+        std::mem::size_of::<BITMAP>() as i32,
+        Some(&mut bmp as *mut _ as *mut c_void),
+    ) == 0
+    {
+        return None;
     }
+    let width = u32::try_from(bmp.bmWidth).ok().filter(|w| *w > 0)?;
+    let full_height = u32::try_from(bmp.bmHeight).ok().filter(|h| *h >= 2)?;
+    let height = full_height / 2;
+    let raw = read_bitmap_mono(mask, width, height * 2)?;
+    let pixels = monochrome_mask_rgba(&raw, width, height)?;
+    Some((width, height, pixels))
+}
 
-    if settings.record_images {
-        if let Ok(img) = clipboard.get_image() {
-            let buffer: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::from_raw(
-                img.width as u32,
-                img.height as u32,
-                img.bytes.into_owned(),
-            )
-            .ok_or_else(|| AppError::Other("无法读取图片数据".into()))?;
-            let mut cursor = Cursor::new(Vec::new());
-            {
-                let img_dyn = image::DynamicImage::ImageRgba8(buffer);
-                img_dyn
-                    .write_to(&mut cursor, image::ImageOutputFormat::Png)
-                    .map_err(|e| AppError::Other(e.to_string()))?;
-            }
-            let png_bytes = cursor.into_inner();
-            let item = ClipboardItem {
-                id: 0,
-                content_type: "image".into(),
-            text_content: None,
-            image_data: Some(png_bytes),
-            source_app: proc_info.as_ref().map(|p| p.display.clone()),
-            source_path: proc_info.as_ref().map(|p| p.path.clone()),
-            source_icon: proc_info.and_then(|p| p.icon_png),
-            created_at: chrono::Utc::now().timestamp_millis(),
-            is_pinned: false,
-            usage_count: 0,
-        };
-            if !is_duplicate(db_path, &item)? {
-                let saved = insert_item(db_path, item, settings.max_history)?;
-                return Ok(Some(saved));
-            }
-            return Ok(None);
-        }
-    }
+/// 1 位 DIB 的颜色表有两项，BITMAPINFO 只留了一项的位置
+#[repr(C)]
+struct MonoBitmapInfo {
+    header: BITMAPINFOHEADER,
+    colors: [RGBQUAD; 2],
+}
 
-    Ok(None)
+/// 按 1 位自上而下读出掩码位图的原始位，行按 mono_stride 对齐；实际读到的行数不足时视为失败
+unsafe fn read_bitmap_mono(bitmap: HBITMAP, width: u32, height: u32) -> Option<Vec<u8>> {
+    let len = mono_stride(width).checked_mul(height as usize)?;
+    let mut info = MonoBitmapInfo {
+        header: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width as i32,
+            biHeight: -(height as i32), // top-down
+            biPlanes: 1,
+            biBitCount: 1,
+            biCompression: BI_RGB.0,
+            biSizeImage: 0,
+            biXPelsPerMeter: 0,
+            biYPelsPerMeter: 0,
+            biClrUsed: 0,
+            biClrImportant: 0,
+        },
+        colors: Default::default(),
+    };
+    let mut bits = vec![0u8; len];
+    let hdc = GetDC(None);
+    let lines = GetDIBits(
+        hdc,
+        bitmap,
+        0,
+        height,
+        Some(bits.as_mut_ptr() as *mut c_void),
+        &mut info as *mut MonoBitmapInfo as *mut BITMAPINFO,
+        DIB_RGB_COLORS,
+    );
+    let _ = ReleaseDC(None, hdc);
+    (lines == height as i32).then_some(bits)
 }
 
-fn insert_item(db_path: &PathBuf, mut item: ClipboardItem, max: i64) -> Result<ClipboardDto, AppError> {
-    let conn = Connection::open(db_path)?;
-    conn.execute(
-        "INSERT INTO clipboard_items (content_type, text_content, image_data, source_app, source_path, source_icon, created_at, is_pinned, usage_count)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 0)",
-        params![
-            item.content_type,
-            item.text_content,
-            item.image_data,
-            item.source_app,
-            item.source_path,
-            item.source_icon,
-            item.created_at,
-            item.is_pinned as i32
-        ],
-    )?;
-    item.id = conn.last_insert_rowid();
-    enforce_limit(db_path, max)?;
-    Ok(to_dto(item))
+/// 不论位图原本的位深，统一请求 32 位自上而下的 BGRA，由 GetDIBits 负责转换；
+/// 实际读到的行数不足时视为失败
+unsafe fn read_bitmap_bgra(bitmap: HBITMAP, width: u32, height: u32) -> Option<Vec<u8>> {
+    let len = (width as usize)
+        .checked_mul(height as usize)?
+        .checked_mul(4)?;
+    let mut bi = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width as i32,
+            biHeight: -(height as i32), // top-down
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0 as u32,
+            biSizeImage: 0,
+            biXPelsPerMeter: 0,
+            biYPelsPerMeter: 0,
+            biClrUsed: 0,
+            biClrImportant: 0,
+        },
+        bmiColors: [Default::default(); 1],
+    };
+    let mut pixels = vec![0u8; len];
+    let hdc = GetDC(None);
+    let lines = GetDIBits(
+        hdc,
+        bitmap,
+        0,
+        height,
+        Some(pixels.as_mut_ptr() as *mut c_void),
+        &mut bi,
+        DIB_RGB_COLORS,
+    );
+    let _ = ReleaseDC(None, hdc);
+    (lines == height as i32).then_some(pixels)
 }
 
-fn is_duplicate(db_path: &PathBuf, item: &ClipboardItem) -> Result<bool, AppError> {
-    let conn = Connection::open(db_path)?;
-    let last: Option<(String, Option<String>, Option<Vec<u8>>)> = conn
-        .query_row(
-            "SELECT content_type, text_content, image_data FROM clipboard_items ORDER BY created_at DESC LIMIT 1",
-            [],
-            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
-        )
-        .optional()?;
-    if let Some((ctype, text, image)) = last {
-        if ctype == item.content_type {
-            if ctype == "text" {
-                return Ok(text == item.text_content);
-            } else {
-                return Ok(image.as_ref().map(|v| v.len()) == item.image_data.as_ref().map(|v| v.len()));
-            }
-        }
-    }
-    Ok(false)
+fn process_info_from_foreground(include_title: bool) -> Option<ProcessInfo> {
+    process_info_from_window(unsafe { GetForegroundWindow() }, include_title)
 }
 
-fn to_dto(item: ClipboardItem) -> ClipboardDto {
-    let image_thumb = item
-        .image_data
-        .as_ref()
-        .map(|bytes| format!("data:image/png;base64,{}", BASE64.encode(bytes)));
-    let source_icon = item
-        .source_icon
-        .as_ref()
-        .map(|bytes| format!("data:image/png;base64,{}", BASE64.encode(bytes)));
-    ClipboardDto {
-        id: item.id,
-        content_type: item.content_type,
-        text_content: item.text_content,
-        image_thumb,
-        source_app: item.source_app,
-        source_icon,
-        created_at: item.created_at,
-        is_pinned: item.is_pinned,
-        usage_count: item.usage_count,
+/// 采集时的来源：开启 prefer_clipboard_owner 且所有者窗口属于另一个可解析的进程时用它，否则用前台窗口
+fn process_info_for_capture(settings: &Settings) -> Option<ProcessInfo> {
+    let foreground = process_info_from_foreground(settings.capture_window_titles);
+    if !settings.prefer_clipboard_owner {
+        return foreground;
+    }
+    let owner = unsafe { GetClipboardOwner() };
+    match process_info_from_window(owner, settings.capture_window_titles) {
+        Some(mut info)
+            if foreground
+                .as_ref()
+                .is_none_or(|fg| !fg.path.eq_ignore_ascii_case(&info.path)) =>
+        {
+            info.from_owner = true;
+            Some(info)
+        }
+        _ => foreground,
     }
 }
 
-#[tauri::command]
-fn get_history(
-    state: State<AppState>,
-    query: Option<String>,
-    type_filter: Option<String>,
-    time_filter: Option<String>,
-    source_filter: Option<String>,
-) -> Result<Vec<ClipboardDto>, String> {
-    let db_path = &state.db_path;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-    let mut sql = String::from("SELECT id, content_type, text_content, image_data, source_app, source_path, source_icon, created_at, is_pinned, usage_count FROM clipboard_items WHERE 1=1");
-    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-
-    if let Some(q) = query.clone() {
-        if !q.trim().is_empty() {
-            sql.push_str(" AND text_content LIKE ?");
-            params_vec.push(Box::new(format!("%{}%", q)));
-        }
+fn process_info_from_window(
+    hwnd: windows::Win32::Foundation::HWND,
+    include_title: bool,
+) -> Option<ProcessInfo> {
+    if hwnd.0 == 0 {
+        return None;
     }
-    if let Some(t) = type_filter {
-        if t == "text" || t == "image" {
-            sql.push_str(" AND content_type = ?");
-            params_vec.push(Box::new(t));
+    let wide = window_process_path(hwnd)?;
+
+    // 标题只用于隐私窗口判断时不写进来源名
+    let title = window_title(hwnd);
+    let mut info = match String::from_utf16(&wide) {
+        Ok(path) => build_process_info(&path),
+        Err(_) => lossy_process_info(&wide),
+    };
+    if let Some(t) = title.as_ref().filter(|_| include_title) {
+        // combine window title with app name for more context (e.g., webpage title)
+        if !t.trim().is_empty() && *t != info.display {
+            info.display = format!("{} ({})", t, info.display);
         }
     }
-    if let Some(sf) = source_filter {
-        if !sf.is_empty() {
-            sql.push_str(" AND source_app = ?");
-            params_vec.push(Box::new(sf));
+    info.window_title = title;
+    (info.monitor, info.dpi) = window_display_context(hwnd);
+    Some(info)
+}
+
+/// 窗口所属进程的完整 exe 路径（未解码的 UTF-16）
+fn window_process_path(hwnd: windows::Win32::Foundation::HWND) -> Option<Vec<u16>> {
+    unsafe {
+        let mut pid = 0u32;
+        // signature expects Option<*mut u32>
+        GetWindowThreadProcessId(hwnd, Some(&mut pid));
+        if pid == 0 {
+            return None;
         }
-    }
-    if let Some(tf) = time_filter {
-        let now = Local::now();
-        let today_local = now.date_naive();
-        let today_start = Local
-            .from_local_datetime(&today_local.and_hms_opt(0, 0, 0).unwrap())
-            .single()
-            .unwrap()
-            .with_timezone(&chrono::Utc)
-            .timestamp_millis();
-        let yesterday_start = Local
-            .from_local_datetime(&(today_local - ChronoDuration::days(1)).and_hms_opt(0, 0, 0).unwrap())
-            .single()
-            .unwrap()
-            .with_timezone(&chrono::Utc)
-            .timestamp_millis();
-        match tf.as_str() {
-            "today" => {
-                sql.push_str(" AND created_at >= ?");
-                params_vec.push(Box::new(today_start));
-            }
-            "yesterday" => {
-                sql.push_str(" AND created_at >= ? AND created_at < ?");
-                params_vec.push(Box::new(yesterday_start));
-                params_vec.push(Box::new(today_start));
-            }
-            "earlier" => {
-                sql.push_str(" AND created_at < ?");
-                params_vec.push(Box::new(yesterday_start));
-            }
-            _ => {}
+        let handle = match OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) {
+            Ok(h) => h,
+            Err(_) => return None,
+        };
+
+        let mut buf = [0u16; 260];
+        let mut len = buf.len() as u32;
+        let result = QueryFullProcessImageNameW(
+            handle,
+            PROCESS_NAME_FORMAT(0),
+            PWSTR(buf.as_mut_ptr()),
+            &mut len,
+        );
+        let _ = CloseHandle(handle);
+        if result.is_err() || len == 0 {
+            return None;
         }
+
+        Some(buf[..len as usize].to_vec())
     }
+}
 
-    sql.push_str(" ORDER BY is_pinned DESC, created_at DESC LIMIT 500");
-    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
-    let mut rows = stmt
-        .query(rusqlite::params_from_iter(params_vec.iter().map(|v| &**v)))
-        .map_err(|e| e.to_string())?;
-    let mut result = Vec::new();
-    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
-        let item = ClipboardItem {
-            id: row.get(0).map_err(|e| e.to_string())?,
-            content_type: row.get(1).map_err(|e| e.to_string())?,
-            text_content: row.get(2).map_err(|e| e.to_string())?,
-            image_data: row.get(3).map_err(|e| e.to_string())?,
-            source_app: row.get(4).map_err(|e| e.to_string())?,
-            source_path: row.get(5).map_err(|e| e.to_string())?,
-            source_icon: row.get(6).map_err(|e| e.to_string())?,
-            created_at: row.get(7).map_err(|e| e.to_string())?,
-            is_pinned: row.get::<_, i32>(8).map_err(|e| e.to_string())? != 0,
-            usage_count: row.get(9).map_err(|e| e.to_string())?,
-        };
-        result.push(to_dto(item));
+/// 路径含未配对代理项时，来源名只按单独解码的 exe 文件名确定，保存的路径仅作展示
+fn lossy_process_info(wide: &[u16]) -> ProcessInfo {
+    ProcessInfo {
+        display: display_name_from_wide(wide, &APP_NAME_MAP.lock()),
+        path: String::from_utf16_lossy(wide),
+        icon_png: None,
+        window_title: None,
+        monitor: None,
+        dpi: None,
+        from_owner: false,
     }
-    Ok(result)
 }
 
-#[tauri::command]
-fn delete_entry(state: State<AppState>, id: i64) -> Result<(), String> {
-    let conn = Connection::open(&state.db_path).map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM clipboard_items WHERE id = ?1", params![id])
-        .map_err(|e| e.to_string())?;
-    Ok(())
+/// 按 exe 文件名查自定义映射，再退回内置名称；文件名本身无法解码时显示 "?"
+fn display_name_from_wide(
+    wide: &[u16],
+    names: &std::collections::HashMap<String, String>,
+) -> String {
+    match exe_stem_from_wide(wide) {
+        Some(stem) => names
+            .get(&stem.to_lowercase())
+            .cloned()
+            .unwrap_or_else(|| map_known_app_name(&stem)),
+        None => "?".into(),
+    }
 }
 
-#[tauri::command]
-fn toggle_pin(state: State<AppState>, id: i64) -> Result<(), String> {
-    let conn = Connection::open(&state.db_path).map_err(|e| e.to_string())?;
-    conn.execute(
-        "UPDATE clipboard_items SET is_pinned = CASE is_pinned WHEN 1 THEN 0 ELSE 1 END WHERE id = ?1",
-        params![id],
-    )
-    .map_err(|e| e.to_string())?;
-    Ok(())
+/// 从宽字符路径中单独解码 exe 文件名（不含 .exe）；目录部分有无效字符时仍能得到可靠的名称
+fn exe_stem_from_wide(wide: &[u16]) -> Option<String> {
+    let start = wide
+        .iter()
+        .rposition(|&c| c == u16::from(b'\\') || c == u16::from(b'/'))
+        .map_or(0, |i| i + 1);
+    let name = String::from_utf16(&wide[start..]).ok()?;
+    let stem = Path::new(&name).file_stem()?.to_str()?.to_string();
+    Some(stem).filter(|s| !s.is_empty())
 }
 
-#[tauri::command]
-fn paste_entry(state: State<AppState>, id: i64, plain: bool) -> Result<(), String> {
-    let conn = Connection::open(&state.db_path).map_err(|e| e.to_string())?;
-    let item: ClipboardItem = conn
-        .query_row(
-            "SELECT id, content_type, text_content, image_data, source_app, source_path, source_icon, created_at, is_pinned, usage_count FROM clipboard_items WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(ClipboardItem {
-                    id: row.get(0)?,
-                    content_type: row.get(1)?,
-                    text_content: row.get(2)?,
-                    image_data: row.get(3)?,
-                    source_app: row.get(4)?,
-                    source_path: row.get(5)?,
-                    source_icon: row.get(6)?,
-                    created_at: row.get(7)?,
-                    is_pinned: row.get::<_, i32>(8)? != 0,
-                    usage_count: row.get(9)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
+/// 远程桌面会话里剪贴板由 rdpclip.exe 代为持有，内容要等首次读取时才从另一端取回
+fn clipboard_owned_by_rdpclip() -> bool {
+    let owner = unsafe { GetClipboardOwner() };
+    if owner.0 == 0 {
+        return false;
+    }
+    window_process_path(owner)
+        .and_then(|wide| exe_stem_from_wide(&wide))
+        .is_some_and(|stem| stem.eq_ignore_ascii_case("rdpclip"))
+}
 
-    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
-    // Avoid recording this paste as a new history entry in watcher
-    let now_ms = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_err(|e| e.to_string())?
-        .as_millis() as u64;
-    SKIP_UNTIL_MS.store(now_ms + 1200, Ordering::SeqCst);
-    if item.content_type == "text" {
-        let text = item.text_content.unwrap_or_default();
-        let final_text = if plain {
-            clean_plain_text(&text)
+/// 前台窗口所在显示器的设备名（如 \\.\DISPLAY2）与 DPI；查询失败的一项返回 None
+fn window_display_context(hwnd: windows::Win32::Foundation::HWND) -> (Option<String>, Option<u32>) {
+    unsafe {
+        let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONULL);
+        let name = if monitor.is_invalid() {
+            None
         } else {
-            text.clone()
-        };
-        clipboard
-            .set_text(final_text)
-            .map_err(|e| e.to_string())?;
-    } else if let Some(img_bytes) = item.image_data {
-        let png = image::load_from_memory(&img_bytes).map_err(|e| e.to_string())?;
-        let rgba = png.to_rgba8();
-        let (w, h) = rgba.dimensions();
-        let img_data = arboard::ImageData {
-            width: w as usize,
-            height: h as usize,
-            bytes: std::borrow::Cow::Owned(rgba.into_raw()),
+            let mut info = MONITORINFOEXW::default();
+            info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+            if GetMonitorInfoW(monitor, &mut info.monitorInfo).as_bool() {
+                let len = info
+                    .szDevice
+                    .iter()
+                    .position(|&c| c == 0)
+                    .unwrap_or(info.szDevice.len());
+                Some(String::from_utf16_lossy(&info.szDevice[..len])).filter(|n| !n.is_empty())
+            } else {
+                None
+            }
         };
-        clipboard.set_image(img_data).map_err(|e| e.to_string())?;
+        // 早于 Win10 1607 或窗口已关闭时返回 0
+        let dpi = Some(GetDpiForWindow(hwnd)).filter(|&d| d > 0);
+        (name, dpi)
+    }
+}
+
+/// 仅凭窗口标题判断浏览器隐私窗口：各浏览器会在标题末尾附加 InPrivate/Incognito 等标记。
+/// 标题被网页改写、浏览器语言不在列表内或浏览器不显示标记时无法识别。
+fn is_private_window_title(title: &str) -> bool {
+    const MARKERS: [&str; 8] = [
+        "inprivate",
+        "incognito",
+        "private browsing",
+        "privates surfen",
+        "navigation privée",
+        "隐身",
+        "无痕",
+        "私密浏览",
+    ];
+    let lower = title.to_lowercase();
+    MARKERS.iter().any(|m| lower.contains(m))
+}
+
+/// 浏览器复制时会附带 CF_HTML（"HTML Format"），其头部的 SourceURL 即来源页面
+fn read_clipboard_source_url() -> Option<String> {
+    let format = unsafe { RegisterClipboardFormatW(w!("HTML Format")) };
+    if format == 0 {
+        return None;
     }
+    let bytes = read_clipboard_bytes(format)?;
+    parse_source_url(&String::from_utf8_lossy(&bytes))
+}
 
+/// 读取指定格式的剪贴板原始字节
+fn read_clipboard_bytes(format: u32) -> Option<Vec<u8>> {
     unsafe {
-        simulate_paste(plain).map_err(|e| e.to_string())?;
+        if OpenClipboard(None).is_err() {
+            return None;
+        }
+        let bytes = GetClipboardData(format).ok().and_then(|handle| {
+            let hglobal = HGLOBAL(handle.0 as *mut c_void);
+            let ptr = GlobalLock(hglobal) as *const u8;
+            if ptr.is_null() {
+                return None;
+            }
+            let size = GlobalSize(hglobal);
+            let bytes = std::slice::from_raw_parts(ptr, size).to_vec();
+            let _ = GlobalUnlock(hglobal);
+            Some(bytes)
+        });
+        let _ = CloseClipboard();
+        bytes
     }
+}
 
-    conn.execute(
-        "UPDATE clipboard_items SET usage_count = usage_count + 1 WHERE id = ?1",
-        params![id],
-    )
-    .map_err(|e| e.to_string())?;
-    Ok(())
+/// 优先取 CF_DIBV5（带 alpha 掩码与色彩空间信息），没有再取 CF_DIB
+fn read_clipboard_dib() -> Option<Vec<u8>> {
+    read_clipboard_bytes(CF_DIBV5).or_else(|| read_clipboard_bytes(CF_DIB))
 }
 
-fn clean_plain_text(input: &str) -> String {
-    // 轻量“纯文本”处理：去掉常见 Markdown 强调/列表/引用标记与多余空格
-    let mut result = String::with_capacity(input.len());
-    for (idx, line) in input.lines().enumerate() {
-        let mut l = line.trim_start();
+/// 按头部大小判断是 BITMAPV5HEADER 还是普通 DIB，原样写回剪贴板
+fn write_clipboard_dib(bytes: &[u8]) -> Result<(), String> {
+    let header_size = bytes
+        .get(..4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| "DIB 数据不完整".to_string())?;
+    let format = if header_size >= 124 { CF_DIBV5 } else { CF_DIB };
+    write_clipboard_formats(&[(format, bytes)])
+}
 
-        // 标题 #
-        while let Some(rest) = l.strip_prefix('#') {
-            l = rest.trim_start();
+/// 清空剪贴板后一次写入多种格式的原始字节
+fn write_clipboard_formats(formats: &[(u32, &[u8])]) -> Result<(), String> {
+    unsafe {
+        OpenClipboard(None).map_err(|e| e.to_string())?;
+        let result = (|| -> Result<(), String> {
+            EmptyClipboard().map_err(|e| e.to_string())?;
+            for (format, bytes) in formats {
+                let hglobal = GlobalAlloc(GMEM_MOVEABLE, bytes.len()).map_err(|e| e.to_string())?;
+                let ptr = GlobalLock(hglobal) as *mut u8;
+                if ptr.is_null() {
+                    let _ = GlobalFree(hglobal);
+                    return Err("无法锁定剪贴板内存".into());
+                }
+                std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+                let _ = GlobalUnlock(hglobal);
+                // 成功后内存归系统所有，失败才需要自行释放
+                if let Err(err) = SetClipboardData(*format, HANDLE(hglobal.0 as isize)) {
+                    let _ = GlobalFree(hglobal);
+                    return Err(err.to_string());
+                }
+            }
+            Ok(())
+        })();
+        let _ = CloseClipboard();
+        result
+    }
+}
+
+/// 32 位 BI_RGB 的 CF_DIB：BITMAPINFOHEADER + 自下而上的 BGRA 行，alpha 通道多数程序会忽略
+fn encode_dib(img: &image::RgbaImage) -> Vec<u8> {
+    let (w, h) = img.dimensions();
+    let mut out = Vec::with_capacity(40 + (w * h * 4) as usize);
+    out.extend_from_slice(&40u32.to_le_bytes());
+    out.extend_from_slice(&(w as i32).to_le_bytes());
+    out.extend_from_slice(&(h as i32).to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // planes
+    out.extend_from_slice(&32u16.to_le_bytes()); // bit count
+    out.extend_from_slice(&0u32.to_le_bytes()); // BI_RGB
+    out.extend_from_slice(&(w * h * 4).to_le_bytes());
+    out.extend_from_slice(&[0u8; 16]); // 分辨率与调色板均为 0
+    for y in (0..h).rev() {
+        for x in 0..w {
+            let [r, g, b, a] = img.get_pixel(x, y).0;
+            out.extend_from_slice(&[b, g, r, a]);
         }
+    }
+    out
+}
 
-        // 列表、引用前缀
-        for prefix in ["- ", "* ", "+ ", "> ", "• ", "– "] {
-            if let Some(rest) = l.strip_prefix(prefix) {
-                l = rest.trim_start();
-                break;
-            }
+/// 按 paste_image_format 写入图片：png 写注册格式 "PNG" 并附带 DIB，bmp 只写 DIB
+fn write_clipboard_image_as(
+    clipboard: &mut Clipboard,
+    stored: &[u8],
+    format: &str,
+) -> Result<(), String> {
+    if format == "preserve" {
+        return set_clipboard_png(clipboard, stored);
+    }
+    let img = image::load_from_memory(stored).map_err(|e| e.to_string())?;
+    let dib = encode_dib(&img.to_rgba8());
+    if format == "bmp" {
+        return write_clipboard_formats(&[(CF_DIB, &dib)]);
+    }
+    let mut cursor = Cursor::new(Vec::new());
+    img.write_to(&mut cursor, image::ImageOutputFormat::Png)
+        .map_err(|e| e.to_string())?;
+    let png_format = unsafe { RegisterClipboardFormatW(w!("PNG")) };
+    if png_format == 0 {
+        return Err("无法注册 PNG 剪贴板格式".into());
+    }
+    write_clipboard_formats(&[(png_format, &cursor.into_inner()), (CF_DIB, &dib)])
+}
+
+fn parse_source_url(html: &str) -> Option<String> {
+    // 头部为 "Key:Value" 行，遇到正文 "<" 即结束
+    for line in html.lines() {
+        let line = line.trim();
+        if line.starts_with('<') {
+            break;
         }
-        if let Some(stripped) = l
-            .trim_start()
-            .strip_prefix(|c: char| c.is_ascii_digit())
-            .and_then(|s| s.strip_prefix('.'))
-        {
-            l = stripped.trim_start();
+        if let Some(url) = line.strip_prefix("SourceURL:") {
+            let url = url.trim();
+            if !url.is_empty() && !url.eq_ignore_ascii_case("about:blank") {
+                return Some(url.to_string());
+            }
         }
+    }
+    None
+}
 
-        // 去掉强调/代码符号
-        let mut cleaned = l
-            .replace("**", "")
-            .replace("__", "")
-            .replace('*', "")
-            .replace('_', "")
-            .replace('`', "");
+const MAX_BLACKLIST_ENTRIES: usize = 500;
 
-        // 合并多空格
-        while cleaned.contains("  ") {
-            cleaned = cleaned.replace("  ", " ");
-        }
+fn fold_app_name(name: &str) -> String {
+    name.trim().to_lowercase()
+}
 
-        if idx > 0 {
-            result.push('\n');
+/// 去空白、统一小写并去重，保留首次出现的顺序
+fn normalize_blacklist(list: &[String]) -> Result<Vec<String>, String> {
+    let mut out: Vec<String> = Vec::new();
+    for name in list.iter().map(|n| fold_app_name(n)) {
+        if !name.is_empty() && !out.contains(&name) {
+            out.push(name);
         }
-        result.push_str(cleaned.trim_end());
     }
-    result
+    if out.len() > MAX_BLACKLIST_ENTRIES {
+        return Err(format!("黑名单最多 {MAX_BLACKLIST_ENTRIES} 项"));
+    }
+    Ok(out)
+}
+
+/// 旧版本保存的黑名单可能未规范化，匹配时两侧都折叠一次
+fn blacklist_contains(list: &[String], app: &str) -> bool {
+    let app = fold_app_name(app);
+    list.iter().any(|b| fold_app_name(b) == app)
+}
+
+fn read_clipboard(db_path: &Path, state: &AppState) -> Result<Option<ClipboardDto>, AppError> {
+    let settings = state.settings.lock().clone();
+    let stats = &state.stats;
+    let proc_info = process_info_for_capture(&settings);
+    if let Some(app) = &proc_info {
+        CaptureStats::bump(if app.from_owner {
+            &stats.source_from_owner
+        } else {
+            &stats.source_from_foreground
+        });
+        if blacklist_contains(&settings.blacklist, &app.display) {
+            CaptureStats::bump(&stats.skipped_blacklist);
+            return Ok(None);
+        }
+        if settings.ignore_private_windows
+            && app
+                .window_title
+                .as_deref()
+                .is_some_and(is_private_window_title)
+        {
+            CaptureStats::bump(&stats.skipped_private);
+            return Ok(None);
+        }
+    }
+
+    let mut captured = capture_clipboard_item(&settings, proc_info.clone())?;
+    // rdpclip 转发的数据可能还没取回来，第一次读到空内容时稍等重试，避免这次复制被当成空内容丢掉
+    if captured.is_none() && settings.rdp_clipboard_retry && clipboard_owned_by_rdpclip() {
+        for attempt in 1..=RDP_RETRY_ATTEMPTS {
+            thread::sleep(Duration::from_millis(RDP_RETRY_DELAY_MS));
+            captured = capture_clipboard_item(&settings, proc_info.clone())?;
+            if captured.is_some() {
+                log::info!("rdp clipboard ready after {attempt} retries");
+                break;
+            }
+        }
+    }
+    let Some(mut item) = captured else {
+        CaptureStats::bump(&stats.skipped_empty);
+        return Ok(None);
+    };
+    if !settings.capture_transforms.is_empty()
+        && item.content_type != "image"
+        && item.content_type != "unsupported"
+    {
+        let original = item.text_content.take().unwrap_or_default();
+        let transformed = apply_capture_transforms(&original, &settings.capture_transforms);
+        if transformed.trim().is_empty() {
+            CaptureStats::bump(&stats.skipped_empty);
+            return Ok(None);
+        }
+        if transformed != original {
+            item.content_type = text_content_type(&transformed).into();
+            item.raw_text = item.raw_text.or(Some(original));
+            item.text_content = Some(transformed);
+        } else {
+            item.text_content = Some(original);
+        }
+    }
+    // both：文本之外同时有图片时，图片先存一条，文本作为最新一条返回
+    if settings.capture_preference == "both" && item.content_type != "image" {
+        let mut clipboard = Clipboard::new().map_err(|e| AppError::Clipboard(format!("{e}")))?;
+        if let Some(mut image) = capture_image_item(&mut clipboard, &settings, proc_info)? {
+            image.created_at = image.created_at.min(item.created_at) - 1;
+            if let Some(dto) = save_captured(db_path, image, &settings, stats)? {
+                if let Some(app) = APP_HANDLE.get() {
+                    let _ = app.emit_all("clipboard://new", dto);
+                }
+            }
+        }
+    }
+    save_captured(db_path, item, &settings, stats)
+}
+
+/// 按 duplicate_policy 去重后保存一条采集到的条目，没有新增或更新任何条目时返回 None
+fn save_captured(
+    db_path: &Path,
+    mut item: ClipboardItem,
+    settings: &Settings,
+    stats: &CaptureStats,
+) -> Result<Option<ClipboardDto>, AppError> {
+    // 钩子没装上时无法区分，留空
+    if settings.classify_capture_source && copy_hook_active() {
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        item.capture_source = Some(
+            if copy_key_recent(now_ms) {
+                "user"
+            } else {
+                "auto"
+            }
+            .into(),
+        );
+    }
+    let policy = settings.duplicate_policy.as_str();
+    if item.content_type == "image" && policy != "always_new" {
+        if let Some(dto) = bump_repeated_image(db_path, &item)? {
+            CaptureStats::bump(&stats.repeated_images);
+            return Ok(Some(dto));
+        }
+    }
+    match policy {
+        "always_new" => {}
+        "bump" => {
+            let conn = open_db(db_path)?;
+            if let Some(id) = find_existing(&conn, &item)? {
+                conn.execute(
+                    "UPDATE clipboard_items SET created_at = ?1 WHERE id = ?2",
+                    params![item.created_at, id],
+                )?;
+                CaptureStats::bump(&stats.skipped_duplicate);
+                let dto = to_dto(fetch_item(&conn, id)?);
+                if let Some(state) = app_state() {
+                    state.place_recent(&conn, dto.clone());
+                }
+                return Ok(Some(dto));
+            }
+        }
+        _ => {
+            if is_duplicate(db_path, &item)? {
+                CaptureStats::bump(&stats.skipped_duplicate);
+                return Ok(None);
+            }
+        }
+    }
+    let saved = insert_item(db_path, item, settings)?;
+    CaptureStats::bump(&stats.saved);
+    Ok(Some(saved))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CaptureKind {
+    Text,
+    Image,
+}
+
+/// 文本（含文件列表）和图片都有时按 capture_preference 取其一，both 按 text 处理、图片由
+/// read_clipboard 另存一条；只有一种时不看偏好
+fn capture_choice(has_text: bool, has_image: bool, preference: &str) -> Option<CaptureKind> {
+    match (has_text, has_image) {
+        (false, false) => None,
+        (true, false) => Some(CaptureKind::Text),
+        (false, true) => Some(CaptureKind::Image),
+        (true, true) if preference == "image" => Some(CaptureKind::Image),
+        (true, true) => Some(CaptureKind::Text),
+    }
+}
+
+/// 把当前剪贴板内容组装成待保存的条目，不做过滤与去重。文本和图片的取舍见 capture_choice；
+/// 只有空白的文本视为没有文本
+fn capture_clipboard_item(
+    settings: &Settings,
+    proc_info: Option<ProcessInfo>,
+) -> Result<Option<ClipboardItem>, AppError> {
+    let mut clipboard = Clipboard::new().map_err(|e| AppError::Clipboard(format!("{e}")))?;
+    let text = capture_text_item(&mut clipboard, settings, proc_info.clone())
+        .or_else(|| capture_file_item(proc_info.clone()));
+    // 已有文本且不偏好图片时结果与图片无关，省掉一次解码
+    let image = if text.is_none() || settings.capture_preference == "image" {
+        capture_image_item(&mut clipboard, settings, proc_info.clone())?
+    } else {
+        None
+    };
+    match capture_choice(
+        text.is_some(),
+        image.is_some(),
+        &settings.capture_preference,
+    ) {
+        Some(CaptureKind::Text) => return Ok(text),
+        Some(CaptureKind::Image) => return Ok(image),
+        None => {}
+    }
+
+    if settings.record_unknown_formats {
+        let formats = clipboard_format_names();
+        if !formats.is_empty() {
+            return Ok(Some(ClipboardItem {
+                id: 0,
+                content_type: "unsupported".into(),
+                text_content: Some(formats.join(", ")),
+                source_app: proc_info.as_ref().map(|p| p.display.clone()),
+                source_path: proc_info.as_ref().map(|p| p.path.clone()),
+                source_icon: proc_info.and_then(|p| p.icon_png),
+                created_at: chrono::Utc::now().timestamp_millis(),
+                ..Default::default()
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+fn capture_text_item(
+    clipboard: &mut Clipboard,
+    settings: &Settings,
+    proc_info: Option<ProcessInfo>,
+) -> Option<ClipboardItem> {
+    let text = clipboard.get_text().ok()?;
+    if text.trim().is_empty() {
+        return None;
+    }
+    // 表格依赖制表符分列，不做规整
+    let (text, raw_text) =
+        if settings.normalize_whitespace_on_capture && detect_table(&text).is_none() {
+            let normalized = normalize_whitespace(&text);
+            if normalized != text {
+                (normalized, Some(text))
+            } else {
+                (text, None)
+            }
+        } else {
+            (text, None)
+        };
+    Some(ClipboardItem {
+        id: 0,
+        content_type: text_content_type(&text).into(),
+        text_content: Some(text.clone()),
+        raw_text,
+        image_data: None,
+        source_app: proc_info.as_ref().map(|p| p.display.clone()),
+        source_path: proc_info.as_ref().map(|p| p.path.clone()),
+        source_icon: proc_info.and_then(|p| p.icon_png),
+        source_url: read_clipboard_source_url(),
+        created_at: chrono::Utc::now().timestamp_millis(),
+        is_pinned: false,
+        usage_count: 0,
+        ..Default::default()
+    })
+}
+
+/// 资源管理器复制的文件（CF_HDROP）记为路径条目，每行一个路径；同时记下复制/剪切意图，缺省按复制处理
+fn capture_file_item(proc_info: Option<ProcessInfo>) -> Option<ClipboardItem> {
+    let paths = parse_hdrop(&read_clipboard_bytes(u32::from(CF_HDROP))?);
+    if paths.is_empty() {
+        return None;
+    }
+    let effect = read_clipboard_bytes(preferred_drop_effect_format())
+        .and_then(|b| {
+            b.get(..4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        })
+        .filter(|&effect| effect == DROPEFFECT_COPY.0 || effect == DROPEFFECT_MOVE.0)
+        .unwrap_or(DROPEFFECT_COPY.0);
+    Some(ClipboardItem {
+        content_type: "path".into(),
+        text_content: Some(paths.join("\n")),
+        drop_effect: Some(i64::from(effect)),
+        source_app: proc_info.as_ref().map(|p| p.display.clone()),
+        source_path: proc_info.as_ref().map(|p| p.path.clone()),
+        source_icon: proc_info.and_then(|p| p.icon_png),
+        created_at: chrono::Utc::now().timestamp_millis(),
+        ..Default::default()
+    })
+}
+
+fn preferred_drop_effect_format() -> u32 {
+    unsafe { RegisterClipboardFormatW(w!("Preferred DropEffect")) }
+}
+
+/// 解析 DROPFILES：pFiles 为文件列表偏移，fWide 为 1 时是宽字符，列表以双 0 结尾
+fn parse_hdrop(bytes: &[u8]) -> Vec<String> {
+    let read_u32 = |at: usize| {
+        bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let (Some(offset), Some(wide)) = (read_u32(0), read_u32(16)) else {
+        return Vec::new();
+    };
+    let Some(list) = bytes.get(offset as usize..) else {
+        return Vec::new();
+    };
+    let names: Vec<String> = if wide != 0 {
+        let units: Vec<u16> = list
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        units
+            .split(|&c| c == 0)
+            .map(String::from_utf16_lossy)
+            .collect()
+    } else {
+        list.split(|&c| c == 0)
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect()
+    };
+    names
+        .into_iter()
+        .take_while(|name| !name.is_empty())
+        .collect()
+}
+
+/// DROPFILES 头（pFiles=20、pt=(0,0)、fNC=0、fWide=1）加双 0 结尾的宽字符路径列表
+fn hdrop_bytes(paths: &[PathBuf]) -> Vec<u8> {
+    let mut hdrop = Vec::new();
+    hdrop.extend_from_slice(&20u32.to_le_bytes());
+    hdrop.extend_from_slice(&[0u8; 12]);
+    hdrop.extend_from_slice(&1u32.to_le_bytes());
+    for path in paths {
+        hdrop.extend(utf16_z(&path.to_string_lossy()));
+    }
+    hdrop.extend_from_slice(&[0, 0]);
+    hdrop
+}
+
+/// 路径条目中仍然存在的文件/文件夹（每行一个）
+fn entry_paths(text: &str) -> Vec<PathBuf> {
+    text.lines().filter_map(existing_path).collect()
+}
+
+/// 未开启 record_images、图片过大或为空白图时返回 None
+fn capture_image_item(
+    clipboard: &mut Clipboard,
+    settings: &Settings,
+    proc_info: Option<ProcessInfo>,
+) -> Result<Option<ClipboardItem>, AppError> {
+    if !settings.record_images {
+        return Ok(None);
+    }
+    let Ok(img) = clipboard.get_image() else {
+        return Ok(None);
+    };
+    let (width, height) = match checked_image_dims(
+        img.width,
+        img.height,
+        img.bytes.len(),
+        settings.max_image_pixels,
+    ) {
+        Ok(dims) => dims,
+        Err(reason) => {
+            log::warn!(
+                "skip clipboard image {}x{}: {reason}",
+                img.width,
+                img.height
+            );
+            return Ok(None);
+        }
+    };
+    let buffer: ImageBuffer<Rgba<u8>, Vec<u8>> =
+        ImageBuffer::from_raw(width, height, img.bytes.into_owned())
+            .ok_or_else(|| AppError::Other("无法读取图片数据".into()))?;
+    if settings.skip_blank_images && is_blank_image(&buffer, settings.blank_image_tolerance) {
+        log::info!("skip blank clipboard image {width}x{height}");
+        return Ok(None);
+    }
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let img_dyn = image::DynamicImage::ImageRgba8(buffer);
+        img_dyn
+            .write_to(&mut cursor, image::ImageOutputFormat::Png)
+            .map_err(|e| AppError::Other(e.to_string()))?;
+    }
+    let png_bytes = cursor.into_inner();
+    let dib_data = if settings.lossless_images {
+        read_clipboard_dib()
+    } else {
+        None
+    };
+    Ok(Some(ClipboardItem {
+        id: 0,
+        content_type: "image".into(),
+        text_content: None,
+        image_data: Some(png_bytes),
+        dib_data,
+        source_monitor: proc_info.as_ref().and_then(|p| p.monitor.clone()),
+        source_dpi: proc_info.as_ref().and_then(|p| p.dpi).map(i64::from),
+        source_app: proc_info.as_ref().map(|p| p.display.clone()),
+        source_path: proc_info.as_ref().map(|p| p.path.clone()),
+        source_icon: proc_info.and_then(|p| p.icon_png),
+        source_url: read_clipboard_source_url(),
+        created_at: chrono::Utc::now().timestamp_millis(),
+        is_pinned: false,
+        usage_count: 0,
+        ..Default::default()
+    }))
+}
+
+/// 列出剪贴板当前所有格式的名称；预定义格式没有注册名，用常量名代替
+fn clipboard_format_names() -> Vec<String> {
+    const STANDARD: &[(u32, &str)] = &[
+        (1, "CF_TEXT"),
+        (2, "CF_BITMAP"),
+        (3, "CF_METAFILEPICT"),
+        (7, "CF_OEMTEXT"),
+        (8, "CF_DIB"),
+        (13, "CF_UNICODETEXT"),
+        (14, "CF_ENHMETAFILE"),
+        (15, "CF_HDROP"),
+        (16, "CF_LOCALE"),
+        (17, "CF_DIBV5"),
+    ];
+    let mut names = Vec::new();
+    unsafe {
+        if OpenClipboard(None).is_err() {
+            return names;
+        }
+        let mut format = 0;
+        loop {
+            format = EnumClipboardFormats(format);
+            if format == 0 {
+                break;
+            }
+            let mut buf = [0u16; 256];
+            let len = GetClipboardFormatNameW(format, &mut buf);
+            let name = if len > 0 {
+                String::from_utf16_lossy(&buf[..len as usize])
+            } else if let Some((_, name)) = STANDARD.iter().find(|(id, _)| *id == format) {
+                (*name).to_string()
+            } else {
+                format!("#{format}")
+            };
+            names.push(name);
+        }
+        let _ = CloseClipboard();
+    }
+    names
+}
+
+/// 剪贴板非空且现有格式全部在忽略列表里
+fn only_ignored_formats(ignored: &[String]) -> bool {
+    if ignored.is_empty() {
+        return false;
+    }
+    let formats = clipboard_format_names();
+    !formats.is_empty()
+        && formats
+            .iter()
+            .all(|f| ignored.iter().any(|i| i.eq_ignore_ascii_case(f)))
+}
+
+/// 占位条目只有格式名称，没有可写回的内容
+fn ensure_restorable(item: &ClipboardItem) -> Result<(), String> {
+    if item.content_type == "unsupported" {
+        return Err("该条目只记录了剪贴板格式，无法还原内容".into());
+    }
+    Ok(())
+}
+
+/// 一步“留住”当前剪贴板：已记录过则直接置顶，否则新增一条置顶条目；不受黑名单和去重影响
+#[tauri::command]
+fn pin_current(app: AppHandle, state: State<AppState>) -> Result<ClipboardDto, String> {
+    ensure_not_read_only()?;
+    let settings = state.settings.lock().clone();
+    let proc_info = process_info_for_capture(&settings);
+    let mut item = capture_clipboard_item(&settings, proc_info)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "剪贴板为空或内容不受支持".to_string())?;
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let dto = match find_existing(&conn, &item).map_err(|e| e.to_string())? {
+        Some(id) => {
+            conn.execute(
+                "UPDATE clipboard_items SET is_pinned = 1 WHERE id = ?1",
+                params![id],
+            )
+            .map_err(|e| e.to_string())?;
+            let dto = to_dto(fetch_item(&conn, id).map_err(|e| e.to_string())?);
+            state.place_recent(&conn, dto.clone());
+            dto
+        }
+        None => {
+            item.is_pinned = true;
+            item.capture_source = Some("user".into());
+            insert_item(&state.db_path, item, &settings).map_err(|e| e.to_string())?
+        }
+    };
+    let _ = app.emit_all("clipboard://new", dto.clone());
+    Ok(dto)
+}
+
+/// 监听暂停或漏掉变化时手动补录一次当前剪贴板；默认与自动采集走同一套黑名单和去重，
+/// force 为 true 时跳过这些检查直接新增。没有保存任何条目时返回 None
+#[tauri::command]
+fn recapture_now(
+    app: AppHandle,
+    state: State<AppState>,
+    force: Option<bool>,
+) -> Result<Option<ClipboardDto>, String> {
+    ensure_not_read_only()?;
+    let dto = if force.unwrap_or(false) {
+        let settings = state.settings.lock().clone();
+        let proc_info = process_info_for_capture(&settings);
+        match capture_clipboard_item(&settings, proc_info).map_err(|e| e.to_string())? {
+            Some(mut item) => {
+                item.capture_source = Some("user".into());
+                let dto =
+                    insert_item(&state.db_path, item, &settings).map_err(|e| e.to_string())?;
+                CaptureStats::bump(&state.stats.saved);
+                Some(dto)
+            }
+            None => None,
+        }
+    } else {
+        read_clipboard(&state.db_path, &state).map_err(|e| e.to_string())?
+    };
+    if let Some(dto) = &dto {
+        let _ = app.emit_all("clipboard://new", dto.clone());
+    }
+    Ok(dto)
+}
+
+/// 抽样约 4096 个像素：全部完全透明，或各通道与第一个样本相差都不超过 tolerance 即视为空白
+fn is_blank_image(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, tolerance: u8) -> bool {
+    let total = img.width() as usize * img.height() as usize;
+    let step = (total / 4096).max(1);
+    let raw = img.as_raw();
+    let sample = |idx: usize| &raw[idx * 4..idx * 4 + 4];
+    let first = sample(0);
+    let mut all_transparent = true;
+    let mut all_same = true;
+    for idx in (0..total).step_by(step) {
+        let px = sample(idx);
+        all_transparent &= px[3] == 0;
+        all_same &= px
+            .iter()
+            .zip(first)
+            .all(|(a, b)| a.abs_diff(*b) <= tolerance);
+        if !all_transparent && !all_same {
+            return false;
+        }
+    }
+    true
+}
+
+/// 校验剪贴板图片尺寸：乘法不溢出、像素数不超上限、字节数与 RGBA 尺寸一致
+fn checked_image_dims(
+    width: usize,
+    height: usize,
+    byte_len: usize,
+    max_pixels: u64,
+) -> Result<(u32, u32), String> {
+    let w = u32::try_from(width).map_err(|_| "宽度超出范围".to_string())?;
+    let h = u32::try_from(height).map_err(|_| "高度超出范围".to_string())?;
+    let pixels = checked_pixel_count(w, h, max_pixels)?;
+    let expected = pixels
+        .checked_mul(4)
+        .ok_or_else(|| "字节数溢出".to_string())?;
+    if expected != byte_len as u64 {
+        return Err(format!("数据长度 {byte_len} 与尺寸不符"));
+    }
+    Ok((w, h))
+}
+
+/// 像素数不为 0 且不超过上限；解码前用图片头里的尺寸检查，避免超大图片占满内存
+fn checked_pixel_count(width: u32, height: u32, max_pixels: u64) -> Result<u64, String> {
+    if width == 0 || height == 0 {
+        return Err("尺寸为 0".into());
+    }
+    let pixels = u64::from(width) * u64::from(height);
+    if pixels > max_pixels {
+        return Err(format!("像素数 {pixels} 超过上限 {max_pixels}"));
+    }
+    Ok(pixels)
+}
+
+fn insert_item(
+    db_path: &Path,
+    item: ClipboardItem,
+    settings: &Settings,
+) -> Result<ClipboardDto, AppError> {
+    let conn = open_db(db_path)?;
+    let dto = insert_row(&conn, item, settings)?;
+    enforce_limit_if_needed(&conn, db_path, settings)?;
+    Ok(dto)
+}
+
+/// 只写入一行（含缩略图与格式识别），不做条数清理；调用方可在自己的事务内批量写入
+fn insert_row(
+    conn: &Connection,
+    mut item: ClipboardItem,
+    settings: &Settings,
+) -> Result<ClipboardDto, AppError> {
+    if let Some(bytes) = &item.image_data {
+        item.thumb_data = make_thumbnail(bytes, settings.thumb_max_dim, settings.webp_thumbnails)?;
+        item.image_hash = image_dhash(bytes);
+    }
+    if let Some((rows, cols)) = item.text_content.as_deref().and_then(detect_table) {
+        item.detected_format = Some("table".into());
+        item.table_rows = Some(rows as i64);
+        item.table_cols = Some(cols as i64);
+    }
+    if settings.detect_entities && item.content_type != "image" {
+        item.detected_entity = item
+            .text_content
+            .as_deref()
+            .and_then(detect_entity)
+            .map(Into::into);
+    }
+    conn.execute(
+        "INSERT INTO clipboard_items (content_type, text_content, image_data, source_app, source_path, source_icon, source_url, created_at, is_pinned, usage_count, detected_format, table_rows, table_cols, thumb_data, raw_text, dib_data, detected_entity, source_monitor, source_dpi, capture_source, derived_from, image_hash, drop_effect, checksum)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 0, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)",
+        params![
+            item.content_type,
+            item.text_content,
+            item.image_data,
+            item.source_app,
+            item.source_path,
+            item.source_icon,
+            item.source_url,
+            item.created_at,
+            item.is_pinned as i32,
+            item.detected_format,
+            item.table_rows,
+            item.table_cols,
+            item.thumb_data,
+            item.raw_text,
+            item.dib_data,
+            item.detected_entity,
+            item.source_monitor,
+            item.source_dpi,
+            item.capture_source,
+            item.derived_from,
+            item.image_hash,
+            item.drop_effect,
+            item_checksum(&item)
+        ],
+    )?;
+    item.id = conn.last_insert_rowid();
+    // 事务里先留在库里：回滚后文件会成为孤儿，id 还可能被下一条复用，由调用方提交后再迁出
+    if let (true, true, Some(bytes)) = (
+        settings.store_images_on_disk,
+        conn.is_autocommit(),
+        &item.image_data,
+    ) {
+        if let Err(err) = externalize_image(conn, item.id, bytes) {
+            log::warn!("keep image {} in database: {err}", item.id);
+        }
+    }
+    let dto = to_dto(item);
+    // 事务里写入的可能回滚，由调用方提交后更新最近缓存
+    if conn.is_autocommit() {
+        if let Some(state) = app_state() {
+            state.place_recent(conn, dto.clone());
+        }
+    }
+    Ok(dto)
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// 完整性校验和：对内容与来源做 64 位 FNV-1a，写入后不再变化的列才参与计算（created_at 会被刷新，不算在内）。
+/// 只用于发现磁盘损坏或外部修改，改动时同时重算校验和的篡改无法识别
+fn item_checksum(item: &ClipboardItem) -> i64 {
+    let mut hash = FNV_OFFSET;
+    let mut feed = |bytes: &[u8]| hash = fnv1a(hash, bytes);
+    let fields: [Option<&[u8]>; 6] = [
+        Some(item.content_type.as_bytes()),
+        item.text_content.as_deref().map(str::as_bytes),
+        item.image_data.as_deref(),
+        item.source_app.as_deref().map(str::as_bytes),
+        item.source_path.as_deref().map(str::as_bytes),
+        item.source_url.as_deref().map(str::as_bytes),
+    ];
+    // 每个字段前写入长度，空值与空串区分开，避免相邻字段拼接后碰撞
+    for field in fields {
+        match field {
+            Some(bytes) => {
+                feed(&(bytes.len() as u64).to_le_bytes());
+                feed(bytes);
+            }
+            None => feed(&u64::MAX.to_le_bytes()),
+        }
+    }
+    hash as i64
+}
+
+/// 重新计算每一行（含回收站）的校验和，返回与存储值不符的条目 id；外存图片文件缺失也算不符。
+/// 旧版本写入、还没有校验和的行在这里补算，本次不参与比较
+#[tauri::command]
+fn verify_history(state: State<AppState>) -> Result<Vec<i64>, String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut suspects = Vec::new();
+    let mut missing = Vec::new();
+    {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {ITEM_COLUMNS}, checksum FROM clipboard_items ORDER BY id"
+            ))
+            .map_err(|e| e.to_string())?;
+        let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+        while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+            let mut item = item_from_row(row).map_err(|e| e.to_string())?;
+            load_image_file(&mut item);
+            let actual = item_checksum(&item);
+            match row.get::<_, Option<i64>>(25).map_err(|e| e.to_string())? {
+                Some(stored) if stored != actual => suspects.push(item.id),
+                Some(_) => {}
+                None => missing.push((item.id, actual)),
+            }
+        }
+    }
+    if !missing.is_empty() && !*READ_ONLY {
+        let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+        for (id, checksum) in &missing {
+            tx.execute(
+                "UPDATE clipboard_items SET checksum = ?1 WHERE id = ?2",
+                params![checksum, id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        log::info!("filled checksums for {} legacy rows", missing.len());
+    }
+    if !suspects.is_empty() {
+        log::warn!(
+            "checksum mismatch for {} rows: {suspects:?}",
+            suspects.len()
+        );
+    }
+    Ok(suspects)
+}
+
+/// image_path 为相对数据目录的路径（images/<id>.png），转为绝对路径
+fn image_file_path(rel: &str) -> Option<PathBuf> {
+    IMAGES_DIR
+        .get()
+        .and_then(|dir| dir.parent())
+        .map(|base| base.join(rel))
+}
+
+fn read_image_file(rel: &str) -> Option<Vec<u8>> {
+    let path = image_file_path(rel)?;
+    match std::fs::read(&path) {
+        Ok(bytes) => Some(bytes),
+        Err(err) => {
+            log::warn!("read image file {} failed: {err}", path.display());
+            None
+        }
+    }
+}
+
+/// 删除外存的图片文件；shred 时先用零覆盖再删除
+fn remove_image_file(rel: &str, shred: bool) {
+    let Some(path) = image_file_path(rel) else {
+        return;
+    };
+    if shred {
+        if let Ok(meta) = std::fs::metadata(&path) {
+            let zeroed = std::fs::OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .and_then(|mut file| {
+                    file.write_all(&vec![0u8; meta.len() as usize])?;
+                    file.sync_all()
+                });
+            if let Err(err) = zeroed {
+                log::warn!("overwrite image file {} failed: {err}", path.display());
+            }
+        }
+    }
+    if let Err(err) = std::fs::remove_file(&path) {
+        if err.kind() != io::ErrorKind::NotFound {
+            log::warn!("remove image file {} failed: {err}", path.display());
+        }
+    }
+}
+
+/// 把条目的图片写到 images/<id>.png，库里只留相对路径和文件大小。没有缩略图说明原图本身够小，
+/// 留一份在 thumb_data 里给列表用，列表就不必读文件
+fn externalize_image(conn: &Connection, id: i64, bytes: &[u8]) -> Result<(), AppError> {
+    let rel = format!("images/{id}.png");
+    let path = image_file_path(&rel).ok_or_else(|| AppError::Other("图片目录未初始化".into()))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, bytes)?;
+    conn.execute(
+        "UPDATE clipboard_items SET thumb_data = COALESCE(thumb_data, image_data), image_data = NULL, image_path = ?1, image_size = ?2 WHERE id = ?3",
+        params![rel, bytes.len() as i64, id],
+    )?;
+    Ok(())
+}
+
+/// 彻底删除满足条件的行，并删掉它们外存的图片文件、移出最近缓存，返回删除的行数。
+/// 只能在事务外调用；事务里用 delete_rows，提交后再处理文件和缓存
+fn delete_items(
+    conn: &Connection,
+    where_sql: &str,
+    params: impl rusqlite::Params,
+    shred: bool,
+) -> rusqlite::Result<usize> {
+    debug_assert!(conn.is_autocommit(), "delete_items inside a transaction");
+    let removed = delete_rows(conn, where_sql, params)?;
+    remove_image_files(&removed, shred);
+    if !removed.is_empty() {
+        if let Some(state) = app_state() {
+            let ids: Vec<i64> = removed.iter().map(|(id, _)| *id).collect();
+            state.forget_recent(&ids);
+        }
+    }
+    Ok(removed.len())
+}
+
+/// 只删行，返回被删行的 id 与外存图片路径；事务提交前不能动文件，回滚后行还在
+fn delete_rows(
+    conn: &Connection,
+    where_sql: &str,
+    params: impl rusqlite::Params,
+) -> rusqlite::Result<Vec<(i64, Option<String>)>> {
+    let mut stmt = conn.prepare(&format!(
+        "DELETE FROM clipboard_items WHERE {where_sql} RETURNING id, image_path"
+    ))?;
+    let removed = stmt
+        .query_map(params, |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(removed)
+}
+
+fn remove_image_files(removed: &[(i64, Option<String>)], shred: bool) {
+    for rel in removed.iter().filter_map(|(_, rel)| rel.as_deref()) {
+        remove_image_file(rel, shred);
+    }
+}
+
+/// 外存图片在库里只有路径，无法直接比较内容：先按 dHash 找候选，再逐字节比较文件。
+/// extra_where 为附加条件，其参数从 ?2 开始
+fn find_disk_image(
+    conn: &Connection,
+    bytes: &[u8],
+    extra_where: &str,
+    extra: &[&dyn rusqlite::ToSql],
+) -> Result<Option<i64>, AppError> {
+    let Some(hash) = image_dhash(bytes) else {
+        return Ok(None);
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, image_path FROM clipboard_items
+         WHERE content_type = 'image' AND deleted_at IS NULL AND image_path IS NOT NULL AND image_hash = ?1 AND ({extra_where})
+         ORDER BY created_at DESC"
+    ))?;
+    let mut params: Vec<&dyn rusqlite::ToSql> = vec![&hash];
+    params.extend_from_slice(extra);
+    let candidates = stmt
+        .query_map(params.as_slice(), |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(candidates
+        .into_iter()
+        .find(|(_, rel)| read_image_file(rel).is_some_and(|file| file == bytes))
+        .map(|(id, _)| id))
+}
+
+/// 把库里已有的图片 BLOB 迁出为文件（不受 store_images_on_disk 影响），返回迁出的条数。
+/// 迁出后库文件不会自动变小，需要再执行一次 VACUUM
+#[tauri::command]
+fn externalize_images(state: State<AppState>) -> Result<usize, String> {
+    ensure_not_read_only()?;
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let ids = {
+        let mut stmt = conn
+            .prepare("SELECT id FROM clipboard_items WHERE image_data IS NOT NULL AND image_path IS NULL")
+            .map_err(|e| e.to_string())?;
+        let ids = stmt
+            .query_map([], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        ids
+    };
+    externalize_rows(&conn, &ids).map_err(|e| e.to_string())
+}
+
+/// 把指定行里仍在库中的图片迁出为文件，返回迁出的条数；逐条读取，避免一次把所有图片载入内存
+fn externalize_rows(conn: &Connection, ids: &[i64]) -> Result<usize, AppError> {
+    let mut moved = 0;
+    for id in ids {
+        let bytes: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT image_data FROM clipboard_items WHERE id = ?1 AND image_path IS NULL",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        let Some(bytes) = bytes else { continue };
+        externalize_image(conn, *id, &bytes)?;
+        moved += 1;
+    }
+    Ok(moved)
+}
+
+/// 行内连续空格/制表符/不换行空格合并为一个空格，去掉行尾空白，多个空行合并为一个
+fn normalize_whitespace(text: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let mut out = String::with_capacity(line.len());
+        let mut pending_space = false;
+        for ch in line.chars() {
+            if matches!(ch, ' ' | '\t' | '\u{a0}' | '\u{202f}' | '\u{3000}') {
+                pending_space = true;
+                continue;
+            }
+            if pending_space {
+                out.push(' ');
+                pending_space = false;
+            }
+            out.push(ch);
+        }
+        if out.is_empty() && lines.last().is_some_and(|prev| prev.is_empty()) {
+            continue;
+        }
+        lines.push(out);
+    }
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut result = lines.join(newline);
+    if text.ends_with('\n') && !result.is_empty() {
+        result.push_str(newline);
+    }
+    result
+}
+
+static URL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"https?://[^\s<>"']+"#).unwrap());
+// 除 utm_* 外常见的广告/分享跟踪参数
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid", "_hsenc", "_hsmi",
+];
+
+/// 去掉文本中所有网址的跟踪参数，其余参数与锚点保持原顺序
+fn strip_tracking_params(text: &str) -> String {
+    URL_RE
+        .replace_all(text, |caps: &regex::Captures| {
+            let url = &caps[0];
+            let Some((base, rest)) = url.split_once('?') else {
+                return url.to_string();
+            };
+            let (query, fragment) = match rest.split_once('#') {
+                Some((query, fragment)) => (query, Some(fragment)),
+                None => (rest, None),
+            };
+            let kept: Vec<&str> = query
+                .split('&')
+                .filter(|pair| {
+                    let key = pair
+                        .split('=')
+                        .next()
+                        .unwrap_or_default()
+                        .to_ascii_lowercase();
+                    !pair.is_empty()
+                        && !key.starts_with("utm_")
+                        && !TRACKING_PARAMS.contains(&key.as_str())
+                })
+                .collect();
+            let mut out = base.to_string();
+            if !kept.is_empty() {
+                out.push('?');
+                out.push_str(&kept.join("&"));
+            }
+            if let Some(fragment) = fragment {
+                out.push('#');
+                out.push_str(fragment);
+            }
+            out
+        })
+        .into_owned()
+}
+
+type TextTransform = fn(&str) -> String;
+
+const CAPTURE_TRANSFORMS: &[(&str, TextTransform)] = &[
+    ("trim", |text| text.trim().to_string()),
+    ("collapse_whitespace", normalize_whitespace),
+    ("strip_tracking_params", strip_tracking_params),
+];
+
+/// 按 capture_transforms 的顺序处理文本
+fn apply_capture_transforms(text: &str, names: &[String]) -> String {
+    let mut text = text.to_string();
+    for name in names {
+        match CAPTURE_TRANSFORMS
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(name.trim()))
+        {
+            Some((_, transform)) => text = transform(&text),
+            None => log::warn!("unknown capture transform: {name}"),
+        }
+    }
+    text
+}
+
+/// 单行且指向已存在的文件/文件夹时归为 "path"，支持资源管理器“复制为路径”带的引号
+fn text_content_type(text: &str) -> &'static str {
+    if existing_path(text).is_some() {
+        "path"
+    } else {
+        "text"
+    }
+}
+
+fn existing_path(text: &str) -> Option<PathBuf> {
+    let candidate = text.trim().trim_matches('"');
+    if candidate.is_empty() || candidate.len() > 1024 || candidate.contains(['\n', '\r', '\t']) {
+        return None;
+    }
+    let bytes = candidate.as_bytes();
+    let is_drive = bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'\\' || bytes[2] == b'/');
+    let is_unc = candidate.starts_with("\\\\");
+    if !is_drive && !is_unc {
+        return None;
+    }
+    let path = PathBuf::from(candidate);
+    path.exists().then_some(path)
+}
+
+/// 超过最长边限制时生成缩小后的 PNG；原图足够小则不单独存缩略图
+fn make_thumbnail(png: &[u8], max_dim: u32, webp: bool) -> Result<Option<Vec<u8>>, AppError> {
+    let img = image::load_from_memory(png).map_err(|e| AppError::Other(e.to_string()))?;
+    let small = img.width() <= max_dim && img.height() <= max_dim;
+    if small && !webp {
+        return Ok(None);
+    }
+    let thumb = if small {
+        img
+    } else {
+        img.thumbnail(max_dim, max_dim)
+    };
+    let format = if webp {
+        image::ImageOutputFormat::WebP
+    } else {
+        image::ImageOutputFormat::Png
+    };
+    let mut cursor = Cursor::new(Vec::new());
+    thumb
+        .write_to(&mut cursor, format)
+        .map_err(|e| AppError::Other(e.to_string()))?;
+    let bytes = cursor.into_inner();
+    // 小图转码后反而更大时直接用原图
+    if small && bytes.len() >= png.len() {
+        return Ok(None);
+    }
+    Ok(Some(bytes))
+}
+
+fn image_data_uri(bytes: &[u8]) -> String {
+    let mime = if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        "image/webp"
+    } else {
+        "image/png"
+    };
+    format!("data:{mime};base64,{}", BASE64.encode(bytes))
+}
+
+static EMAIL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}$").unwrap()
+});
+static PHONE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\+?[0-9(][0-9 ()\-.]{5,}[0-9]$").unwrap());
+static DATE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[0-9]{4}[-./][0-9]{1,2}[-./][0-9]{1,2}$").unwrap());
+static IBAN_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[A-Z]{2}[0-9]{2}[A-Z0-9]{11,30}$").unwrap());
+
+/// 整段文本（去首尾空白）恰好是一个邮箱 / 电话 / IBAN 时返回类型，夹在句子里的不算
+fn detect_entity(text: &str) -> Option<&'static str> {
+    let text = text.trim();
+    if text.is_empty() || text.len() > 64 || text.contains('\n') {
+        return None;
+    }
+    if EMAIL_RE.is_match(text) {
+        return Some("email");
+    }
+    let compact: String = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
+    if IBAN_RE.is_match(&compact) && iban_checksum_ok(&compact) {
+        return Some("iban");
+    }
+    if PHONE_RE.is_match(text) && !DATE_RE.is_match(text) {
+        // E.164 最多 15 位，少于 7 位多半是普通数字
+        let digits = text.chars().filter(|c| c.is_ascii_digit()).count();
+        if (7..=15).contains(&digits) {
+            return Some("phone");
+        }
+    }
+    None
+}
+
+/// IBAN mod-97 校验：前四位移到末尾，字母换成 10~35，余数为 1 才有效
+fn iban_checksum_ok(iban: &str) -> bool {
+    let rearranged = iban[4..].chars().chain(iban[..4].chars());
+    let mut remainder: u32 = 0;
+    for ch in rearranged {
+        let Some(value) = ch.to_digit(36) else {
+            return false;
+        };
+        let factor = if value >= 10 { 100 } else { 10 };
+        remainder = (remainder * factor + value) % 97;
+    }
+    remainder == 1
+}
+
+/// 保守识别表格（Excel/Sheets 复制的 TSV）：至少两行两列且每行列数一致
+fn detect_table(text: &str) -> Option<(usize, usize)> {
+    if !text.contains('\t') {
+        return None;
+    }
+    let lines: Vec<&str> = text.trim_end_matches(['\r', '\n']).lines().collect();
+    if lines.len() < 2 {
+        return None;
+    }
+    let cols = lines[0].split('\t').count();
+    if cols < 2 || lines.iter().any(|l| l.split('\t').count() != cols) {
+        return None;
+    }
+    Some((lines.len(), cols))
+}
+
+fn parse_table(text: &str) -> Vec<Vec<String>> {
+    text.trim_end_matches(['\r', '\n'])
+        .lines()
+        .map(|line| line.split('\t').map(|cell| cell.to_string()).collect())
+        .collect()
+}
+
+fn is_duplicate(db_path: &Path, item: &ClipboardItem) -> Result<bool, AppError> {
+    let conn = open_db(db_path)?;
+    let last: Option<(String, Option<String>, Option<u64>)> = conn
+        .query_row(
+            "SELECT content_type, text_content, COALESCE(LENGTH(image_data), image_size) FROM clipboard_items WHERE deleted_at IS NULL ORDER BY created_at DESC LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    if let Some((ctype, text, image_len)) = last {
+        if ctype == item.content_type {
+            if ctype == "image" {
+                return Ok(image_len == item.image_data.as_ref().map(|v| v.len() as u64));
+            } else {
+                return Ok(match (text.as_deref(), item.text_content.as_deref()) {
+                    (Some(a), Some(b)) => same_text(a, b),
+                    (a, b) => a == b,
+                });
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// 截图工具常把同一张图连续写入两次（DIB 与文件各一次），命中时累加已有条目的使用次数
+fn bump_repeated_image(
+    db_path: &Path,
+    item: &ClipboardItem,
+) -> Result<Option<ClipboardDto>, AppError> {
+    let mut conn = open_db(db_path)?;
+    let existing: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM clipboard_items
+             WHERE content_type = 'image' AND deleted_at IS NULL AND created_at >= ?1
+               AND source_path IS ?2 AND image_data = ?3
+             ORDER BY created_at DESC LIMIT 1",
+            params![
+                item.created_at - IMAGE_REPEAT_WINDOW_MS,
+                item.source_path,
+                item.image_data
+            ],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let existing = match (existing, &item.image_data) {
+        (None, Some(bytes)) => find_disk_image(
+            &conn,
+            bytes,
+            "created_at >= ?2 AND source_path IS ?3",
+            &[
+                &(item.created_at - IMAGE_REPEAT_WINDOW_MS),
+                &item.source_path,
+            ],
+        )?,
+        (existing, _) => existing,
+    };
+    let Some(id) = existing else {
+        return Ok(None);
+    };
+    bump_usage(&mut conn, id)?;
+    Ok(Some(to_dto(fetch_item(&conn, id)?)))
+}
+
+/// 按 NFC 比较：同一段阿拉伯文/带组合附加符号的文字可能以不同的规范化形式写入剪贴板
+fn same_text(a: &str, b: &str) -> bool {
+    a == b || a.nfc().eq(b.nfc())
+}
+
+/// 按用户看到的字符（字素簇）计数：带附加符号的字母、emoji 组合都只算一个
+fn char_count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+const ITEM_COLUMNS: &str = "id, content_type, text_content, image_data, source_app, source_path, source_icon, source_url, created_at, is_pinned, usage_count, detected_format, table_rows, table_cols, thumb_data, detected_entity, source_monitor, source_dpi, capture_source, label, derived_from, auto_delete_after_uses, expires_at, image_path, image_size";
+
+fn item_from_row(row: &rusqlite::Row) -> rusqlite::Result<ClipboardItem> {
+    Ok(ClipboardItem {
+        id: row.get(0)?,
+        content_type: row.get(1)?,
+        text_content: row.get(2)?,
+        // 外存的图片这里为空，需要原图的调用方再 load_image_file
+        image_data: row.get(3)?,
+        source_app: row.get(4)?,
+        source_path: row.get(5)?,
+        source_icon: row.get(6)?,
+        source_url: row.get(7)?,
+        created_at: row.get(8)?,
+        is_pinned: row.get::<_, i32>(9)? != 0,
+        usage_count: row.get(10)?,
+        detected_format: row.get(11)?,
+        table_rows: row.get(12)?,
+        table_cols: row.get(13)?,
+        thumb_data: row.get(14)?,
+        detected_entity: row.get(15)?,
+        source_monitor: row.get(16)?,
+        source_dpi: row.get(17)?,
+        capture_source: row.get(18)?,
+        label: row.get(19)?,
+        derived_from: row.get(20)?,
+        auto_delete_after_uses: row.get(21)?,
+        expires_at: row.get(22)?,
+        raw_text: None, // 原文只在 get_raw_text 里按需读取
+        dib_data: None, // 粘贴时再单独读取
+        image_hash: None,
+        drop_effect: None, // 同上
+        image_path: row.get(23)?,
+        image_size: row.get(24)?,
+    })
+}
+
+/// 外存图片从文件读入 image_data；文件缺失时保持为空
+fn load_image_file(item: &mut ClipboardItem) {
+    if item.image_data.is_none() {
+        item.image_data = item.image_path.as_deref().and_then(read_image_file);
+    }
+}
+
+/// 单条读取带原图，粘贴、复制、OCR 等都经过这里；列表查询只用 item_from_row
+fn fetch_item(conn: &Connection, id: i64) -> rusqlite::Result<ClipboardItem> {
+    let mut item = conn.query_row(
+        &format!("SELECT {ITEM_COLUMNS} FROM clipboard_items WHERE id = ?1"),
+        params![id],
+        item_from_row,
+    )?;
+    load_image_file(&mut item);
+    Ok(item)
+}
+
+/// 毫秒时间戳转为 RFC3339 字符串（本地时区）；超出可表示范围时返回 None
+fn format_timestamp(ms: i64, precision: chrono::SecondsFormat) -> Option<String> {
+    Local
+        .timestamp_millis_opt(ms)
+        .single()
+        .map(|t| t.to_rfc3339_opts(precision, false))
+}
+
+fn to_dto(item: ClipboardItem) -> ClipboardDto {
+    let size_bytes = item
+        .image_data
+        .as_ref()
+        .map(|bytes| bytes.len() as i64)
+        .or(item.image_size)
+        .or_else(|| item.text_content.as_ref().map(|text| text.len() as i64))
+        .unwrap_or(0);
+    let image_thumb = item
+        .thumb_data
+        .as_ref()
+        .or(item.image_data.as_ref())
+        .map(|bytes| image_data_uri(bytes));
+    let source_icon = item
+        .source_icon
+        .as_ref()
+        .map(|bytes| format!("data:image/png;base64,{}", BASE64.encode(bytes)));
+    ClipboardDto {
+        id: item.id,
+        content_type: item.content_type,
+        text_content: item.text_content,
+        image_thumb,
+        source_app: item.source_app,
+        source_icon,
+        source_url: item.source_url,
+        created_at: item.created_at,
+        created_at_iso: format_timestamp(item.created_at, chrono::SecondsFormat::Millis),
+        is_pinned: item.is_pinned,
+        usage_count: item.usage_count,
+        size_bytes,
+        detected_format: item.detected_format,
+        table_rows: item.table_rows,
+        table_cols: item.table_cols,
+        detected_entity: item.detected_entity,
+        source_monitor: item.source_monitor,
+        source_dpi: item.source_dpi,
+        capture_source: item.capture_source,
+        label: item.label,
+        derived_from: item.derived_from,
+        auto_delete_after_uses: item.auto_delete_after_uses,
+        expires_at: item.expires_at,
+    }
+}
+
+/// 搜索字段名到列的映射；默认搜索全部字段：text（正文）、url（来源网址）、source（来源应用）
+fn search_columns(fields: Option<&[String]>) -> Result<Vec<&'static str>, String> {
+    const ALL: [(&str, &str); 3] = [
+        ("text", "text_content"),
+        ("url", "source_url"),
+        ("source", "source_app"),
+    ];
+    let Some(fields) = fields.filter(|f| !f.is_empty()) else {
+        return Ok(ALL.iter().map(|(_, column)| *column).collect());
+    };
+    let mut columns = Vec::new();
+    for field in fields {
+        let column = ALL
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, column)| *column)
+            .ok_or_else(|| format!("不支持的搜索字段：{field}"))?;
+        if !columns.contains(&column) {
+            columns.push(column);
+        }
+    }
+    Ok(columns)
+}
+
+type SqlParams = Vec<Box<dyn rusqlite::ToSql>>;
+
+/// get_history 的筛选条件，copy_search_results 复用同一套查询
+struct HistoryFilter {
+    query: Option<String>,
+    type_filter: Option<String>,
+    time_filter: Option<String>,
+    source_filter: Option<String>,
+    has_source: Option<bool>,
+    content_only: Option<bool>,
+    current_app_only: Option<bool>,
+    entity_filter: Option<String>,
+    search_fields: Option<Vec<String>>,
+    capture_source: Option<String>,
+}
+
+impl HistoryFilter {
+    /// 生成以 " AND" 开头的条件与参数；只看当前应用却取不到当前应用时返回 None，即结果为空
+    fn into_sql(self) -> Result<Option<(String, SqlParams)>, String> {
+        let mut sql = String::new();
+        let mut params_vec: SqlParams = Vec::new();
+
+        if let Some(q) = self.query {
+            if !q.trim().is_empty() {
+                let columns = search_columns(self.search_fields.as_deref())?;
+                let clause = columns
+                    .iter()
+                    .map(|c| format!("{c} LIKE ?"))
+                    .collect::<Vec<_>>()
+                    .join(" OR ");
+                sql.push_str(&format!(" AND ({clause})"));
+                for _ in &columns {
+                    params_vec.push(Box::new(format!("%{}%", q)));
+                }
+            }
+        }
+        if let Some(t) = self.type_filter {
+            if matches!(t.as_str(), "text" | "image" | "path" | "unsupported") {
+                sql.push_str(" AND content_type = ?");
+                params_vec.push(Box::new(t));
+            }
+        }
+        if let Some(sf) = self.source_filter {
+            if !sf.is_empty() {
+                // 附加了窗口标题的来源形如 "标题 (应用名)"
+                sql.push_str(" AND (source_app = ? OR source_app LIKE ? ESCAPE '\\')");
+                let escaped = sf
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_");
+                params_vec.push(Box::new(sf));
+                params_vec.push(Box::new(format!("% ({escaped})")));
+            }
+        }
+        match self.has_source {
+            Some(true) => sql.push_str(" AND source_app IS NOT NULL AND source_app != ''"),
+            Some(false) => sql.push_str(" AND (source_app IS NULL OR source_app = '')"),
+            None => {}
+        }
+        if self.content_only == Some(true) {
+            // 排除 Pastify 自身窗口为前台时产生的条目
+            sql.push_str(
+                " AND (source_path IS NULL OR LOWER(source_path) NOT LIKE '%\\pastify.exe')",
+            );
+        }
+        if let Some(entity) = self.entity_filter.filter(|e| !e.is_empty()) {
+            sql.push_str(" AND detected_entity = ?");
+            params_vec.push(Box::new(entity));
+        }
+        match self.capture_source.as_deref() {
+            None | Some("") => {}
+            Some(source @ ("user" | "auto")) => {
+                sql.push_str(" AND capture_source = ?");
+                params_vec.push(Box::new(source.to_string()));
+            }
+            Some(other) => return Err(format!("不支持的来源类型：{other}")),
+        }
+        if self.current_app_only == Some(true) {
+            let Some(path) = current_app_path() else {
+                return Ok(None);
+            };
+            sql.push_str(" AND source_path = ?");
+            params_vec.push(Box::new(path));
+        }
+        if let Some(tf) = self.time_filter {
+            let today_start = local_day_start(0);
+            let yesterday_start = local_day_start(1);
+            match tf.as_str() {
+                "today" => {
+                    sql.push_str(" AND created_at >= ?");
+                    params_vec.push(Box::new(today_start));
+                }
+                "yesterday" => {
+                    sql.push_str(" AND created_at >= ? AND created_at < ?");
+                    params_vec.push(Box::new(yesterday_start));
+                    params_vec.push(Box::new(today_start));
+                }
+                "earlier" => {
+                    sql.push_str(" AND created_at < ?");
+                    params_vec.push(Box::new(yesterday_start));
+                }
+                _ => {}
+            }
+        }
+        Ok(Some((sql, params_vec)))
+    }
+}
+
+/// 按 pinned_display 排序并分页的列表查询，get_history 与 get_history_compact 共用；筛选条件不可能命中时返回 None
+fn history_page_query(
+    settings: &Settings,
+    filter: HistoryFilter,
+    columns: &str,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<Option<(String, SqlParams)>, String> {
+    let Some((where_sql, mut params_vec)) = filter.into_sql()? else {
+        return Ok(None);
+    };
+    let mut sql =
+        format!("SELECT {columns} FROM clipboard_items WHERE deleted_at IS NULL{where_sql}");
+    // 上限取 max_history，保证所有已存条目都能分页取到
+    let (list_limit, max_history) = (settings.list_limit, settings.max_history);
+    let effective_limit = limit
+        .unwrap_or(list_limit)
+        .clamp(1, max_history.max(list_limit).max(1));
+    if settings.pinned_display == "inline" {
+        sql.push_str(" ORDER BY created_at DESC LIMIT ? OFFSET ?");
+    } else {
+        sql.push_str(" ORDER BY is_pinned DESC, created_at DESC LIMIT ? OFFSET ?");
+    }
+    params_vec.push(Box::new(effective_limit));
+    params_vec.push(Box::new(offset.unwrap_or(0).max(0)));
+    Ok(Some((sql, params_vec)))
+}
+
+/// 与 get_history 相同的筛选条件下共有多少条，只做 COUNT，不受 list_limit 限制
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn count_history(
+    state: State<AppState>,
+    query: Option<String>,
+    type_filter: Option<String>,
+    time_filter: Option<String>,
+    source_filter: Option<String>,
+    has_source: Option<bool>,
+    content_only: Option<bool>,
+    current_app_only: Option<bool>,
+    entity_filter: Option<String>,
+    search_fields: Option<Vec<String>>,
+    capture_source: Option<String>,
+) -> Result<i64, String> {
+    let filter = HistoryFilter {
+        query,
+        type_filter,
+        time_filter,
+        source_filter,
+        has_source,
+        content_only,
+        current_app_only,
+        entity_filter,
+        search_fields,
+        capture_source,
+    };
+    let Some((where_sql, params_vec)) = filter.into_sql()? else {
+        return Ok(0);
+    };
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    conn.query_row(
+        &format!("SELECT COUNT(*) FROM clipboard_items WHERE deleted_at IS NULL{where_sql}"),
+        rusqlite::params_from_iter(params_vec.iter().map(|v| &**v)),
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// 列表首屏用的精简条目：不含图标、缩略图等 base64 数据
+#[derive(Serialize)]
+struct CompactEntry {
+    id: i64,
+    content_type: String,
+    preview: String,
+    created_at: i64,
+    is_pinned: bool,
+    label: Option<String>,
+}
+
+const COMPACT_PREVIEW_CHARS: usize = 200;
+
+/// 与 get_history 参数相同，但只返回精简条目（始终是单个数组，separate 模式下按 is_pinned 自行分组）；
+/// 可见行的完整数据再用 get_entries 按需获取
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn get_history_compact(
+    state: State<AppState>,
+    query: Option<String>,
+    type_filter: Option<String>,
+    time_filter: Option<String>,
+    source_filter: Option<String>,
+    has_source: Option<bool>,
+    content_only: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    current_app_only: Option<bool>,
+    entity_filter: Option<String>,
+    search_fields: Option<Vec<String>>,
+    capture_source: Option<String>,
+) -> Result<Vec<CompactEntry>, String> {
+    let filter = HistoryFilter {
+        query,
+        type_filter,
+        time_filter,
+        source_filter,
+        has_source,
+        content_only,
+        current_app_only,
+        entity_filter,
+        search_fields,
+        capture_source,
+    };
+    let settings = state.settings.lock().clone();
+    let columns = format!("id, content_type, substr(text_content, 1, {COMPACT_PREVIEW_CHARS}), created_at, is_pinned, label");
+    let Some((sql, params_vec)) = history_page_query(&settings, filter, &columns, limit, offset)?
+    else {
+        return Ok(Vec::new());
+    };
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(
+            rusqlite::params_from_iter(params_vec.iter().map(|v| &**v)),
+            compact_from_row,
+        )
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+/// 列依次为 id, content_type, 截断后的正文, created_at, is_pinned, label
+fn compact_from_row(row: &rusqlite::Row) -> rusqlite::Result<CompactEntry> {
+    let text: Option<String> = row.get(2)?;
+    Ok(CompactEntry {
+        id: row.get(0)?,
+        content_type: row.get(1)?,
+        preview: text
+            .map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+            .unwrap_or_default(),
+        created_at: row.get(3)?,
+        is_pinned: row.get::<_, i32>(4)? != 0,
+        label: row.get(5)?,
+    })
+}
+
+const MAX_RECENT: usize = 50;
+const RECENT_PREVIEW_CHARS: usize = 60;
+
+/// 从内存缓存返回最新的至多 k 条未删除条目（新到旧，最多 20），不查库
+#[tauri::command]
+fn get_recent_cached(state: State<AppState>, k: usize) -> Vec<ClipboardDto> {
+    state.recent.lock().iter().take(k).cloned().collect()
+}
+
+/// 托盘/快捷键菜单用：最新的 n 条精简条目（最多 50），不看置顶与筛选，按时间从新到旧
+#[tauri::command]
+fn get_recent(state: State<AppState>, n: usize) -> Result<Vec<CompactEntry>, String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, content_type, substr(text_content, 1, {RECENT_PREVIEW_CHARS}), created_at, is_pinned, label
+             FROM clipboard_items WHERE deleted_at IS NULL ORDER BY created_at DESC LIMIT ?1"
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![n.min(MAX_RECENT) as i64], compact_from_row)
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+/// 按 id 批量取完整条目（一次最多 200 个），返回顺序与传入顺序一致，已删除或不存在的 id 直接略过
+#[tauri::command]
+fn get_entries(state: State<AppState>, ids: Vec<i64>) -> Result<Vec<ClipboardDto>, String> {
+    if ids.len() > 200 {
+        return Err("一次最多获取 200 条".into());
+    }
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {ITEM_COLUMNS} FROM clipboard_items WHERE id = ?1 AND deleted_at IS NULL"
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut result = Vec::with_capacity(ids.len());
+    for id in ids {
+        if let Some(item) = stmt
+            .query_row(params![id], item_from_row)
+            .optional()
+            .map_err(|e| e.to_string())?
+        {
+            result.push(to_dto(item));
+        }
+    }
+    Ok(result)
+}
+
+/// pinned_display 为 separate 时置顶与其余条目分开返回，其他情况仍是单个数组
+#[derive(Serialize)]
+#[serde(untagged)]
+enum HistoryResponse {
+    List(Vec<ClipboardDto>),
+    Sections {
+        pinned: Vec<ClipboardDto>,
+        recent: Vec<ClipboardDto>,
+    },
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn get_history(
+    state: State<AppState>,
+    query: Option<String>,
+    type_filter: Option<String>,
+    time_filter: Option<String>,
+    source_filter: Option<String>,
+    has_source: Option<bool>,
+    content_only: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    current_app_only: Option<bool>,
+    entity_filter: Option<String>,
+    search_fields: Option<Vec<String>>,
+    capture_source: Option<String>,
+) -> Result<HistoryResponse, String> {
+    let filter = HistoryFilter {
+        query,
+        type_filter,
+        time_filter,
+        source_filter,
+        has_source,
+        content_only,
+        current_app_only,
+        entity_filter,
+        search_fields,
+        capture_source,
+    };
+    let settings = state.settings.lock().clone();
+    let separate = settings.pinned_display == "separate";
+    let Some((sql, params_vec)) =
+        history_page_query(&settings, filter, ITEM_COLUMNS, limit, offset)?
+    else {
+        return Ok(if separate {
+            HistoryResponse::Sections {
+                pinned: Vec::new(),
+                recent: Vec::new(),
+            }
+        } else {
+            HistoryResponse::List(Vec::new())
+        });
+    };
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let mut rows = stmt
+        .query(rusqlite::params_from_iter(params_vec.iter().map(|v| &**v)))
+        .map_err(|e| e.to_string())?;
+    let mut result = Vec::new();
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let item = item_from_row(row).map_err(|e| e.to_string())?;
+        result.push(to_dto(item));
+    }
+    if separate {
+        // 与 top 同序分页，置顶条目总在前面，按 is_pinned 拆开即可
+        let (pinned, recent) = result.into_iter().partition(|dto| dto.is_pinned);
+        return Ok(HistoryResponse::Sections { pinned, recent });
+    }
+    Ok(HistoryResponse::List(result))
+}
+
+#[derive(Serialize, Clone)]
+struct HistoryChunk {
+    stream_id: u64,
+    items: Vec<ClipboardDto>,
+    done: bool,
+    error: Option<String>,
+}
+
+/// 与 get_history 参数相同，但立即返回推送 id，由后台线程按 chunk_size（默认 50）分批发送
+/// clipboard://history-chunk，最后一批 done 为 true（出错时带 error）；同一时刻只保留最近一次推送，避免一次性编码全部缩略图。
+/// 结果始终按列表顺序排列，separate 模式下按 is_pinned 自行分组
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn stream_history(
+    app: AppHandle,
+    state: State<AppState>,
+    query: Option<String>,
+    type_filter: Option<String>,
+    time_filter: Option<String>,
+    source_filter: Option<String>,
+    has_source: Option<bool>,
+    content_only: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    current_app_only: Option<bool>,
+    entity_filter: Option<String>,
+    search_fields: Option<Vec<String>>,
+    capture_source: Option<String>,
+    chunk_size: Option<usize>,
+) -> Result<u64, String> {
+    let filter = HistoryFilter {
+        query,
+        type_filter,
+        time_filter,
+        source_filter,
+        has_source,
+        content_only,
+        current_app_only,
+        entity_filter,
+        search_fields,
+        capture_source,
+    };
+    let settings = state.settings.lock().clone();
+    let chunk_size = chunk_size.unwrap_or(50).clamp(1, 500);
+    let stream_id = NEXT_IO_OP.fetch_add(1, Ordering::SeqCst);
+    LATEST_HISTORY_STREAM.store(stream_id, Ordering::SeqCst);
+    let db_path = state.db_path.clone();
+    thread::spawn(move || {
+        let send = |items: Vec<ClipboardDto>, done: bool, error: Option<String>| {
+            let _ = app.emit_all(
+                "clipboard://history-chunk",
+                HistoryChunk {
+                    stream_id,
+                    items,
+                    done,
+                    error,
+                },
+            );
+        };
+        let result = (|| -> Result<(), String> {
+            let Some((sql, params_vec)) =
+                history_page_query(&settings, filter, ITEM_COLUMNS, limit, offset)?
+            else {
+                send(Vec::new(), true, None);
+                return Ok(());
+            };
+            let conn = open_db(&db_path).map_err(|e| e.to_string())?;
+            let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+            let mut rows = stmt
+                .query(rusqlite::params_from_iter(params_vec.iter().map(|v| &**v)))
+                .map_err(|e| e.to_string())?;
+            let mut chunk = Vec::with_capacity(chunk_size);
+            while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+                if LATEST_HISTORY_STREAM.load(Ordering::SeqCst) != stream_id {
+                    return Ok(());
+                }
+                chunk.push(to_dto(item_from_row(row).map_err(|e| e.to_string())?));
+                if chunk.len() == chunk_size {
+                    send(
+                        std::mem::replace(&mut chunk, Vec::with_capacity(chunk_size)),
+                        false,
+                        None,
+                    );
+                }
+            }
+            send(chunk, true, None);
+            Ok(())
+        })();
+        if let Err(err) = result {
+            log::error!("stream_history failed: {err}");
+            send(Vec::new(), true, Some(err));
+        }
+    });
+    Ok(stream_id)
+}
+
+/// 代码块围栏比正文里最长的连续反引号再多一个，避免内容提前结束代码块
+fn markdown_fence(text: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for ch in text.chars() {
+        run = if ch == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// 把匹配的条目按列表顺序渲染成 Markdown：每条一个“来源 · 时间”小标题，文本放代码块，
+/// 图片默认引用 image-<id>.png（可用 export_entry 另存），inline_images 为 true 时内嵌 data URI。
+/// 超过 4MB 时截断并在末尾注明
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn export_markdown(
+    state: State<AppState>,
+    query: Option<String>,
+    type_filter: Option<String>,
+    time_filter: Option<String>,
+    source_filter: Option<String>,
+    has_source: Option<bool>,
+    content_only: Option<bool>,
+    current_app_only: Option<bool>,
+    entity_filter: Option<String>,
+    search_fields: Option<Vec<String>>,
+    capture_source: Option<String>,
+    inline_images: Option<bool>,
+) -> Result<String, String> {
+    const MAX_BYTES: usize = 4 * 1024 * 1024;
+    let filter = HistoryFilter {
+        query,
+        type_filter,
+        time_filter,
+        source_filter,
+        has_source,
+        content_only,
+        current_app_only,
+        entity_filter,
+        search_fields,
+        capture_source,
+    };
+    let Some((where_sql, params_vec)) = filter.into_sql()? else {
+        return Err("没有匹配的条目".into());
+    };
+    let inline_images = inline_images.unwrap_or(false);
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {ITEM_COLUMNS} FROM clipboard_items
+             WHERE deleted_at IS NULL AND content_type != 'unsupported'{where_sql}
+             ORDER BY is_pinned DESC, created_at DESC"
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt
+        .query(rusqlite::params_from_iter(params_vec.iter().map(|v| &**v)))
+        .map_err(|e| e.to_string())?;
+    let mut doc = String::from("# Pastify 剪贴板历史\n");
+    let mut count = 0;
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let mut item = item_from_row(row).map_err(|e| e.to_string())?;
+        if inline_images {
+            load_image_file(&mut item);
+        }
+        let time =
+            format_timestamp(item.created_at, chrono::SecondsFormat::Secs).unwrap_or_default();
+        let source = item.source_app.as_deref().unwrap_or("未知来源");
+        let mut section = format!(
+            "\n## {} · {time}\n\n",
+            item.label.as_deref().unwrap_or(source)
+        );
+        if item.label.is_some() {
+            section.push_str(&format!("来源：{source}\n\n"));
+        }
+        if let Some(url) = &item.source_url {
+            section.push_str(&format!("<{url}>\n\n"));
+        }
+        if item.content_type == "image" {
+            match (&item.image_data, inline_images) {
+                (Some(bytes), true) => {
+                    section.push_str(&format!("![#{}]({})\n", item.id, image_data_uri(bytes)))
+                }
+                _ => section.push_str(&format!("![#{}](image-{}.png)\n", item.id, item.id)),
+            }
+        } else {
+            let text = item.text_content.unwrap_or_default();
+            let fence = markdown_fence(&text);
+            section.push_str(&format!(
+                "{fence}\n{}\n{fence}\n",
+                text.trim_end_matches(['\r', '\n'])
+            ));
+        }
+        if doc.len() + section.len() > MAX_BYTES {
+            log::warn!("export_markdown truncated after {count} items");
+            doc.push_str("\n> 内容过多，其余条目已省略\n");
+            break;
+        }
+        doc.push_str(&section);
+        count += 1;
+    }
+    if count == 0 {
+        return Err("没有匹配的条目".into());
+    }
+    Ok(doc)
+}
+
+/// 把所有匹配的文本条目按列表顺序用分隔符（默认空行）连接后写入剪贴板，返回实际拼接的条数；
+/// 总长超过 8MB 时只取前面放得下的部分
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn copy_search_results(
+    state: State<AppState>,
+    query: Option<String>,
+    type_filter: Option<String>,
+    time_filter: Option<String>,
+    source_filter: Option<String>,
+    has_source: Option<bool>,
+    content_only: Option<bool>,
+    current_app_only: Option<bool>,
+    entity_filter: Option<String>,
+    search_fields: Option<Vec<String>>,
+    capture_source: Option<String>,
+    separator: Option<String>,
+) -> Result<usize, String> {
+    const MAX_BYTES: usize = 8 * 1024 * 1024;
+    let filter = HistoryFilter {
+        query,
+        type_filter,
+        time_filter,
+        source_filter,
+        has_source,
+        content_only,
+        current_app_only,
+        entity_filter,
+        search_fields,
+        capture_source,
+    };
+    let Some((where_sql, params_vec)) = filter.into_sql()? else {
+        return Err("没有匹配的文本条目".into());
+    };
+    let separator = separator.unwrap_or_else(|| "\n\n".into());
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT text_content FROM clipboard_items
+             WHERE deleted_at IS NULL AND content_type != 'image' AND text_content IS NOT NULL{where_sql}
+             ORDER BY is_pinned DESC, created_at DESC"
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt
+        .query(rusqlite::params_from_iter(params_vec.iter().map(|v| &**v)))
+        .map_err(|e| e.to_string())?;
+    let mut joined = String::new();
+    let mut count = 0;
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let text: String = row.get(0).map_err(|e| e.to_string())?;
+        let extra = if count == 0 {
+            text.len()
+        } else {
+            separator.len() + text.len()
+        };
+        if joined.len() + extra > MAX_BYTES {
+            log::warn!("copy_search_results truncated after {count} items");
+            break;
+        }
+        if count > 0 {
+            joined.push_str(&separator);
+        }
+        joined.push_str(&text);
+        count += 1;
+    }
+    if count == 0 {
+        return Err("没有匹配的文本条目".into());
+    }
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    skip_own_write();
+    clipboard.set_text(joined).map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+/// 本地时间 days_ago 天前 0 点的毫秒时间戳
+fn local_day_start(days_ago: i64) -> i64 {
+    let day = Local::now().date_naive() - ChronoDuration::days(days_ago);
+    Local
+        .from_local_datetime(&day.and_hms_opt(0, 0, 0).unwrap())
+        .single()
+        .unwrap()
+        .with_timezone(&chrono::Utc)
+        .timestamp_millis()
+}
+
+/// 历史中出现过的来源应用及条目数（多到少），供来源筛选下拉框使用。
+/// 有程序路径的按路径取友好名称，不受 capture_window_titles 附加的窗口标题影响
+#[tauri::command]
+fn list_sources(state: State<AppState>) -> Result<Vec<SourceApp>, String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    source_counts(&conn, i64::MIN).map_err(|e| e.to_string())
+}
+
+/// 按来源应用统计 since 之后的条目数，多到少排序
+fn source_counts(conn: &Connection, since: i64) -> rusqlite::Result<Vec<SourceApp>> {
+    let mut stmt = conn.prepare(
+        "SELECT source_path, COUNT(*), MAX(id) FROM clipboard_items
+         WHERE deleted_at IS NULL AND source_app IS NOT NULL AND source_app != '' AND created_at >= ?1
+         GROUP BY COALESCE(source_path, source_app)",
+    )?;
+    let groups = stmt
+        .query_map(params![since], |row| {
+            Ok((
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut sources: Vec<SourceApp> = Vec::new();
+    for (path, count, latest_id) in groups {
+        let (app, icon): (String, Option<Vec<u8>>) = conn.query_row(
+            "SELECT source_app, source_icon FROM clipboard_items WHERE id = ?1",
+            params![latest_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let app = path.as_deref().map(display_name_for_path).unwrap_or(app);
+        // 同名应用的不同安装路径合并为一项
+        match sources.iter_mut().find(|s| s.app == app) {
+            Some(existing) => existing.count += count,
+            None => sources.push(SourceApp {
+                app,
+                icon: icon.map(|bytes| format!("data:image/png;base64,{}", BASE64.encode(bytes))),
+                count,
+            }),
+        }
+    }
+    sources.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.app.cmp(&b.app)));
+    Ok(sources)
+}
+
+#[derive(Debug, Serialize)]
+struct FilterSuggestions {
+    top_sources: Vec<SourceApp>,
+    content_types_present: Vec<String>,
+    has_today: bool,
+}
+
+/// 筛选快捷标签所需的数据：最近 7 天最常见的来源（最多 limit 个，默认 5）、历史中存在的内容类型、今天是否有记录
+#[tauri::command]
+fn get_filter_suggestions(
+    state: State<AppState>,
+    limit: Option<usize>,
+) -> Result<FilterSuggestions, String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut top_sources = source_counts(&conn, local_day_start(7)).map_err(|e| e.to_string())?;
+    top_sources.truncate(limit.unwrap_or(5).clamp(1, 20));
+    let mut stmt = conn
+        .prepare("SELECT DISTINCT content_type FROM clipboard_items WHERE deleted_at IS NULL ORDER BY content_type")
+        .map_err(|e| e.to_string())?;
+    let content_types_present = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())?;
+    let has_today = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM clipboard_items WHERE deleted_at IS NULL AND created_at >= ?1)",
+            params![local_day_start(0)],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(FilterSuggestions {
+        top_sources,
+        content_types_present,
+        has_today,
+    })
+}
+
+/// 按占用字节数倒序列出条目（图片按 PNG 大小，文本按 UTF-8 长度）
+#[tauri::command]
+fn get_largest(state: State<AppState>, limit: Option<i64>) -> Result<Vec<ClipboardDto>, String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {ITEM_COLUMNS} FROM clipboard_items
+             WHERE deleted_at IS NULL
+             ORDER BY COALESCE(LENGTH(image_data), image_size, LENGTH(CAST(text_content AS BLOB)), 0) DESC
+             LIMIT ?1"
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![limit.unwrap_or(50).clamp(1, 500)], item_from_row)
+        .map_err(|e| e.to_string())?;
+    let mut result = Vec::new();
+    for item in rows {
+        result.push(to_dto(item.map_err(|e| e.to_string())?));
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+fn get_table(state: State<AppState>, id: i64) -> Result<Vec<Vec<String>>, String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let item = fetch_item(&conn, id).map_err(|e| e.to_string())?;
+    if item.detected_format.as_deref() != Some("table") {
+        return Err("该条目不是表格内容".into());
+    }
+    Ok(parse_table(
+        item.text_content.as_deref().unwrap_or_default(),
+    ))
+}
+
+/// 开启空白规整后取原始文本，未规整过的条目返回当前文本
+#[tauri::command]
+fn get_raw_text(state: State<AppState>, id: i64) -> Result<Option<String>, String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    conn.query_row(
+        "SELECT COALESCE(raw_text, text_content) FROM clipboard_items WHERE id = ?1",
+        params![id],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// 预览大图时取原图，列表只用缩略图
+#[tauri::command]
+fn get_image_data(state: State<AppState>, id: i64) -> Result<Option<String>, String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let item = fetch_item(&conn, id).map_err(|e| e.to_string())?;
+    Ok(item
+        .image_data
+        .map(|bytes| format!("data:image/png;base64,{}", BASE64.encode(bytes))))
+}
+
+/// 把原图以 data URI 文本写入剪贴板，便于直接贴进 CSS/HTML
+#[tauri::command]
+fn copy_as_data_uri(state: State<AppState>, id: i64) -> Result<(), String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let item = fetch_item(&conn, id).map_err(|e| e.to_string())?;
+    let bytes = match (item.content_type.as_str(), item.image_data) {
+        ("image", Some(bytes)) => bytes,
+        _ => return Err(format!("条目 {id} 不是图片")),
+    };
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    skip_own_write();
+    clipboard
+        .set_text(image_data_uri(&bytes))
+        .map_err(|e| e.to_string())
+}
+
+/// 按当前缩略图设置在后台重建所有图片的缩略图，原图缺失或损坏的条目跳过并计数
+#[tauri::command]
+fn regenerate_thumbnails(app: AppHandle, state: State<AppState>) -> Result<u64, String> {
+    ensure_not_read_only()?;
+    let db_path = state.db_path.clone();
+    let settings = state.settings.clone();
+    let state = state.inner().clone();
+    Ok(spawn_io_op(app, "thumbnails", move |progress| {
+        let conn = open_db(&db_path)?;
+        let ids = conn
+            .prepare("SELECT id FROM clipboard_items WHERE content_type = 'image'")?
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let total = ids.len();
+        let (mut updated, mut skipped) = (0, 0);
+        // 统计列表实际传输的图片字节数，便于比较 PNG 与 WebP
+        let (mut before, mut after) = (0usize, 0usize);
+        for (idx, id) in ids.into_iter().enumerate() {
+            progress(idx + 1, total);
+            // 每条都读最新设置，任务进行中改了设置也能按新值继续
+            let (max_dim, webp) = {
+                let settings = settings.lock();
+                (settings.thumb_max_dim, settings.webp_thumbnails)
+            };
+            let (bytes, old_thumb, image_path): (Option<Vec<u8>>, Option<Vec<u8>>, Option<String>) =
+                conn.query_row(
+                    "SELECT image_data, thumb_data, image_path FROM clipboard_items WHERE id = ?1",
+                    params![id],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )?;
+            let bytes = bytes.or_else(|| image_path.as_deref().and_then(read_image_file));
+            let Some(bytes) = bytes else {
+                log::warn!("skip thumbnail for {id}: image data missing");
+                skipped += 1;
+                continue;
+            };
+            let thumb = match make_thumbnail(&bytes, max_dim, webp) {
+                Ok(thumb) => thumb,
+                Err(err) => {
+                    log::warn!("skip thumbnail for {id}: {err}");
+                    skipped += 1;
+                    continue;
+                }
+            };
+            // 外存图片不生成缩略图时同样留一份原图给列表用，见 externalize_image
+            let thumb = thumb.or_else(|| image_path.is_some().then(|| bytes.clone()));
+            before += old_thumb.as_ref().unwrap_or(&bytes).len();
+            after += thumb.as_ref().unwrap_or(&bytes).len();
+            conn.execute(
+                "UPDATE clipboard_items SET thumb_data = ?1 WHERE id = ?2",
+                params![thumb, id],
+            )?;
+            updated += 1;
+        }
+        log::info!(
+            "regenerated {updated} thumbnails ({skipped} skipped): {before} -> {after} bytes"
+        );
+        state.reload_recent(&conn);
+        Ok((updated, skipped))
+    }))
+}
+
+/// 差异哈希：缩成 9x8 灰度图，逐行比较相邻像素明暗得到 64 位；缩放、轻微压缩后基本不变
+fn image_dhash(bytes: &[u8]) -> Option<i64> {
+    let img = image::load_from_memory(bytes).ok()?;
+    let small = img
+        .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+        .to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y).0[0] < small.get_pixel(x + 1, y).0[0] {
+                hash |= 1;
+            }
+        }
+    }
+    Some(hash as i64)
+}
+
+#[derive(Serialize)]
+struct SimilarEntry {
+    entry: ClipboardDto,
+    score: f32,
+}
+
+/// 查找与指定条目相近的其他条目（相似度 0~1，高到低）：文本按字符级相似度，图片按 dHash 汉明距离。
+/// 只扫描最近的 500 条同类条目，文本只比较前 2000 个字符
+#[tauri::command]
+fn find_similar(
+    state: State<AppState>,
+    id: i64,
+    limit: Option<usize>,
+) -> Result<Vec<SimilarEntry>, String> {
+    const SCAN_LIMIT: i64 = 500;
+    const TEXT_CHARS: usize = 2000;
+    const MIN_TEXT_SCORE: f32 = 0.6;
+    const MAX_HASH_DISTANCE: u32 = 10;
+    let limit = limit.unwrap_or(20).clamp(1, 100);
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let target = fetch_item(&conn, id).map_err(|e| e.to_string())?;
+    let is_image = target.content_type == "image";
+    let kind = if is_image {
+        "content_type = 'image'"
+    } else {
+        "content_type IN ('text', 'path')"
+    };
+    let mut scored: Vec<(f32, i64)> = Vec::new();
+    if is_image {
+        let hash_of = |id: i64, stored: Option<i64>, bytes: Option<Vec<u8>>| -> Option<i64> {
+            if stored.is_some() {
+                return stored;
+            }
+            // 旧条目没有哈希，第一次用到时补算并写回
+            let hash = image_dhash(bytes.as_deref()?)?;
+            let _ = conn.execute(
+                "UPDATE clipboard_items SET image_hash = ?1 WHERE id = ?2",
+                params![hash, id],
+            );
+            Some(hash)
+        };
+        let stored: Option<i64> = conn
+            .query_row(
+                "SELECT image_hash FROM clipboard_items WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        let Some(target_hash) = hash_of(id, stored, target.image_data.clone()) else {
+            return Err("无法读取图片数据".into());
+        };
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT id, image_hash, CASE WHEN image_hash IS NULL THEN image_data END, CASE WHEN image_hash IS NULL THEN image_path END FROM clipboard_items
+                 WHERE {kind} AND deleted_at IS NULL AND id != ?1 ORDER BY created_at DESC LIMIT ?2"
+            ))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![id, SCAN_LIMIT], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<(i64, Option<i64>, Option<Vec<u8>>, Option<String>)>, _>>()
+            .map_err(|e| e.to_string())?;
+        for (other, stored, bytes, image_path) in rows {
+            let bytes = bytes.or_else(|| image_path.as_deref().and_then(read_image_file));
+            let Some(hash) = hash_of(other, stored, bytes) else {
+                continue;
+            };
+            let distance = (target_hash ^ hash).count_ones();
+            if distance <= MAX_HASH_DISTANCE {
+                scored.push((1.0 - distance as f32 / 64.0, other));
+            }
+        }
+    } else {
+        let text = target.text_content.unwrap_or_default();
+        let head: String = text.chars().take(TEXT_CHARS).collect();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT id, substr(text_content, 1, {TEXT_CHARS}) FROM clipboard_items
+                 WHERE {kind} AND deleted_at IS NULL AND id != ?1 ORDER BY created_at DESC LIMIT ?2"
+            ))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![id, SCAN_LIMIT], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        for (other, other_text) in rows {
+            let other_text = other_text.unwrap_or_default();
+            let score = TextDiff::configure()
+                .timeout(Duration::from_millis(20))
+                .diff_chars(&head, &other_text)
+                .ratio();
+            if score >= MIN_TEXT_SCORE {
+                scored.push((score, other));
+            }
+        }
+    }
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.truncate(limit);
+    let mut result = Vec::with_capacity(scored.len());
+    for (score, other) in scored {
+        let entry = to_dto(fetch_item(&conn, other).map_err(|e| e.to_string())?);
+        result.push(SimilarEntry { entry, score });
+    }
+    Ok(result)
+}
+
+/// 返回两条文本条目的 unified diff（a 为旧版本，b 为新版本）
+#[tauri::command]
+fn diff_entries(state: State<AppState>, a: i64, b: i64) -> Result<String, String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let old = fetch_item(&conn, a).map_err(|e| e.to_string())?;
+    let new = fetch_item(&conn, b).map_err(|e| e.to_string())?;
+    if old.content_type == "image" || new.content_type == "image" {
+        return Err("只能比较文本条目".into());
+    }
+    let old_text = old.text_content.unwrap_or_default();
+    let new_text = new.text_content.unwrap_or_default();
+    let diff = TextDiff::from_lines(&old_text, &new_text);
+    Ok(diff
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("#{a}"), &format!("#{b}"))
+        .to_string())
+}
+
+/// 用系统自带的 Windows.Media.Ocr（按用户语言设置选择识别语言）识别 PNG 中的文字，按行拼接
+fn ocr_image(png: &[u8]) -> Result<String, AppError> {
+    let win_err =
+        |e: windows::core::Error| AppError::Other(format!("文字识别失败：{}", e.message()));
+    let mut img = image::load_from_memory(png).map_err(|e| AppError::Other(e.to_string()))?;
+    let max_dim = OcrEngine::MaxImageDimension().map_err(win_err)?;
+    if img.width() > max_dim || img.height() > max_dim {
+        img = img.resize(max_dim, max_dim, image::imageops::FilterType::Triangle);
+    }
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let mut bgra = rgba.into_raw();
+    for px in bgra.chunks_exact_mut(4) {
+        px.swap(0, 2);
+    }
+    let engine = OcrEngine::TryCreateFromUserProfileLanguages()
+        .map_err(|_| AppError::Other("系统未安装可用的 OCR 语言包".into()))?;
+    let writer = DataWriter::new().map_err(win_err)?;
+    writer.WriteBytes(&bgra).map_err(win_err)?;
+    let buffer = writer.DetachBuffer().map_err(win_err)?;
+    let bitmap = SoftwareBitmap::CreateCopyFromBuffer(
+        &buffer,
+        BitmapPixelFormat::Bgra8,
+        width as i32,
+        height as i32,
+    )
+    .map_err(win_err)?;
+    let result = engine
+        .RecognizeAsync(&bitmap)
+        .map_err(win_err)?
+        .get()
+        .map_err(win_err)?;
+    let mut lines = Vec::new();
+    for line in result.Lines().map_err(win_err)? {
+        lines.push(line.Text().map_err(win_err)?.to_string());
+    }
+    Ok(lines.join("\n"))
+}
+
+/// 识别图片条目中的文字，另存为一条可搜索的文本条目（derived_from 指向原图）
+#[tauri::command]
+fn extract_text_from_image(
+    app: AppHandle,
+    state: State<AppState>,
+    id: i64,
+) -> Result<ClipboardDto, String> {
+    ensure_not_read_only()?;
+    let settings = state.settings.lock().clone();
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let source = fetch_item(&conn, id).map_err(|e| e.to_string())?;
+    let png = match (source.content_type.as_str(), source.image_data) {
+        ("image", Some(bytes)) => bytes,
+        _ => return Err(format!("条目 {id} 不是图片")),
+    };
+    // WinRT 异步调用在独立线程上等待，不阻塞界面线程的消息循环
+    let text = thread::spawn(move || ocr_image(&png))
+        .join()
+        .map_err(|_| "文字识别线程异常退出".to_string())?
+        .map_err(|e| e.to_string())?;
+    if text.trim().is_empty() {
+        return Err("图片中没有识别到文字".into());
+    }
+    let item = ClipboardItem {
+        content_type: "text".into(),
+        text_content: Some(text),
+        source_app: source.source_app,
+        source_path: source.source_path,
+        source_icon: source.source_icon,
+        source_url: source.source_url,
+        derived_from: Some(id),
+        created_at: chrono::Utc::now().timestamp_millis(),
+        ..Default::default()
+    };
+    let dto = insert_item(&state.db_path, item, &settings).map_err(|e| e.to_string())?;
+    let _ = app.emit_all("clipboard://new", dto.clone());
+    Ok(dto)
+}
+
+/// 把多张图片按网格拼成一张（各自等比缩放进统一大小的格子），作为新条目保存
+#[tauri::command]
+fn combine_images(
+    app: AppHandle,
+    state: State<AppState>,
+    ids: Vec<i64>,
+    columns: u32,
+) -> Result<ClipboardDto, String> {
+    ensure_not_read_only()?;
+    const PADDING: u32 = 8;
+    const MAX_CELL: u32 = 512;
+    if ids.len() < 2 || ids.len() > 64 {
+        return Err("请选择 2~64 张图片".into());
+    }
+    let settings = state.settings.lock().clone();
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut images = Vec::with_capacity(ids.len());
+    for id in &ids {
+        let item = fetch_item(&conn, *id).map_err(|e| e.to_string())?;
+        let bytes = match (item.content_type.as_str(), item.image_data) {
+            ("image", Some(bytes)) => bytes,
+            _ => return Err(format!("条目 {id} 不是图片")),
+        };
+        images.push(image::load_from_memory(&bytes).map_err(|e| e.to_string())?);
+    }
+    let cell_w = images
+        .iter()
+        .map(|img| img.width())
+        .max()
+        .unwrap_or(1)
+        .min(MAX_CELL);
+    let cell_h = images
+        .iter()
+        .map(|img| img.height())
+        .max()
+        .unwrap_or(1)
+        .min(MAX_CELL);
+    let cols = columns.clamp(1, images.len() as u32);
+    let rows = (images.len() as u32).div_ceil(cols);
+    let width = cols * cell_w + (cols + 1) * PADDING;
+    let height = rows * cell_h + (rows + 1) * PADDING;
+    if width as u64 * height as u64 > settings.max_image_pixels {
+        return Err("拼接后的图片过大".into());
+    }
+    let mut sheet = image::RgbaImage::from_pixel(width, height, Rgba([255, 255, 255, 255]));
+    for (idx, img) in images.iter().enumerate() {
+        let idx = idx as u32;
+        let cell = img
+            .resize(cell_w, cell_h, image::imageops::FilterType::Triangle)
+            .to_rgba8();
+        // 在格子内居中
+        let x = PADDING + (idx % cols) * (cell_w + PADDING) + (cell_w - cell.width()) / 2;
+        let y = PADDING + (idx / cols) * (cell_h + PADDING) + (cell_h - cell.height()) / 2;
+        image::imageops::overlay(&mut sheet, &cell, x as i64, y as i64);
+    }
+    let mut cursor = Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgba8(sheet)
+        .write_to(&mut cursor, image::ImageOutputFormat::Png)
+        .map_err(|e| e.to_string())?;
+    let item = ClipboardItem {
+        content_type: "image".into(),
+        image_data: Some(cursor.into_inner()),
+        source_app: Some("Pastify".into()),
+        created_at: chrono::Utc::now().timestamp_millis(),
+        ..Default::default()
+    };
+    let dto = insert_item(&state.db_path, item, &settings).map_err(|e| e.to_string())?;
+    let _ = app.emit_all("clipboard://new", dto.clone());
+    Ok(dto)
+}
+
+/// 把一条文本按分隔符（默认换行）拆成多条，跳过空段；全部写入在同一事务内完成
+#[tauri::command]
+fn split_entry(
+    app: AppHandle,
+    state: State<AppState>,
+    id: i64,
+    delimiter: Option<String>,
+    delete_source: bool,
+) -> Result<Vec<ClipboardDto>, String> {
+    ensure_not_read_only()?;
+    let settings = state.settings.lock().clone();
+    let mut conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let source = fetch_item(&conn, id).map_err(|e| e.to_string())?;
+    let text = match (source.content_type.as_str(), source.text_content) {
+        ("image", _) | (_, None) => return Err("只能拆分文本条目".into()),
+        (_, Some(text)) => text,
+    };
+    let segments: Vec<&str> = match delimiter.as_deref().filter(|d| !d.is_empty()) {
+        Some(delimiter) => text.split(delimiter).collect(),
+        None => text.lines().collect(),
+    };
+    let segments: Vec<&str> = segments
+        .into_iter()
+        .map(str::trim)
+        .filter(|seg| !seg.is_empty())
+        .collect();
+    if segments.len() < 2 {
+        return Err("没有可拆分的内容".into());
+    }
+    let now = chrono::Utc::now().timestamp_millis();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut dtos = Vec::with_capacity(segments.len());
+    // 与 import_plaintext 一致：靠前的段视为更新
+    for (idx, segment) in segments.iter().enumerate() {
+        let item = ClipboardItem {
+            content_type: text_content_type(segment).into(),
+            text_content: Some(segment.to_string()),
+            source_app: source.source_app.clone(),
+            source_path: source.source_path.clone(),
+            source_icon: source.source_icon.clone(),
+            source_url: source.source_url.clone(),
+            created_at: now - idx as i64,
+            ..Default::default()
+        };
+        dtos.push(insert_row(&tx, item, &settings).map_err(|e| e.to_string())?);
+    }
+    if delete_source {
+        tx.execute(
+            "UPDATE clipboard_items SET deleted_at = ?1 WHERE id = ?2",
+            params![now, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    LIVE_ITEM_COUNT.store(-1, Ordering::SeqCst);
+    enforce_limit_if_needed(&conn, &state.db_path, &settings).map_err(|e| e.to_string())?;
+    state.reload_recent(&conn);
+    if delete_source {
+        let _ = app.emit_all("clipboard://removed", id);
+    }
+    for dto in dtos.iter().rev() {
+        let _ = app.emit_all("clipboard://new", dto.clone());
+    }
+    Ok(dtos)
+}
+
+/// 把时间线上连续来自 source_app 的未置顶文本条目各自合并成一条（按复制顺序换行拼接，时间取最后一条），
+/// 原条目移入回收站；置顶条目不参与并会打断连续段。全部改动在同一事务内完成，返回合并后的新条目
+#[tauri::command]
+fn collapse_consecutive(
+    app: AppHandle,
+    state: State<AppState>,
+    source_app: String,
+) -> Result<Vec<ClipboardDto>, String> {
+    ensure_not_read_only()?;
+    let settings = state.settings.lock().clone();
+    let mut conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let timeline: Vec<(i64, bool)> = {
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, content_type = 'text' AND is_pinned = 0 AND source_app IS ?1
+                 FROM clipboard_items WHERE deleted_at IS NULL ORDER BY created_at ASC, id ASC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![source_app], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+    };
+    let mut runs: Vec<Vec<i64>> = Vec::new();
+    let mut current = Vec::new();
+    for (id, eligible) in timeline {
+        if eligible {
+            current.push(id);
+        } else if !current.is_empty() {
+            runs.push(std::mem::take(&mut current));
+        }
+    }
+    runs.push(current);
+    runs.retain(|run| run.len() > 1);
+    if runs.is_empty() {
+        return Ok(Vec::new());
+    }
+    let now = chrono::Utc::now().timestamp_millis();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut dtos = Vec::with_capacity(runs.len());
+    let mut removed = Vec::new();
+    for run in &runs {
+        let mut parts = Vec::with_capacity(run.len());
+        let mut last = None;
+        for id in run {
+            let item = fetch_item(&tx, *id).map_err(|e| e.to_string())?;
+            parts.push(item.text_content.clone().unwrap_or_default());
+            last = Some(item);
+        }
+        let Some(last) = last else { continue };
+        let text = parts.join("\n");
+        let item = ClipboardItem {
+            content_type: text_content_type(&text).into(),
+            text_content: Some(text),
+            source_app: last.source_app,
+            source_path: last.source_path,
+            source_icon: last.source_icon,
+            source_url: last.source_url,
+            created_at: last.created_at,
+            ..Default::default()
+        };
+        dtos.push(insert_row(&tx, item, &settings).map_err(|e| e.to_string())?);
+        for id in run {
+            tx.execute(
+                "UPDATE clipboard_items SET deleted_at = ?1 WHERE id = ?2",
+                params![now, id],
+            )
+            .map_err(|e| e.to_string())?;
+            removed.push(*id);
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    LIVE_ITEM_COUNT.store(-1, Ordering::SeqCst);
+    state.reload_recent(&conn);
+    // 合并后的条目沿用原来的时间，不发 clipboard://new（前端会把它放到最前），由调用方重新拉取列表
+    for id in removed {
+        let _ = app.emit_all("clipboard://removed", id);
+    }
+    Ok(dtos)
+}
+
+/// 排查“复制了却没记下来”：读取耗时与各类跳过原因的计数
+#[tauri::command]
+fn get_diagnostics(state: State<AppState>) -> Diagnostics {
+    let stats = &state.stats;
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+    let reads = load(&stats.reads);
+    let to_ms = |us: u64| us as f64 / 1000.0;
+    Diagnostics {
+        reads,
+        min_ms: to_ms(load(&stats.min_us)),
+        avg_ms: to_ms(load(&stats.total_us).checked_div(reads).unwrap_or(0)),
+        max_ms: to_ms(load(&stats.max_us)),
+        saved: load(&stats.saved),
+        repeated_images: load(&stats.repeated_images),
+        skipped_own_write: load(&stats.skipped_own_write),
+        skipped_not_copy: load(&stats.skipped_not_copy),
+        skipped_ignored_format: load(&stats.skipped_ignored_format),
+        skipped_blacklist: load(&stats.skipped_blacklist),
+        skipped_private: load(&stats.skipped_private),
+        skipped_empty: load(&stats.skipped_empty),
+        skipped_duplicate: load(&stats.skipped_duplicate),
+        errors: load(&stats.errors),
+        source_from_foreground: load(&stats.source_from_foreground),
+        source_from_owner: load(&stats.source_from_owner),
+        watcher_restarts: load(&stats.watcher_restarts),
+        same_app_pastes: load(&stats.same_app_pastes),
+    }
+}
+
+#[tauri::command]
+fn get_db_size(state: State<AppState>) -> Result<u64, String> {
+    let meta = std::fs::metadata(&state.db_path).map_err(|e| e.to_string())?;
+    Ok(meta.len())
+}
+
+/// 软删除：移入回收站，超过保留天数后由 purge_trash 彻底删除
+#[tauri::command]
+fn delete_entry(state: State<AppState>, id: i64) -> Result<(), String> {
+    ensure_not_read_only()?;
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE clipboard_items SET deleted_at = ?1 WHERE id = ?2",
+        params![chrono::Utc::now().timestamp_millis(), id],
+    )
+    .map_err(|e| e.to_string())?;
+    state.forget_recent(&[id]);
+    let retention = state.settings.lock().trash_retention_days;
+    purge_trash(&state.db_path, Some(retention)).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+fn restore_entry(state: State<AppState>, id: i64) -> Result<ClipboardDto, String> {
+    ensure_not_read_only()?;
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE clipboard_items SET deleted_at = NULL WHERE id = ?1",
+        params![id],
+    )
+    .map_err(|e| e.to_string())?;
+    // 恢复会让条目数变多，计数作废
+    LIVE_ITEM_COUNT.store(-1, Ordering::SeqCst);
+    let dto = to_dto(fetch_item(&conn, id).map_err(|e| e.to_string())?);
+    state.place_recent(&conn, dto.clone());
+    Ok(dto)
+}
+
+#[tauri::command]
+fn get_trash(state: State<AppState>) -> Result<Vec<ClipboardDto>, String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {ITEM_COLUMNS} FROM clipboard_items WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC"
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], item_from_row)
+        .map_err(|e| e.to_string())?;
+    let mut result = Vec::new();
+    for item in rows {
+        result.push(to_dto(item.map_err(|e| e.to_string())?));
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+fn empty_trash(state: State<AppState>) -> Result<usize, String> {
+    ensure_not_read_only()?;
+    purge_trash(&state.db_path, None).map_err(|e| e.to_string())
+}
+
+/// 立即彻底删除单个条目：先用同长度的零覆盖内容列，再在 secure_delete 下删除并把 WAL 写回主文件后截断。
+/// 返回 false 表示有其他连接正在读取，WAL 暂未截断（下次 checkpoint 时才会清掉）
+#[tauri::command]
+fn secure_delete_entry(app: AppHandle, state: State<AppState>, id: i64) -> Result<bool, String> {
+    ensure_not_read_only()?;
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    conn.query_row("PRAGMA secure_delete = ON", [], |_| Ok(()))
+        .map_err(|e| e.to_string())?;
+    let overwritten = conn
+        .execute(
+            "UPDATE clipboard_items SET
+                text_content = CASE WHEN text_content IS NULL THEN NULL ELSE zeroblob(length(CAST(text_content AS BLOB))) END,
+                raw_text = CASE WHEN raw_text IS NULL THEN NULL ELSE zeroblob(length(CAST(raw_text AS BLOB))) END,
+                image_data = CASE WHEN image_data IS NULL THEN NULL ELSE zeroblob(length(image_data)) END,
+                thumb_data = CASE WHEN thumb_data IS NULL THEN NULL ELSE zeroblob(length(thumb_data)) END,
+                dib_data = CASE WHEN dib_data IS NULL THEN NULL ELSE zeroblob(length(dib_data)) END
+             WHERE id = ?1",
+            params![id],
+        )
+        .map_err(|e| e.to_string())?;
+    if overwritten == 0 {
+        return Err("条目不存在".into());
+    }
+    delete_items(&conn, "id = ?1", params![id], true).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM named_buffers WHERE item_id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    LIVE_ITEM_COUNT.store(-1, Ordering::SeqCst);
+    // 第一列为 1 表示被其他连接挡住，没能完整写回并截断
+    let busy: i64 = conn
+        .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if busy != 0 {
+        log::warn!("secure delete: wal checkpoint blocked by another connection");
+    }
+    let _ = app.emit_all("clipboard://removed", id);
+    Ok(busy == 0)
+}
+
+/// 彻底删除回收站条目；retention_days 为 None 时清空全部
+fn purge_trash(db_path: &Path, retention_days: Option<i64>) -> Result<usize, AppError> {
+    let conn = open_db(db_path)?;
+    let cutoff = match retention_days {
+        Some(days) => (chrono::Utc::now() - ChronoDuration::days(days)).timestamp_millis(),
+        None => i64::MAX,
+    };
+    let removed = delete_items(
+        &conn,
+        "deleted_at IS NOT NULL AND deleted_at <= ?1",
+        params![cutoff],
+        false,
+    )?;
+    conn.execute(
+        "DELETE FROM named_buffers WHERE item_id NOT IN (SELECT id FROM clipboard_items)",
+        [],
+    )?;
+    Ok(removed)
+}
+
+#[tauri::command]
+fn toggle_pin(state: State<AppState>, id: i64) -> Result<(), String> {
+    ensure_not_read_only()?;
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE clipboard_items SET is_pinned = CASE is_pinned WHEN 1 THEN 0 ELSE 1 END WHERE id = ?1",
+        params![id],
+    )
+    .map_err(|e| e.to_string())?;
+    state.refresh_recent(&conn, id);
+    Ok(())
+}
+
+/// 设置列表中显示的标题，粘贴内容不变；传空字符串恢复显示内容预览
+#[tauri::command]
+fn set_label(state: State<AppState>, id: i64, label: String) -> Result<ClipboardDto, String> {
+    ensure_not_read_only()?;
+    let label = label.trim();
+    if char_count(label) > 100 {
+        return Err("标题不能超过 100 个字符".into());
+    }
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let updated = conn
+        .execute(
+            "UPDATE clipboard_items SET label = ?1 WHERE id = ?2",
+            params![Some(label).filter(|l| !l.is_empty()), id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err("条目不存在".into());
+    }
+    state.refresh_recent(&conn, id);
+    let item = fetch_item(&conn, id).map_err(|e| e.to_string())?;
+    Ok(to_dto(item))
+}
+
+#[tauri::command]
+fn promote_entry(app: AppHandle, state: State<AppState>, id: i64) -> Result<ClipboardDto, String> {
+    ensure_not_read_only()?;
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE clipboard_items SET created_at = ?1 WHERE id = ?2",
+        params![chrono::Utc::now().timestamp_millis(), id],
+    )
+    .map_err(|e| e.to_string())?;
+    state.refresh_recent(&conn, id);
+    let dto = to_dto(fetch_item(&conn, id).map_err(|e| e.to_string())?);
+    // 复用新条目事件，前端按 id 去重后置顶
+    let _ = app.emit_all("clipboard://new", dto.clone());
+    Ok(dto)
+}
+
+/// 按记录的 source_path 重新提取图标，用于升级旧条目
+#[tauri::command]
+fn refresh_icon(state: State<AppState>, id: i64) -> Result<ClipboardDto, String> {
+    ensure_not_read_only()?;
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut item = fetch_item(&conn, id).map_err(|e| e.to_string())?;
+    let path = item
+        .source_path
+        .clone()
+        .ok_or_else(|| "该条目没有记录来源程序路径".to_string())?;
+    if !Path::new(&path).exists() {
+        return Err(format!("来源程序已不存在：{path}"));
+    }
+    let info = build_process_info(&path);
+    let icon = info
+        .icon_png
+        .ok_or_else(|| format!("无法从 {path} 提取图标"))?;
+    conn.execute(
+        "UPDATE clipboard_items SET source_icon = ?1 WHERE id = ?2",
+        params![icon, id],
+    )
+    .map_err(|e| e.to_string())?;
+    state.refresh_recent(&conn, id);
+    item.source_icon = Some(icon);
+    Ok(to_dto(item))
+}
+
+fn path_of_entry(db_path: &Path, id: i64) -> Result<PathBuf, String> {
+    let conn = open_db(db_path).map_err(|e| e.to_string())?;
+    let item = fetch_item(&conn, id).map_err(|e| e.to_string())?;
+    if item.content_type != "path" {
+        return Err("该条目不是文件路径".into());
+    }
+    // 多个文件时取第一个
+    entry_paths(item.text_content.as_deref().unwrap_or_default())
+        .into_iter()
+        .next()
+        .ok_or_else(|| "路径已不存在".to_string())
+}
+
+fn shell_execute(file: &str, parameters: Option<&str>) -> Result<(), String> {
+    let to_wide = |s: &str| -> Vec<u16> {
+        std::ffi::OsStr::new(s)
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect()
+    };
+    let file_w = to_wide(file);
+    let params_w = parameters.map(to_wide);
+    let params_ptr = params_w
+        .as_ref()
+        .map(|p| PCWSTR(p.as_ptr()))
+        .unwrap_or(PCWSTR::null());
+    let result = unsafe {
+        ShellExecuteW(
+            None,
+            w!("open"),
+            PCWSTR(file_w.as_ptr()),
+            params_ptr,
+            PCWSTR::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+    // 返回值 <= 32 表示失败
+    if result.0 <= 32 {
+        return Err(format!("无法打开 {file}（错误码 {}）", result.0));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn open_path(state: State<AppState>, id: i64) -> Result<(), String> {
+    let path = path_of_entry(&state.db_path, id)?;
+    shell_execute(&path.to_string_lossy(), None)
+}
+
+#[tauri::command]
+fn reveal_in_explorer(state: State<AppState>, id: i64) -> Result<(), String> {
+    let path = path_of_entry(&state.db_path, id)?;
+    shell_execute(
+        "explorer.exe",
+        Some(&format!("/select,\"{}\"", path.display())),
+    )
+}
+
+#[tauri::command]
+fn paste_entry(state: State<AppState>, id: i64, plain: bool) -> Result<(), String> {
+    paste_item(&state, id, plain)
+}
+
+fn paste_item(state: &AppState, id: i64, plain: bool) -> Result<(), String> {
+    let mut conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let item = fetch_item(&conn, id).map_err(|e| e.to_string())?;
+    ensure_restorable(&item)?;
+    let plain = plain || check_same_app_paste(state, &item);
+
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    let snapshot = if state.settings.lock().restore_clipboard_after_paste {
+        snapshot_clipboard(&mut clipboard)
+    } else {
+        None
+    };
+    // Avoid recording this paste as a new history entry in watcher
+    skip_own_write();
+    let drop_effect: Option<i64> = if item.content_type == "path" && !plain {
+        conn.query_row(
+            "SELECT drop_effect FROM clipboard_items WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?
+    } else {
+        None
+    };
+    let paths = entry_paths(item.text_content.as_deref().unwrap_or_default());
+    if let Some(effect) = drop_effect.filter(|_| !paths.is_empty()) {
+        // 复制文件得到的条目按文件粘贴，并带回原来的复制/剪切意图
+        let effect = u32::try_from(effect)
+            .unwrap_or(DROPEFFECT_COPY.0)
+            .to_le_bytes();
+        let text = utf16_z(item.text_content.as_deref().unwrap_or_default());
+        write_clipboard_formats(&[
+            (u32::from(CF_HDROP), &hdrop_bytes(&paths)),
+            (preferred_drop_effect_format(), &effect),
+            (u32::from(CF_UNICODETEXT), &text),
+        ])?;
+    } else if item.content_type != "image" {
+        let text = item.text_content.unwrap_or_default();
+        let mut final_text = if plain {
+            clean_plain_text(&text)
+        } else {
+            text.clone()
+        };
+        if state.settings.lock().strip_trailing_newline_on_paste {
+            if let Some(stripped) = final_text.strip_suffix('\n') {
+                final_text = stripped.strip_suffix('\r').unwrap_or(stripped).to_string();
+            }
+        }
+        clipboard.set_text(final_text).map_err(|e| e.to_string())?;
+    } else if let Some(img_bytes) = item.image_data {
+        let dib: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT dib_data FROM clipboard_items WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        let format = state.settings.lock().paste_image_format.clone();
+        match dib {
+            Some(dib) if !plain && format == "preserve" => write_clipboard_dib(&dib)?,
+            _ => write_clipboard_image_as(&mut clipboard, &img_bytes, &format)?,
+        }
+    }
+
+    unsafe {
+        simulate_paste(plain).map_err(|e| e.to_string())?;
+    }
+    if let Some(original) = snapshot {
+        restore_clipboard_later(original);
+    }
+
+    bump_usage(&mut conn, id).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 粘贴目标就是条目的来源应用时按 same_app_paste 处理，返回是否改为纯文本粘贴。
+/// 只有文本条目会改为纯文本，文件与图片条目照常粘贴，仅计数
+fn check_same_app_paste(state: &AppState, item: &ClipboardItem) -> bool {
+    let mode = state.settings.lock().same_app_paste.clone();
+    if mode == "off" {
+        return false;
+    }
+    let (Some(source), Some(target)) = (item.source_path.as_deref(), current_app_path()) else {
+        return false;
+    };
+    if !source.eq_ignore_ascii_case(&target) {
+        return false;
+    }
+    CaptureStats::bump(&state.stats.same_app_pastes);
+    if let Some(app) = APP_HANDLE.get() {
+        let _ = app.emit_all("clipboard://same-app-paste", item.id);
+    }
+    mode == "plain" && item.content_type == "text"
+}
+
+/// 标记接下来一小段时间内的剪贴板变化来自本程序，监听线程据此跳过
+fn skip_own_write() {
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    SKIP_UNTIL_MS.store(now_ms + 1200, Ordering::SeqCst);
+}
+
+fn set_clipboard_png(clipboard: &mut Clipboard, png_bytes: &[u8]) -> Result<(), String> {
+    let png = image::load_from_memory(png_bytes).map_err(|e| e.to_string())?;
+    let rgba = png.to_rgba8();
+    let (w, h) = rgba.dimensions();
+    let img_data = arboard::ImageData {
+        width: w as usize,
+        height: h as usize,
+        bytes: std::borrow::Cow::Owned(rgba.into_raw()),
+    };
+    clipboard.set_image(img_data).map_err(|e| e.to_string())
+}
+
+/// 仅写入系统剪贴板：不粘贴、不记录、不改使用次数
+#[tauri::command]
+fn set_clipboard_text(text: String) -> Result<(), String> {
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    skip_own_write();
+    clipboard.set_text(text).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_clipboard_image(data_uri: String) -> Result<(), String> {
+    let encoded = data_uri
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"))
+        .map(|(_, data)| data)
+        .ok_or_else(|| "图片需为 base64 data URI".to_string())?;
+    let bytes = BASE64.decode(encoded.trim()).map_err(|e| e.to_string())?;
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    skip_own_write();
+    set_clipboard_png(&mut clipboard, &bytes)
+}
+
+fn snapshot_clipboard(clipboard: &mut Clipboard) -> Option<ClipboardSnapshot> {
+    if let Ok(text) = clipboard.get_text() {
+        return Some(ClipboardSnapshot::Text(text));
+    }
+    clipboard.get_image().ok().map(ClipboardSnapshot::Image)
+}
+
+fn restore_clipboard_later(original: ClipboardSnapshot) {
+    thread::spawn(move || {
+        // 给目标应用留出读取剪贴板的时间
+        thread::sleep(Duration::from_millis(500));
+        // 恢复写入同样不应被记录为新条目
+        skip_own_write();
+        let result = Clipboard::new().and_then(|mut clipboard| match original {
+            ClipboardSnapshot::Text(text) => clipboard.set_text(text),
+            ClipboardSnapshot::Image(img) => clipboard.set_image(img),
+        });
+        if let Err(err) = result {
+            log::error!("restore clipboard error: {err}");
+        }
+    });
+}
+
+fn normalize_buffer_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || char_count(name) > 32 {
+        return Err("缓冲区名称需为 1~32 个字符".into());
+    }
+    Ok(name.to_string())
+}
+
+/// 类似 Vim 命名寄存器：把条目存入指定名称的槽位
+#[tauri::command]
+fn set_buffer(state: State<AppState>, name: String, id: i64) -> Result<(), String> {
+    ensure_not_read_only()?;
+    let name = normalize_buffer_name(&name)?;
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    fetch_item(&conn, id).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO named_buffers(name, item_id) VALUES(?1, ?2)",
+        params![name, id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+fn paste_buffer(state: State<AppState>, name: String) -> Result<(), String> {
+    let name = normalize_buffer_name(&name)?;
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let id: Option<i64> = conn
+        .query_row(
+            "SELECT b.item_id FROM named_buffers b JOIN clipboard_items c ON c.id = b.item_id WHERE b.name = ?1 AND c.deleted_at IS NULL",
+            params![name],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let id = id.ok_or_else(|| format!("缓冲区 {name} 为空或条目已删除"))?;
+    paste_item(&state, id, false)
+}
+
+#[tauri::command]
+fn list_buffers(state: State<AppState>) -> Result<Vec<(String, ClipboardDto)>, String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT b.name, b.item_id FROM named_buffers b JOIN clipboard_items c ON c.id = b.item_id
+             WHERE c.deleted_at IS NULL ORDER BY b.name",
+        )
+        .map_err(|e| e.to_string())?;
+    let pairs = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut result = Vec::new();
+    for (name, id) in pairs {
+        if let Some(item) = fetch_item(&conn, id)
+            .optional()
+            .map_err(|e| e.to_string())?
+        {
+            result.push((name, to_dto(item)));
+        }
+    }
+    Ok(result)
+}
+
+/// 便签与历史分开存放在 settings 表，不参与去重和条数清理
+#[tauri::command]
+fn get_scratch(state: State<AppState>) -> Result<String, String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let text: Option<String> = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = 'scratch'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(text.unwrap_or_default())
+}
+
+#[tauri::command]
+fn set_scratch(state: State<AppState>, text: String) -> Result<(), String> {
+    ensure_not_read_only()?;
+    if text.len() > 1024 * 1024 {
+        return Err("便签内容不能超过 1MB".into());
+    }
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO settings(key, value) VALUES('scratch', ?1)",
+        params![text],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+fn paste_scratch(state: State<AppState>) -> Result<(), String> {
+    let text = get_scratch(state.clone())?;
+    if text.is_empty() {
+        return Err("便签为空".into());
+    }
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    let snapshot = if state.settings.lock().restore_clipboard_after_paste {
+        snapshot_clipboard(&mut clipboard)
+    } else {
+        None
+    };
+    skip_own_write();
+    clipboard.set_text(text).map_err(|e| e.to_string())?;
+    unsafe {
+        simulate_paste(false).map_err(|e| e.to_string())?;
+    }
+    if let Some(original) = snapshot {
+        restore_clipboard_later(original);
+    }
+    Ok(())
+}
+
+fn clean_plain_text(input: &str) -> String {
+    // 轻量“纯文本”处理：去掉常见 Markdown 强调/列表/引用标记与多余空格
+    let mut result = String::with_capacity(input.len());
+    for (idx, line) in input.lines().enumerate() {
+        let mut l = line.trim_start();
+
+        // 标题 #
+        while let Some(rest) = l.strip_prefix('#') {
+            l = rest.trim_start();
+        }
+
+        // 列表、引用前缀
+        for prefix in ["- ", "* ", "+ ", "> ", "• ", "– "] {
+            if let Some(rest) = l.strip_prefix(prefix) {
+                l = rest.trim_start();
+                break;
+            }
+        }
+        if let Some(stripped) = l
+            .trim_start()
+            .strip_prefix(|c: char| c.is_ascii_digit())
+            .and_then(|s| s.strip_prefix('.'))
+        {
+            l = stripped.trim_start();
+        }
+
+        // 去掉强调/代码符号
+        let mut cleaned = l
+            .replace("**", "")
+            .replace("__", "")
+            .replace('*', "")
+            .replace('_', "")
+            .replace('`', "");
+
+        // 合并多空格
+        while cleaned.contains("  ") {
+            cleaned = cleaned.replace("  ", " ");
+        }
+
+        if idx > 0 {
+            result.push('\n');
+        }
+        result.push_str(cleaned.trim_end());
+    }
+    result
+}
+
+const CF_UNICODETEXT: u16 = 13;
+const CF_HDROP: u16 = 15;
+
+/// 拖放时提供给目标程序的数据，只支持 HGLOBAL 介质
+#[implement(IDataObject)]
+struct DragData {
+    formats: Vec<(u16, Vec<u8>)>,
+}
+
+impl DragData {
+    fn find(&self, format: *const FORMATETC) -> Option<&[u8]> {
+        let format = unsafe { format.as_ref()? };
+        if format.dwAspect != DVASPECT_CONTENT.0 || format.tymed & TYMED_HGLOBAL.0 as u32 == 0 {
+            return None;
+        }
+        self.formats
+            .iter()
+            .find(|(cf, _)| *cf == format.cfFormat)
+            .map(|(_, bytes)| bytes.as_slice())
+    }
+}
+
+impl IDataObject_Impl for DragData {
+    fn GetData(&self, format: *const FORMATETC) -> windows::core::Result<STGMEDIUM> {
+        let bytes = self
+            .find(format)
+            .ok_or(windows::core::Error::from(DV_E_FORMATETC))?;
+        unsafe {
+            let hglobal = GlobalAlloc(GMEM_MOVEABLE, bytes.len())?;
+            let ptr = GlobalLock(hglobal) as *mut u8;
+            if ptr.is_null() {
+                let _ = GlobalFree(hglobal);
+                return Err(windows::core::Error::from_win32());
+            }
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+            let _ = GlobalUnlock(hglobal);
+            // 介质交给调用方，由其 ReleaseStgMedium 释放
+            Ok(STGMEDIUM {
+                tymed: TYMED_HGLOBAL.0 as u32,
+                u: STGMEDIUM_0 { hGlobal: hglobal },
+                pUnkForRelease: std::mem::ManuallyDrop::new(None),
+            })
+        }
+    }
+
+    fn GetDataHere(
+        &self,
+        _format: *const FORMATETC,
+        _medium: *mut STGMEDIUM,
+    ) -> windows::core::Result<()> {
+        Err(E_NOTIMPL.into())
+    }
+
+    fn QueryGetData(&self, format: *const FORMATETC) -> windows::core::HRESULT {
+        if self.find(format).is_some() {
+            S_OK
+        } else {
+            DV_E_FORMATETC
+        }
+    }
+
+    fn GetCanonicalFormatEtc(
+        &self,
+        _format_in: *const FORMATETC,
+        format_out: *mut FORMATETC,
+    ) -> windows::core::HRESULT {
+        if let Some(out) = unsafe { format_out.as_mut() } {
+            out.ptd = std::ptr::null_mut();
+        }
+        DATA_S_SAMEFORMATETC
+    }
+
+    fn SetData(
+        &self,
+        _format: *const FORMATETC,
+        _medium: *const STGMEDIUM,
+        _release: BOOL,
+    ) -> windows::core::Result<()> {
+        Err(E_NOTIMPL.into())
+    }
+
+    fn EnumFormatEtc(&self, direction: u32) -> windows::core::Result<IEnumFORMATETC> {
+        if direction != DATADIR_GET.0 as u32 {
+            return Err(E_NOTIMPL.into());
+        }
+        let formats: Vec<FORMATETC> = self
+            .formats
+            .iter()
+            .map(|(cf, _)| FORMATETC {
+                cfFormat: *cf,
+                ptd: std::ptr::null_mut(),
+                dwAspect: DVASPECT_CONTENT.0,
+                lindex: -1,
+                tymed: TYMED_HGLOBAL.0 as u32,
+            })
+            .collect();
+        unsafe { SHCreateStdEnumFmtEtc(&formats) }
+    }
+
+    fn DAdvise(
+        &self,
+        _format: *const FORMATETC,
+        _advf: u32,
+        _sink: Option<&IAdviseSink>,
+    ) -> windows::core::Result<u32> {
+        Err(OLE_E_ADVISENOTSUPPORTED.into())
+    }
+
+    fn DUnadvise(&self, _connection: u32) -> windows::core::Result<()> {
+        Err(OLE_E_ADVISENOTSUPPORTED.into())
+    }
+
+    fn EnumDAdvise(&self) -> windows::core::Result<IEnumSTATDATA> {
+        Err(OLE_E_ADVISENOTSUPPORTED.into())
+    }
+}
+
+#[implement(IDropSource)]
+struct DragSource;
+
+impl IDropSource_Impl for DragSource {
+    fn QueryContinueDrag(
+        &self,
+        escape_pressed: BOOL,
+        key_state: MODIFIERKEYS_FLAGS,
+    ) -> windows::core::HRESULT {
+        if escape_pressed.as_bool() {
+            DRAGDROP_S_CANCEL
+        } else if key_state.0 & MK_LBUTTON.0 == 0 {
+            DRAGDROP_S_DROP
+        } else {
+            S_OK
+        }
+    }
+
+    fn GiveFeedback(&self, _effect: DROPEFFECT) -> windows::core::HRESULT {
+        DRAGDROP_S_USEDEFAULTCURSORS
+    }
+}
+
+fn utf16_z(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+/// 按条目类型准备拖放格式：文本 CF_UNICODETEXT，图片 CF_DIB，路径额外附带 CF_HDROP
+fn drag_formats(item: &ClipboardItem) -> Result<Vec<(u16, Vec<u8>)>, String> {
+    ensure_restorable(item)?;
+    if item.content_type == "image" {
+        let bytes = item
+            .image_data
+            .as_deref()
+            .ok_or_else(|| "图片数据缺失".to_string())?;
+        let img = image::load_from_memory(bytes).map_err(|e| e.to_string())?;
+        return Ok(vec![(CF_DIB as u16, encode_dib(&img.to_rgba8()))]);
+    }
+    let text = item.text_content.as_deref().unwrap_or_default();
+    let mut formats = Vec::new();
+    if item.content_type == "path" {
+        let paths = entry_paths(text);
+        if !paths.is_empty() {
+            formats.push((CF_HDROP, hdrop_bytes(&paths)));
+        }
+    }
+    formats.push((CF_UNICODETEXT, utf16_z(text)));
+    Ok(formats)
+}
+
+/// 在独立线程里完成 OLE 拖放，避免阻塞 WebView（它自己也可能是放置目标）；
+/// 结束后发出 pastify://drag-end，载荷为是否真正放下
+#[tauri::command]
+fn begin_drag(app: AppHandle, state: State<AppState>, id: i64) -> Result<(), String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let item = fetch_item(&conn, id).map_err(|e| e.to_string())?;
+    let formats = drag_formats(&item)?;
+    let db_path = state.db_path.clone();
+    thread::spawn(move || {
+        let mut effect = DROPEFFECT(0);
+        let result = unsafe {
+            if let Err(err) = OleInitialize(None) {
+                log::warn!("OleInitialize failed: {err}");
+                let _ = app.emit_all("pastify://drag-end", false);
+                return;
+            }
+            let data: IDataObject = DragData { formats }.into();
+            let source: IDropSource = DragSource.into();
+            let hr = DoDragDrop(&data, &source, DROPEFFECT_COPY, &mut effect);
+            OleUninitialize();
+            hr
+        };
+        // 取消（Esc 或目标拒收）不算错误，也不计入使用次数
+        let dropped = result == DRAGDROP_S_DROP && effect.0 != 0;
+        if result != DRAGDROP_S_DROP && result != DRAGDROP_S_CANCEL {
+            log::warn!("DoDragDrop failed: {result:?}");
+        }
+        if dropped {
+            if let Ok(mut conn) = open_db(&db_path) {
+                let _ = bump_usage(&mut conn, id);
+            }
+        }
+        let _ = app.emit_all("pastify://drag-end", dropped);
+    });
+    Ok(())
+}
+
+#[tauri::command]
+fn copy_entry(state: State<AppState>, id: i64) -> Result<(), String> {
+    let mut conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let item = fetch_item(&conn, id).map_err(|e| e.to_string())?;
+    ensure_restorable(&item)?;
+
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    // Avoid duplicating the same item into history when we set clipboard ourselves
+    skip_own_write();
+
+    if item.content_type != "image" {
+        let text = item.text_content.unwrap_or_default();
+        clipboard.set_text(text).map_err(|e| e.to_string())?;
+    } else if let Some(img_bytes) = item.image_data {
+        set_clipboard_png(&mut clipboard, &img_bytes)?;
+    }
+
+    bump_usage(&mut conn, id).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+unsafe fn simulate_paste(_plain: bool) -> Result<(), AppError> {
+    // 统一发送 Ctrl+V，由于我们已写入纯文本到剪贴板，目标应用会按文本粘贴
+    let inputs = [
+        // Ctrl down
+        INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: VIRTUAL_KEY(VK_CONTROL.0 as u16),
+                    wScan: 0,
+                    dwFlags: KEYBD_EVENT_FLAGS(0),
+                    time: 0,
+                    dwExtraInfo: 0,
+                },
+            },
+        },
+        // V down
+        INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: VIRTUAL_KEY(VK_V.0 as u16),
+                    wScan: 0,
+                    dwFlags: KEYBD_EVENT_FLAGS(0),
+                    time: 0,
+                    dwExtraInfo: 0,
+                },
+            },
+        },
+        // V up
+        INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: VIRTUAL_KEY(VK_V.0 as u16),
+                    wScan: 0,
+                    dwFlags: KEYEVENTF_KEYUP,
+                    time: 0,
+                    dwExtraInfo: 0,
+                },
+            },
+        },
+        // Ctrl up
+        INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: VIRTUAL_KEY(VK_CONTROL.0 as u16),
+                    wScan: 0,
+                    dwFlags: KEYEVENTF_KEYUP,
+                    time: 0,
+                    dwExtraInfo: 0,
+                },
+            },
+        },
+    ];
+    let sent = SendInput(&inputs, std::mem::size_of::<INPUT>() as i32);
+    if sent == 0 {
+        return Err(AppError::Other("发送粘贴快捷键失败".into()));
+    }
+    Ok(())
+}
+
+fn content_exists(conn: &Connection, item: &ClipboardItem) -> Result<bool, AppError> {
+    Ok(find_existing(conn, item)?.is_some())
+}
+
+/// 查找内容完全相同且未删除的条目
+fn find_existing(conn: &Connection, item: &ClipboardItem) -> Result<Option<i64>, AppError> {
+    let found: Option<i64> = if item.content_type != "image" {
+        conn.query_row(
+            "SELECT id FROM clipboard_items WHERE content_type != 'image' AND text_content = ?1 AND deleted_at IS NULL LIMIT 1",
+            params![item.text_content],
+            |row| row.get(0),
+        )
+        .optional()?
+    } else {
+        conn.query_row(
+            "SELECT id FROM clipboard_items WHERE content_type = 'image' AND image_data = ?1 AND deleted_at IS NULL LIMIT 1",
+            params![item.image_data],
+            |row| row.get(0),
+        )
+        .optional()?
+    };
+    if let (None, Some(bytes)) = (found, &item.image_data) {
+        return find_disk_image(conn, bytes, "1", &[]);
+    }
+    Ok(found)
+}
+
+fn imported_item(
+    content_type: &str,
+    text: Option<String>,
+    image: Option<Vec<u8>>,
+    created_at: i64,
+) -> ClipboardItem {
+    ClipboardItem {
+        id: 0,
+        content_type: content_type.into(),
+        text_content: text,
+        image_data: image,
+        source_app: Some("Imported".into()),
+        source_path: None,
+        source_icon: None,
+        source_url: None,
+        created_at,
+        is_pinned: false,
+        usage_count: 0,
+        ..Default::default()
+    }
+}
+
+/// 导入纯文本文件：含换页符时按换页符分条，否则按行分条
+#[tauri::command]
+fn import_plaintext(state: State<AppState>, path: String) -> Result<usize, String> {
+    ensure_not_read_only()?;
+    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let chunks: Vec<&str> = if content.contains('\u{c}') {
+        content.split('\u{c}').collect()
+    } else {
+        content.lines().collect()
+    };
+    let settings = state.settings.lock().clone();
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut imported = 0;
+    for (idx, chunk) in chunks.iter().enumerate() {
+        let text = chunk.trim_matches(|c| c == '\r' || c == '\n');
+        if text.trim().is_empty() {
+            continue;
+        }
+        // 文件中靠前的条目视为更新
+        let item = imported_item("text", Some(text.to_string()), None, now - idx as i64);
+        if content_exists(&conn, &item).map_err(|e| e.to_string())? {
+            continue;
+        }
+        insert_item(&state.db_path, item, &settings).map_err(|e| e.to_string())?;
+        imported += 1;
+    }
+    Ok(imported)
+}
+
+/// 导入目录下的 PNG/JPEG 图片，统一转为 PNG 存储
+#[tauri::command]
+fn import_images_from_dir(state: State<AppState>, dir: String) -> Result<usize, String> {
+    ensure_not_read_only()?;
+    let settings = state.settings.lock().clone();
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| matches!(ext.to_lowercase().as_str(), "png" | "jpg" | "jpeg"))
+                .unwrap_or(false)
+        })
+        .collect();
+    paths.sort();
+    let mut imported = 0;
+    for path in paths {
+        let dims = image::io::Reader::open(&path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(|e| e.to_string())
+            .and_then(|reader| reader.into_dimensions().map_err(|e| e.to_string()))
+            .and_then(|(w, h)| checked_pixel_count(w, h, settings.max_image_pixels));
+        if let Err(reason) = dims {
+            log::warn!("skip image {}: {reason}", path.display());
+            continue;
+        }
+        let img = match image::open(&path) {
+            Ok(img) => img,
+            Err(err) => {
+                log::warn!("skip image {}: {err}", path.display());
+                continue;
+            }
+        };
+        let mut cursor = Cursor::new(Vec::new());
+        img.write_to(&mut cursor, image::ImageOutputFormat::Png)
+            .map_err(|e| e.to_string())?;
+        let created_at = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+        let item = imported_item("image", None, Some(cursor.into_inner()), created_at);
+        if content_exists(&conn, &item).map_err(|e| e.to_string())? {
+            continue;
+        }
+        insert_item(&state.db_path, item, &settings).map_err(|e| e.to_string())?;
+        imported += 1;
+    }
+    Ok(imported)
+}
+
+// Windows FILETIME 纪元（1601-01-01）与 Unix 纪元之间的 100ns 间隔数
+const FILETIME_UNIX_EPOCH: i64 = 116_444_736_000_000_000;
+
+/// 从系统剪贴板历史（Win+V）导入文本和图片，与已有内容重复的跳过；系统未开启剪贴板历史时返回错误
+#[tauri::command]
+fn import_windows_history(state: State<AppState>) -> Result<usize, String> {
+    ensure_not_read_only()?;
+    let win_err = |e: windows::core::Error| format!("读取系统剪贴板历史失败：{}", e.message());
+    if !WinRtClipboard::IsHistoryEnabled().map_err(win_err)? {
+        return Err("系统剪贴板历史未开启（设置 → 系统 → 剪贴板）".into());
+    }
+    let result = WinRtClipboard::GetHistoryItemsAsync()
+        .map_err(win_err)?
+        .get()
+        .map_err(win_err)?;
+    match result.Status().map_err(win_err)? {
+        ClipboardHistoryItemsResultStatus::Success => {}
+        ClipboardHistoryItemsResultStatus::AccessDenied => {
+            return Err("没有权限读取系统剪贴板历史".into())
+        }
+        _ => return Err("系统剪贴板历史未开启（设置 → 系统 → 剪贴板）".into()),
+    }
+    let settings = state.settings.lock().clone();
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let mut imported = 0;
+    for history_item in result.Items().map_err(win_err)? {
+        let created_at = history_item
+            .Timestamp()
+            .map(|t| (t.UniversalTime - FILETIME_UNIX_EPOCH) / 10_000)
+            .unwrap_or_else(|_| chrono::Utc::now().timestamp_millis());
+        let content = history_item.Content().map_err(win_err)?;
+        let mut item = match read_history_package(&content, settings.record_images, created_at) {
+            Ok(Some(item)) => item,
+            Ok(None) => continue,
+            Err(err) => {
+                log::warn!("skip windows clipboard history item: {}", err.message());
+                continue;
+            }
+        };
+        item.source_app = Some("Windows Clipboard".into());
+        if content_exists(&conn, &item).map_err(|e| e.to_string())? {
+            continue;
+        }
+        insert_item(&state.db_path, item, &settings).map_err(|e| e.to_string())?;
+        imported += 1;
+    }
+    Ok(imported)
+}
+
+/// 取出一条系统剪贴板历史的内容（文本优先），图片统一转成 PNG；都没有时返回 None
+fn read_history_package(
+    content: &DataPackageView,
+    record_images: bool,
+    created_at: i64,
+) -> windows::core::Result<Option<ClipboardItem>> {
+    if content.Contains(&StandardDataFormats::Text()?)? {
+        let text = content.GetTextAsync()?.get()?.to_string();
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+        return Ok(Some(imported_item(
+            text_content_type(&text),
+            Some(text),
+            None,
+            created_at,
+        )));
+    }
+    if record_images && content.Contains(&StandardDataFormats::Bitmap()?)? {
+        let stream = content.GetBitmapAsync()?.get()?.OpenReadAsync()?.get()?;
+        let Ok(size) = u32::try_from(stream.Size()?) else {
+            log::warn!("skip clipboard history image: stream too large");
+            return Ok(None);
+        };
+        let reader = DataReader::CreateDataReader(&stream.GetInputStreamAt(0)?)?;
+        reader.LoadAsync(size)?.get()?;
+        let mut bytes = vec![0u8; size as usize];
+        reader.ReadBytes(&mut bytes)?;
+        let Ok(img) = image::load_from_memory(&bytes) else {
+            return Ok(None);
+        };
+        let mut cursor = Cursor::new(Vec::new());
+        if img
+            .write_to(&mut cursor, image::ImageOutputFormat::Png)
+            .is_err()
+        {
+            return Ok(None);
+        }
+        return Ok(Some(imported_item(
+            "image",
+            None,
+            Some(cursor.into_inner()),
+            created_at,
+        )));
+    }
+    Ok(None)
+}
+
+/// 后台线程执行导入/导出等批量任务，立即返回操作 id；进度通过 pastify://io-progress 推送，结束时发 pastify://io-done。
+/// job 返回（成功条数，跳过条数）
+fn spawn_io_op<F>(app: AppHandle, kind: &'static str, job: F) -> u64
+where
+    F: FnOnce(&dyn Fn(usize, usize)) -> Result<(usize, usize), AppError> + Send + 'static,
+{
+    let op_id = NEXT_IO_OP.fetch_add(1, Ordering::SeqCst);
+    thread::spawn(move || {
+        let last_sent = std::cell::Cell::new(0usize);
+        let progress = |processed: usize, total: usize| {
+            // 每 100 条及结束时推送一次，避免事件过密
+            if processed >= last_sent.get() + 100 || processed == total {
+                last_sent.set(processed);
+                let _ = app.emit_all(
+                    "pastify://io-progress",
+                    IoProgress {
+                        op_id,
+                        kind,
+                        processed,
+                        total,
+                    },
+                );
+            }
+        };
+        let done = match job(&progress) {
+            Ok((count, skipped)) => IoDone {
+                op_id,
+                kind,
+                count,
+                skipped,
+                error: None,
+            },
+            Err(err) => {
+                log::error!("{kind} failed: {err}");
+                IoDone {
+                    op_id,
+                    kind,
+                    count: 0,
+                    skipped: 0,
+                    error: Some(err.to_string()),
+                }
+            }
+        };
+        let _ = app.emit_all("pastify://io-done", done);
+    });
+    op_id
+}
+
+/// 把单个条目按自身类型写成文件：图片为 .png，单行网址为 .url（InternetShortcut），其余为 .txt。
+/// 扩展名与类型不符时改为对应扩展名，返回实际写入的路径
+#[tauri::command]
+fn export_entry(state: State<AppState>, id: i64, path: String) -> Result<String, String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let item = fetch_item(&conn, id).map_err(|e| e.to_string())?;
+    let path = PathBuf::from(path);
+    let (ext, bytes) = match (
+        item.content_type.as_str(),
+        item.image_data,
+        item.text_content,
+    ) {
+        ("image", Some(png), _) => ("png", png),
+        (_, _, Some(text)) => {
+            let trimmed = text.trim();
+            let is_url = !trimmed.contains(char::is_whitespace)
+                && (trimmed.starts_with("http://") || trimmed.starts_with("https://"));
+            if is_url {
+                (
+                    "url",
+                    format!("[InternetShortcut]\r\nURL={trimmed}\r\n").into_bytes(),
+                )
+            } else {
+                ("txt", text.into_bytes())
+            }
+        }
+        _ => return Err("条目没有可导出的内容".into()),
+    };
+    let path = if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(ext))
+    {
+        path
+    } else {
+        path.with_extension(ext)
+    };
+    std::fs::write(&path, bytes).map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// iso_timestamps 为 "secs" 或 "millis" 时每行额外写入该精度的 created_at_iso
+#[tauri::command]
+fn export_history(
+    app: AppHandle,
+    state: State<AppState>,
+    path: String,
+    iso_timestamps: Option<String>,
+) -> Result<u64, String> {
+    let precision = match iso_timestamps.as_deref() {
+        None => None,
+        Some("secs") => Some(chrono::SecondsFormat::Secs),
+        Some("millis") => Some(chrono::SecondsFormat::Millis),
+        Some(other) => return Err(format!("未知的时间精度：{other}")),
+    };
+    let db_path = state.db_path.clone();
+    Ok(spawn_io_op(app, "export", move |progress| {
+        // 每个后台任务使用独立连接，靠 busy_timeout 与监听线程错开写入
+        let conn = open_db(&db_path)?;
+        let total: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM clipboard_items WHERE deleted_at IS NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        let total = total as usize;
+        let mut writer = BufWriter::new(std::fs::File::create(&path)?);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {ITEM_COLUMNS} FROM clipboard_items WHERE deleted_at IS NULL ORDER BY created_at ASC"
+        ))?;
+        let mut rows = stmt.query([])?;
+        let mut processed = 0;
+        while let Some(row) = rows.next()? {
+            let mut item = item_from_row(row)?;
+            load_image_file(&mut item);
+            let exported = ExportedItem {
+                content_type: item.content_type,
+                text_content: item.text_content,
+                image_data: item.image_data.map(|bytes| BASE64.encode(bytes)),
+                source_app: item.source_app,
+                source_url: item.source_url,
+                created_at: item.created_at,
+                created_at_iso: precision.and_then(|p| format_timestamp(item.created_at, p)),
+                is_pinned: item.is_pinned,
+                usage_count: item.usage_count,
+            };
+            let line =
+                serde_json::to_string(&exported).map_err(|e| AppError::Other(e.to_string()))?;
+            writeln!(writer, "{line}")?;
+            processed += 1;
+            progress(processed, total);
+        }
+        writer.flush()?;
+        if processed != total {
+            // 导出期间条目数有变化时补一次完成进度
+            progress(processed, processed);
+        }
+        Ok((processed, 0))
+    }))
+}
+
+#[tauri::command]
+fn import_history(app: AppHandle, state: State<AppState>, path: String) -> Result<u64, String> {
+    ensure_not_read_only()?;
+    let db_path = state.db_path.clone();
+    let settings = state.settings.lock().clone();
+    Ok(spawn_io_op(app, "import", move |progress| {
+        let lines = BufReader::new(std::fs::File::open(&path)?)
+            .lines()
+            .collect::<Result<Vec<_>, _>>()?;
+        let total = lines.len();
+        let conn = open_db(&db_path)?;
+        let (mut imported, mut skipped) = (0, 0);
+        for (idx, line) in lines.iter().enumerate() {
+            progress(idx + 1, total);
+            if line.trim().is_empty() {
+                continue;
+            }
+            let exported: ExportedItem = match serde_json::from_str(line) {
+                Ok(v) => v,
+                Err(err) => {
+                    log::warn!("skip import line {}: {err}", idx + 1);
+                    skipped += 1;
+                    continue;
+                }
+            };
+            let image = match exported
+                .image_data
+                .map(|data| BASE64.decode(data))
+                .transpose()
+            {
+                Ok(image) => image,
+                Err(err) => {
+                    log::warn!("skip import line {}: {err}", idx + 1);
+                    skipped += 1;
+                    continue;
+                }
+            };
+            let mut item = imported_item(
+                &exported.content_type,
+                exported.text_content,
+                image,
+                exported.created_at,
+            );
+            item.source_app = exported.source_app.or(item.source_app);
+            item.source_url = exported.source_url;
+            item.is_pinned = exported.is_pinned;
+            if content_exists(&conn, &item)? {
+                continue;
+            }
+            insert_item(&db_path, item, &settings)?;
+            imported += 1;
+        }
+        Ok((imported, skipped))
+    }))
+}
+
+/// 给同步工具用：在一个事务里用传入的条目整体替换历史（含回收站）。先校验全部输入，
+/// 任何一条不合法都不做修改；新条目与原有置顶条目内容相同时保留置顶。返回写入条数
+#[tauri::command]
+fn replace_history(state: State<AppState>, items: Vec<ExportedItem>) -> Result<usize, String> {
+    ensure_not_read_only()?;
+    let settings = state.settings.lock().clone();
+    let count = replace_history_at(&state.db_path, &settings, items)?;
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    state.reload_recent(&conn);
+    Ok(count)
+}
+
+fn replace_history_at(
+    db_path: &Path,
+    settings: &Settings,
+    items: Vec<ExportedItem>,
+) -> Result<usize, String> {
+    let mut prepared = Vec::with_capacity(items.len());
+    for (idx, input) in items.into_iter().enumerate() {
+        let invalid = |reason: &str| format!("第 {} 条无效：{reason}", idx + 1);
+        if input.created_at <= 0 {
+            return Err(invalid("created_at 必须为正数"));
+        }
+        let image = match input.content_type.as_str() {
+            "image" => {
+                let data = input
+                    .image_data
+                    .as_deref()
+                    .ok_or_else(|| invalid("缺少图片数据"))?;
+                let bytes = BASE64.decode(data).map_err(|e| invalid(&e.to_string()))?;
+                image::guess_format(&bytes).map_err(|_| invalid("无法识别的图片格式"))?;
+                Some(bytes)
+            }
+            "text" | "path" => {
+                if input
+                    .text_content
+                    .as_deref()
+                    .is_none_or(|t| t.trim().is_empty())
+                {
+                    return Err(invalid("文本为空"));
+                }
+                None
+            }
+            other => return Err(invalid(&format!("未知类型 {other}"))),
+        };
+        let mut item = imported_item(
+            &input.content_type,
+            input.text_content,
+            image,
+            input.created_at,
+        );
+        item.source_app = input.source_app.or(item.source_app);
+        item.source_url = input.source_url;
+        item.is_pinned = input.is_pinned;
+        item.usage_count = input.usage_count.max(0);
+        prepared.push(item);
+    }
+
+    let mut conn = open_db(db_path).map_err(|e| e.to_string())?;
+    let tx = conn
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(|e| e.to_string())?;
+    let old_max: i64 = tx
+        .query_row(
+            "SELECT COALESCE(MAX(id), 0) FROM clipboard_items",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let pinned = pinned_contents(&tx).map_err(|e| e.to_string())?;
+    let count = prepared.len();
+    let mut inserted = Vec::with_capacity(count);
+    for mut item in prepared {
+        if let Some((image, content)) = pin_content(&item) {
+            item.is_pinned |= pinned
+                .get(&(image, fnv1a(FNV_OFFSET, content)))
+                .is_some_and(|candidates| candidates.iter().any(|old| old == content));
+        }
+        let usage = item.usage_count;
+        let dto = insert_row(&tx, item, settings).map_err(|e| e.to_string())?;
+        inserted.push(dto.id);
+        if usage > 0 {
+            tx.execute(
+                "UPDATE clipboard_items SET usage_count = ?1 WHERE id = ?2",
+                params![usage, dto.id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+    let removed = delete_rows(&tx, "id <= ?1", params![old_max]).map_err(|e| e.to_string())?;
+    tx.execute(
+        "DELETE FROM named_buffers WHERE item_id NOT IN (SELECT id FROM clipboard_items)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    // 文件改动都放在提交之后：旧图片这时才能删，新图片这时才迁出
+    remove_image_files(&removed, false);
+    if settings.store_images_on_disk {
+        if let Err(err) = externalize_rows(&conn, &inserted) {
+            log::warn!("keep replaced images in database: {err}");
+        }
+    }
+    LIVE_ITEM_COUNT.store(-1, Ordering::SeqCst);
+    enforce_limit_if_needed(&conn, db_path, settings).map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+/// 置顶比对的内容：图片比原图字节，其余类型比文本
+fn pin_content(item: &ClipboardItem) -> Option<(bool, &[u8])> {
+    if item.content_type == "image" {
+        item.image_data.as_deref().map(|bytes| (true, bytes))
+    } else {
+        item.text_content
+            .as_deref()
+            .map(|text| (false, text.as_bytes()))
+    }
+}
+
+/// 置顶条目内容按 (是否图片, 内容哈希) 分组
+type PinnedContents = std::collections::HashMap<(bool, u64), Vec<Vec<u8>>>;
+
+/// 收集未删除的置顶条目，哈希相同再逐字节比较；外存图片从文件读取
+fn pinned_contents(conn: &Connection) -> rusqlite::Result<PinnedContents> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ITEM_COLUMNS} FROM clipboard_items WHERE is_pinned = 1 AND deleted_at IS NULL"
+    ))?;
+    let mut pinned = PinnedContents::new();
+    for item in stmt.query_map([], item_from_row)? {
+        let mut item = item?;
+        load_image_file(&mut item);
+        if let Some((image, content)) = pin_content(&item) {
+            pinned
+                .entry((image, fnv1a(FNV_OFFSET, content)))
+                .or_default()
+                .push(content.to_vec());
+        }
+    }
+    Ok(pinned)
 }
 
 #[tauri::command]
-fn copy_entry(state: State<AppState>, id: i64) -> Result<(), String> {
-    let conn = Connection::open(&state.db_path).map_err(|e| e.to_string())?;
-    let item: ClipboardItem = conn
-        .query_row(
-            "SELECT id, content_type, text_content, image_data, source_app, source_path, source_icon, created_at, is_pinned, usage_count FROM clipboard_items WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(ClipboardItem {
-                    id: row.get(0)?,
-                    content_type: row.get(1)?,
-                    text_content: row.get(2)?,
-                    image_data: row.get(3)?,
-                    source_app: row.get(4)?,
-                    source_path: row.get(5)?,
-                    source_icon: row.get(6)?,
-                    created_at: row.get(7)?,
-                    is_pinned: row.get::<_, i32>(8)? != 0,
-                    usage_count: row.get(9)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
+fn get_settings(state: State<AppState>) -> Result<Settings, String> {
+    load_settings(&state.db_path).map_err(|e| e.to_string())
+}
 
-    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
-    let now_ms = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_err(|e| e.to_string())?
-        .as_millis() as u64;
-    // Avoid duplicating the same item into history when we set clipboard ourselves
-    SKIP_UNTIL_MS.store(now_ms + 1200, Ordering::SeqCst);
+#[tauri::command]
+fn update_settings(
+    app: AppHandle,
+    state: State<AppState>,
+    settings: Settings,
+) -> Result<Settings, String> {
+    ensure_not_read_only()?;
+    let mut normalized = settings;
+    normalized.record_images = true;
+    normalized.hotkey = parse_hotkey(&normalized.hotkey)?;
+    if !normalized.plain_paste_hotkey.trim().is_empty() {
+        normalized.plain_paste_hotkey = parse_hotkey(&normalized.plain_paste_hotkey)?;
+        if normalized.plain_paste_hotkey == normalized.hotkey {
+            return Err("纯文本粘贴快捷键不能与呼出快捷键相同".into());
+        }
+    } else {
+        normalized.plain_paste_hotkey.clear();
+    }
+    if !normalized.paste_mode_hotkey.trim().is_empty() {
+        normalized.paste_mode_hotkey = parse_hotkey(&normalized.paste_mode_hotkey)?;
+        if normalized.paste_mode_hotkey == normalized.hotkey
+            || normalized.paste_mode_hotkey == normalized.plain_paste_hotkey
+        {
+            return Err("切换粘贴方式的快捷键不能与其他快捷键相同".into());
+        }
+    } else {
+        normalized.paste_mode_hotkey.clear();
+    }
+    normalized.blacklist = normalize_blacklist(&normalized.blacklist)?;
+    let mut formats: Vec<String> = Vec::new();
+    for name in normalized
+        .ignored_formats
+        .iter()
+        .map(|f| f.trim())
+        .filter(|f| !f.is_empty())
+    {
+        if !formats.iter().any(|f| f.eq_ignore_ascii_case(name)) {
+            formats.push(name.to_string());
+        }
+    }
+    normalized.ignored_formats = formats;
+    if !(64..=1024).contains(&normalized.thumb_max_dim) {
+        return Err("缩略图尺寸需在 64~1024 之间".into());
+    }
+    if normalized.min_capture_interval_ms > 5000 {
+        return Err("采集间隔不能超过 5000 毫秒".into());
+    }
+    if normalized.max_text_items < 0 || normalized.max_image_items < 0 {
+        return Err("分类上限不能为负数，0 表示不单独限制".into());
+    }
+    if !matches!(
+        normalized.pinned_display.as_str(),
+        "top" | "separate" | "inline"
+    ) {
+        return Err("置顶显示方式只能是 top、separate 或 inline".into());
+    }
+    if !matches!(
+        normalized.capture_preference.as_str(),
+        "text" | "image" | "both"
+    ) {
+        return Err("采集偏好只能是 text、image 或 both".into());
+    }
+    if !matches!(
+        normalized.same_app_paste.as_str(),
+        "off" | "plain" | "record"
+    ) {
+        return Err("粘贴回来源应用时的处理只能是 off、plain 或 record".into());
+    }
+    if !matches!(
+        normalized.duplicate_policy.as_str(),
+        "ignore" | "bump" | "always_new"
+    ) {
+        return Err("重复处理方式只能是 ignore、bump 或 always_new".into());
+    }
+    if !matches!(
+        normalized.paste_image_format.as_str(),
+        "preserve" | "png" | "bmp"
+    ) {
+        return Err("粘贴图片格式只能是 preserve、png 或 bmp".into());
+    }
+    let previous = state.settings.lock().clone();
+    if !normalized.pinned_hotkey_modifier.trim().is_empty() {
+        let sample = parse_hotkey(&format!("{}+1", normalized.pinned_hotkey_modifier))?;
+        normalized.pinned_hotkey_modifier = sample.trim_end_matches("+1").to_string();
+        let taken = (1..=9).map(|n| format!("{}+{n}", normalized.pinned_hotkey_modifier));
+        if taken.into_iter().any(|hk| {
+            hk == normalized.hotkey
+                || hk == normalized.plain_paste_hotkey
+                || hk == normalized.paste_mode_hotkey
+        }) {
+            return Err("置顶条目快捷键与其他快捷键冲突".into());
+        }
+    } else {
+        normalized.pinned_hotkey_modifier.clear();
+    }
+    // 每次保存都重新读取，便于编辑映射文件后直接生效
+    load_app_name_map(&normalized.app_name_map_path)?;
+    if let Err(err) = register_hotkey(&app, &normalized) {
+        // 注册失败时恢复原快捷键，避免呼出方式丢失
+        let _ = register_hotkey(&app, &previous);
+        return Err(err);
+    }
+    *HOTKEY_ERROR.lock() = None;
+    save_settings(&state.db_path, &normalized).map_err(|e| e.to_string())?;
+    *state.settings.lock() = normalized.clone();
+    set_copy_hook(normalized.capture_on_copy_only || normalized.classify_capture_source);
+    Ok(normalized)
+}
 
-    if item.content_type == "text" {
-        let text = item.text_content.unwrap_or_default();
-        clipboard.set_text(text).map_err(|e| e.to_string())?;
-    } else if let Some(img_bytes) = item.image_data {
-        let png = image::load_from_memory(&img_bytes).map_err(|e| e.to_string())?;
-        let rgba = png.to_rgba8();
-        let (w, h) = rgba.dimensions();
-        let img_data = arboard::ImageData {
-            width: w as usize,
-            height: h as usize,
-            bytes: std::borrow::Cow::Owned(rgba.into_raw()),
+fn store_blacklist(
+    state: &AppState,
+    update: impl FnOnce(&mut Vec<String>),
+) -> Result<Vec<String>, String> {
+    let mut settings = state.settings.lock();
+    let mut list = settings.blacklist.clone();
+    update(&mut list);
+    let list = normalize_blacklist(&list)?;
+    settings.blacklist = list.clone();
+    save_settings(&state.db_path, &settings).map_err(|e| e.to_string())?;
+    Ok(list)
+}
+
+/// 单独添加一个黑名单应用，返回规范化后的完整列表
+#[tauri::command]
+fn add_blacklist_entry(state: State<AppState>, name: String) -> Result<Vec<String>, String> {
+    ensure_not_read_only()?;
+    if name.trim().is_empty() {
+        return Err("应用名称不能为空".into());
+    }
+    store_blacklist(&state, |list| list.push(name))
+}
+
+#[tauri::command]
+fn remove_blacklist_entry(state: State<AppState>, name: String) -> Result<Vec<String>, String> {
+    ensure_not_read_only()?;
+    let target = fold_app_name(&name);
+    store_blacklist(&state, |list| list.retain(|b| fold_app_name(b) != target))
+}
+
+/// 导出当前设置为 JSON 文件，便于备份或在多台电脑间同步；与历史导出无关
+#[tauri::command]
+fn export_settings(state: State<AppState>, path: String) -> Result<(), String> {
+    let settings = state.settings.lock().clone();
+    let json = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| e.to_string())
+}
+
+/// 导入设置文件：缺少的字段取默认值，不认识的字段忽略并返回其名称，其余走 update_settings 校验并重新注册快捷键
+#[tauri::command]
+fn import_settings(
+    app: AppHandle,
+    state: State<AppState>,
+    path: String,
+) -> Result<(Settings, Vec<String>), String> {
+    ensure_not_read_only()?;
+    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let imported: serde_json::Value =
+        serde_json::from_str(&content).map_err(|e| format!("设置文件不是有效的 JSON：{e}"))?;
+    let serde_json::Value::Object(imported) = imported else {
+        return Err("设置文件顶层必须是对象".into());
+    };
+    let serde_json::Value::Object(mut merged) =
+        serde_json::to_value(&*SETTINGS_DEFAULT).map_err(|e| e.to_string())?
+    else {
+        return Err("无法生成默认设置".into());
+    };
+    let mut unknown = Vec::new();
+    for (key, value) in imported {
+        if merged.contains_key(&key) {
+            merged.insert(key, value);
+        } else {
+            unknown.push(key);
+        }
+    }
+    if !unknown.is_empty() {
+        log::warn!("ignored unknown settings fields: {}", unknown.join(", "));
+    }
+    let settings: Settings = serde_json::from_value(serde_json::Value::Object(merged))
+        .map_err(|e| format!("设置文件字段类型错误：{e}"))?;
+    let saved = update_settings(app, state, settings)?;
+    Ok((saved, unknown))
+}
+
+fn set_copy_hook(enabled: bool) {
+    if enabled {
+        if COPY_HOOK_THREAD
+            .compare_exchange(0, u32::MAX, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            thread::spawn(run_copy_hook);
+        }
+    } else {
+        stop_copy_hook();
+    }
+}
+
+/// 钩子线程已装好（非 0 且不是启动中的占位值）
+fn copy_hook_active() -> bool {
+    !matches!(COPY_HOOK_THREAD.load(Ordering::SeqCst), 0 | u32::MAX)
+}
+
+fn copy_key_recent(now_ms: u64) -> bool {
+    now_ms.saturating_sub(COPY_KEY_AT_MS.load(Ordering::SeqCst)) <= COPY_KEY_WINDOW_MS
+}
+
+fn stop_copy_hook() {
+    let thread_id = COPY_HOOK_THREAD.swap(0, Ordering::SeqCst);
+    if thread_id != 0 && thread_id != u32::MAX {
+        unsafe {
+            let _ = PostThreadMessageW(thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
+        }
+    }
+}
+
+/// 低级键盘钩子需要所在线程持续泵消息，收到 WM_QUIT 后卸载
+fn run_copy_hook() {
+    unsafe {
+        let thread_id = GetCurrentThreadId();
+        if COPY_HOOK_THREAD
+            .compare_exchange(u32::MAX, thread_id, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return;
+        }
+        let hook = match SetWindowsHookExW(WH_KEYBOARD_LL, Some(copy_key_hook), None, 0) {
+            Ok(hook) => hook,
+            Err(err) => {
+                log::error!("install keyboard hook failed: {err}");
+                let _ = COPY_HOOK_THREAD.compare_exchange(
+                    thread_id,
+                    0,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                );
+                return;
+            }
         };
-        clipboard.set_image(img_data).map_err(|e| e.to_string())?;
+        let mut msg = MSG::default();
+        while GetMessageW(&mut msg, None, 0, 0).0 > 0 {}
+        let _ = UnhookWindowsHookEx(hook);
+        let _ = COPY_HOOK_THREAD.compare_exchange(thread_id, 0, Ordering::SeqCst, Ordering::SeqCst);
+    }
+}
+
+unsafe extern "system" fn copy_key_hook(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    let message = wparam.0 as u32;
+    if code == HC_ACTION as i32 && (message == WM_KEYDOWN || message == WM_SYSKEYDOWN) {
+        let kb = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
+        let ctrl_down = GetAsyncKeyState(VK_CONTROL.0 as i32) < 0;
+        let vk = kb.vkCode as u16;
+        if ctrl_down && (vk == VK_C.0 || vk == VK_X.0 || vk == VK_INSERT.0) {
+            let now_ms = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64;
+            COPY_KEY_AT_MS.store(now_ms, Ordering::SeqCst);
+        }
     }
+    CallNextHookEx(None, code, wparam, lparam)
+}
 
-    conn.execute(
-        "UPDATE clipboard_items SET usage_count = usage_count + 1 WHERE id = ?1",
-        params![id],
-    )
-    .map_err(|e| e.to_string())?;
+#[derive(Debug, Clone, Serialize)]
+struct WatcherPanic {
+    message: String,
+    restarts: u64,
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".into())
+}
+
+/// 启动监听线程，调用方负责把返回的句柄存进 WATCHER_THREAD
+fn spawn_clipboard_watcher(app: AppHandle, state: AppState) -> thread::JoinHandle<()> {
+    thread::spawn(move || loop {
+        // 某条内容让 read_clipboard panic 时不让整个监听永久停掉：记录、通知前端，稍后重新进入循环。
+        // 重启后从当前序列号开始，导致 panic 的那次变化不会再被读取
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            watch_clipboard(&app, &state)
+        }));
+        let Err(payload) = result else { break };
+        let restarts = state.stats.watcher_restarts.fetch_add(1, Ordering::Relaxed) + 1;
+        let message = panic_message(payload.as_ref());
+        log::error!("clipboard watcher panicked (restart #{restarts}): {message}");
+        let _ = app.emit_all(
+            "clipboard://watcher-panic",
+            WatcherPanic { message, restarts },
+        );
+        let mut slept = 0;
+        while slept < WATCHER_RESTART_DELAY_MS && !WATCHER_STOP.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(50));
+            slept += 50;
+        }
+        if WATCHER_STOP.load(Ordering::SeqCst) {
+            break;
+        }
+    })
+}
+
+/// 监听循环本体，WATCHER_STOP 置位后返回
+fn watch_clipboard(app: &AppHandle, state: &AppState) {
+    let db_path = &state.db_path;
+    let settings = &state.settings;
+    let stats = &state.stats;
+    let mut last_seq = unsafe { GetClipboardSequenceNumber() };
+    let mut failures: u32 = 0;
+    let mut last_checkpoint = Instant::now();
+    let mut wal_dirty = false;
+    let mut last_expiry_check = Instant::now();
+    loop {
+        // 连续失败时指数退避，避免剪贴板被长期占用时每 250ms 刷一次日志
+        let backoff = WATCH_INTERVAL_MS
+            .saturating_mul(1 << failures.min(5))
+            .min(WATCH_MAX_BACKOFF_MS);
+        // 分段睡眠，退出时不必等满整个退避时间
+        let mut slept = 0;
+        while slept < backoff && !WATCHER_STOP.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(50));
+            slept += 50;
+        }
+        if WATCHER_STOP.load(Ordering::SeqCst) {
+            break;
+        }
+        WATCHER_HEARTBEAT_MS.store(unix_millis(), Ordering::Relaxed);
+        // 连续大量采集时自动 checkpoint 可能一直被读事务挡住，这里定期补一次 PASSIVE，不阻塞其他连接
+        if wal_dirty && last_checkpoint.elapsed() >= WAL_CHECKPOINT_INTERVAL {
+            match open_db(db_path)
+                .and_then(|conn| conn.query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |_| Ok(())))
+            {
+                Ok(()) => wal_dirty = false,
+                Err(err) => log::warn!("passive wal checkpoint failed: {err}"),
+            }
+            last_checkpoint = Instant::now();
+        }
+        if !*READ_ONLY && last_expiry_check.elapsed() >= EXPIRY_CHECK_INTERVAL {
+            match purge_expired(db_path) {
+                Ok(ids) => {
+                    for id in ids {
+                        wal_dirty = true;
+                        let _ = app.emit_all("clipboard://removed", id);
+                    }
+                }
+                Err(err) => log::warn!("purge expired items failed: {err}"),
+            }
+            last_expiry_check = Instant::now();
+        }
+        let seq = unsafe { GetClipboardSequenceNumber() };
+        LAST_SEEN_SEQ.store(u64::from(seq), Ordering::Relaxed);
+        if seq == last_seq {
+            continue;
+        }
+        last_seq = seq;
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        if *READ_ONLY {
+            continue;
+        }
+        if now_ms < SKIP_UNTIL_MS.load(Ordering::SeqCst) {
+            CaptureStats::bump(&stats.skipped_own_write);
+            continue;
+        }
+        if settings.lock().capture_on_copy_only && !copy_key_recent(now_ms) {
+            CaptureStats::bump(&stats.skipped_not_copy);
+            continue;
+        }
+        let quiet_ms = settings.lock().min_capture_interval_ms;
+        let seq = if quiet_ms > 0 {
+            wait_for_quiet_clipboard(seq, quiet_ms)
+        } else {
+            seq
+        };
+        last_seq = seq;
+        let ignored = settings.lock().ignored_formats.clone();
+        if only_ignored_formats(&ignored) {
+            CaptureStats::bump(&stats.skipped_ignored_format);
+            continue;
+        }
+        let started = Instant::now();
+        let result = read_clipboard(db_path, state);
+        stats.record_read(started.elapsed());
+        if result.is_ok() && failures > 0 {
+            log::info!("clipboard watch recovered after {failures} failures");
+            failures = 0;
+        }
+        match result {
+            Ok(Some(dto)) => {
+                wal_dirty = true;
+                LAST_CAPTURE_MS.store(unix_millis(), Ordering::Relaxed);
+                let _ = app.emit_all("clipboard://new", dto);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                CaptureStats::bump(&stats.errors);
+                failures = failures.saturating_add(1);
+                // 只在第 1、2、4、8… 次失败时记录并通知前端
+                if failures.is_power_of_two() {
+                    log::error!("clipboard watch {} error (x{failures}): {err}", err.kind());
+                    let _ = app.emit_all(
+                        "clipboard://error",
+                        WatchError {
+                            kind: err.kind(),
+                            message: err.to_string(),
+                            failures,
+                        },
+                    );
+                }
+                // 剪贴板被占用时下一轮重试同一次变化；写库等其他错误不重试
+                if matches!(err, AppError::Clipboard(_)) {
+                    last_seq = seq.wrapping_sub(1);
+                }
+            }
+        }
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[derive(Debug, Serialize)]
+struct CaptureHealth {
+    /// 监听线程仍在运行（没有因 panic 退出）
+    watcher_alive: bool,
+    /// 距上次心跳的毫秒数；正常情况下不超过退避上限，等待剪贴板静止或远程桌面重试时会稍长
+    heartbeat_age_ms: Option<u64>,
+    last_capture_at: Option<u64>,
+    last_seen_seq: u32,
+    current_seq: u32,
+}
+
+/// 排查“监听线程已经停了却没人发现”：线程是否存活、心跳、最近一次成功记录的时间和序列号。
+/// current_seq 与 last_seen_seq 相差很大且心跳停滞时，说明监听已停止
+#[tauri::command]
+fn get_capture_health() -> CaptureHealth {
+    let alive = WATCHER_THREAD
+        .lock()
+        .as_ref()
+        .is_some_and(|handle| !handle.is_finished());
+    let heartbeat = WATCHER_HEARTBEAT_MS.load(Ordering::Relaxed);
+    let last_capture = LAST_CAPTURE_MS.load(Ordering::Relaxed);
+    CaptureHealth {
+        watcher_alive: alive,
+        heartbeat_age_ms: (heartbeat > 0).then(|| unix_millis().saturating_sub(heartbeat)),
+        last_capture_at: (last_capture > 0).then_some(last_capture),
+        last_seen_seq: LAST_SEEN_SEQ.load(Ordering::Relaxed) as u32,
+        current_seq: unsafe { GetClipboardSequenceNumber() },
+    }
+}
+
+/// 监听线程已退出时重新启动；仍在运行时不做任何事
+#[tauri::command]
+fn restart_watcher(app: AppHandle, state: State<AppState>) -> Result<(), String> {
+    // 全程持有锁：shutdown 也在同一把锁下置位退出标记，两者不会交错
+    let mut guard = WATCHER_THREAD.lock();
+    if SHUTTING_DOWN.load(Ordering::SeqCst) {
+        return Err("程序正在退出".into());
+    }
+    if let Some(handle) = guard.take() {
+        if !handle.is_finished() {
+            *guard = Some(handle);
+            return Ok(());
+        }
+        if handle.join().is_err() {
+            log::warn!("clipboard watcher had panicked, restarting");
+        }
+    }
+    WATCHER_STOP.store(false, Ordering::SeqCst);
+    *guard = Some(spawn_clipboard_watcher(app, state.inner().clone()));
     Ok(())
 }
 
-unsafe fn simulate_paste(_plain: bool) -> Result<(), AppError> {
-    // 统一发送 Ctrl+V，由于我们已写入纯文本到剪贴板，目标应用会按文本粘贴
-    let inputs = [
-        // Ctrl down
-        INPUT {
-            r#type: INPUT_KEYBOARD,
-            Anonymous: INPUT_0 {
-                ki: KEYBDINPUT {
-                    wVk: VIRTUAL_KEY(VK_CONTROL.0 as u16),
-                    wScan: 0,
-                    dwFlags: KEYBD_EVENT_FLAGS(0),
-                    time: 0,
-                    dwExtraInfo: 0,
-                },
-            },
-        },
-        // V down
-        INPUT {
-            r#type: INPUT_KEYBOARD,
-            Anonymous: INPUT_0 {
-                ki: KEYBDINPUT {
-                    wVk: VIRTUAL_KEY(VK_V.0 as u16),
-                    wScan: 0,
-                    dwFlags: KEYBD_EVENT_FLAGS(0),
-                    time: 0,
-                    dwExtraInfo: 0,
-                },
-            },
-        },
-        // V up
-        INPUT {
-            r#type: INPUT_KEYBOARD,
-            Anonymous: INPUT_0 {
-                ki: KEYBDINPUT {
-                    wVk: VIRTUAL_KEY(VK_V.0 as u16),
-                    wScan: 0,
-                    dwFlags: KEYEVENTF_KEYUP,
-                    time: 0,
-                    dwExtraInfo: 0,
-                },
-            },
-        },
-        // Ctrl up
-        INPUT {
-            r#type: INPUT_KEYBOARD,
-            Anonymous: INPUT_0 {
-                ki: KEYBDINPUT {
-                    wVk: VIRTUAL_KEY(VK_CONTROL.0 as u16),
-                    wScan: 0,
-                    dwFlags: KEYEVENTF_KEYUP,
-                    time: 0,
-                    dwExtraInfo: 0,
-                },
-            },
-        },
-    ];
-    let sent = SendInput(&inputs, std::mem::size_of::<INPUT>() as i32);
-    if sent == 0 {
-        return Err(AppError::Other("发送粘贴快捷键失败".into()));
+/// 等剪贴板连续 quiet_ms 没有变化后返回最新的序列号，一次操作里的多次写入只读一次；
+/// 最多等 10 倍间隔，免得持续改写剪贴板的程序让监听一直卡在这里
+fn wait_for_quiet_clipboard(mut seq: u32, quiet_ms: u64) -> u32 {
+    let quiet = Duration::from_millis(quiet_ms);
+    let deadline = Instant::now() + quiet * 10;
+    let mut quiet_since = Instant::now();
+    while quiet_since.elapsed() < quiet
+        && Instant::now() < deadline
+        && !WATCHER_STOP.load(Ordering::SeqCst)
+    {
+        thread::sleep(Duration::from_millis(quiet_ms.min(50)));
+        let current = unsafe { GetClipboardSequenceNumber() };
+        if current != seq {
+            seq = current;
+            quiet_since = Instant::now();
+        }
+    }
+    seq
+}
+
+/// 退出时停止监听线程（最多等 1 秒让它写完当前这条），按设置清空历史，再把 WAL 合并回主库。
+/// 退出与系统注销都会调用，需可重复执行
+fn shutdown(state: &AppState) {
+    let db_path = &state.db_path;
+    let handle = {
+        let mut guard = WATCHER_THREAD.lock();
+        SHUTTING_DOWN.store(true, Ordering::SeqCst);
+        WATCHER_STOP.store(true, Ordering::SeqCst);
+        guard.take()
+    };
+    if let Some(handle) = handle {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while !handle.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        if handle.is_finished() {
+            let _ = handle.join();
+        } else {
+            log::warn!("clipboard watcher did not stop in time");
+        }
+    }
+    let (clear, include_pinned) = {
+        let settings = state.settings.lock();
+        (settings.clear_on_exit, settings.clear_pinned_on_exit)
+    };
+    if clear && !*READ_ONLY {
+        match clear_history(db_path, include_pinned) {
+            Ok(removed) => log::info!("cleared {removed} items on exit"),
+            Err(err) => log::error!("clear on exit failed: {err}"),
+        }
+    }
+    match open_db(db_path) {
+        Ok(conn) => {
+            if let Err(err) = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(())) {
+                log::warn!("wal checkpoint failed: {err}");
+            }
+            if let Err((_, err)) = conn.close() {
+                log::warn!("close db failed: {err}");
+            }
+        }
+        Err(err) => log::warn!("open db on shutdown failed: {err}"),
+    }
+}
+
+/// 彻底删除历史（含回收站），secure_delete 让被删内容在文件里也被覆盖
+fn clear_history(db_path: &Path, include_pinned: bool) -> Result<usize, AppError> {
+    let conn = open_db(db_path)?;
+    conn.query_row("PRAGMA secure_delete = ON", [], |_| Ok(()))?;
+    let removed = delete_items(
+        &conn,
+        if include_pinned { "1" } else { "is_pinned = 0" },
+        [],
+        true,
+    )?;
+    conn.execute(
+        "DELETE FROM named_buffers WHERE item_id NOT IN (SELECT id FROM clipboard_items)",
+        [],
+    )?;
+    LIVE_ITEM_COUNT.store(-1, Ordering::SeqCst);
+    Ok(removed)
+}
+
+/// 隐藏的顶层窗口只为接收 WM_ENDSESSION：注销/关机时进程可能不经过 RunEvent::Exit 就被结束
+fn spawn_session_end_watcher(state: AppState) {
+    if SESSION_STATE.set(state).is_err() {
+        return;
+    }
+    thread::spawn(|| unsafe {
+        let Ok(instance) = GetModuleHandleW(None) else {
+            return;
+        };
+        let class_name = w!("PastifySessionWatcher");
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(session_wnd_proc),
+            hInstance: instance.into(),
+            lpszClassName: class_name,
+            ..Default::default()
+        };
+        if RegisterClassW(&class) == 0 {
+            log::warn!("register session watcher class failed");
+            return;
+        }
+        let hwnd = CreateWindowExW(
+            WINDOW_EX_STYLE(0),
+            class_name,
+            w!(""),
+            WINDOW_STYLE(0),
+            0,
+            0,
+            0,
+            0,
+            None,
+            None,
+            instance,
+            None,
+        );
+        if hwnd.0 == 0 {
+            log::warn!("create session watcher window failed");
+            return;
+        }
+        let mut msg = MSG::default();
+        while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+            let _ = TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    });
+}
+
+unsafe extern "system" fn session_wnd_proc(
+    hwnd: windows::Win32::Foundation::HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    // wParam 为 TRUE 表示会话确实要结束
+    if msg == WM_ENDSESSION && wparam.0 != 0 {
+        if let Some(state) = SESSION_STATE.get() {
+            shutdown(state);
+        }
+    }
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+/// 规范化快捷键字符串：修饰键统一大小写并按 Ctrl/Alt/Shift/Super 排序，主键唯一
+fn parse_hotkey(raw: &str) -> Result<String, String> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Ok(SETTINGS_DEFAULT.hotkey.clone());
+    }
+    let mut mods = [false; 4]; // Ctrl, Alt, Shift, Super
+    let mut key: Option<String> = None;
+    for part in raw.split('+').map(str::trim) {
+        if part.is_empty() {
+            return Err(format!("快捷键格式无效：{raw}"));
+        }
+        let slot = match part.to_lowercase().as_str() {
+            "ctrl" | "control" | "commandorcontrol" | "cmdorctrl" => Some(0),
+            "alt" | "option" => Some(1),
+            "shift" => Some(2),
+            "super" | "win" | "meta" | "cmd" | "command" => Some(3),
+            _ => None,
+        };
+        if let Some(idx) = slot {
+            if mods[idx] {
+                return Err(format!("修饰键重复：{part}"));
+            }
+            mods[idx] = true;
+            continue;
+        }
+        if let Some(existing) = &key {
+            return Err(format!("只能有一个主键，已有 {existing}，多余 {part}"));
+        }
+        key = Some(normalize_hotkey_key(part).ok_or_else(|| format!("不支持的按键：{part}"))?);
+    }
+    let key = key.ok_or_else(|| format!("缺少主键：{raw}"))?;
+    let is_function_key = key.len() > 1 && key.starts_with('F') && key[1..].parse::<u8>().is_ok();
+    if !mods.iter().any(|m| *m) && !is_function_key {
+        return Err(format!("快捷键至少需要一个修饰键：{raw}"));
+    }
+    let mut parts: Vec<String> = ["Ctrl", "Alt", "Shift", "Super"]
+        .iter()
+        .zip(mods.iter())
+        .filter(|(_, on)| **on)
+        .map(|(name, _)| name.to_string())
+        .collect();
+    parts.push(key);
+    Ok(parts.join("+"))
+}
+
+fn normalize_hotkey_key(part: &str) -> Option<String> {
+    let lower = part.to_lowercase();
+    if lower.chars().count() == 1 {
+        let c = lower.chars().next()?;
+        return c
+            .is_ascii_alphanumeric()
+            .then(|| c.to_ascii_uppercase().to_string());
+    }
+    if let Some(num) = lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+        return (1..=24).contains(&num).then(|| format!("F{num}"));
+    }
+    let named = match lower.as_str() {
+        "space" => "Space",
+        "tab" => "Tab",
+        "enter" | "return" => "Enter",
+        "esc" | "escape" => "Escape",
+        "backspace" => "Backspace",
+        "delete" | "del" => "Delete",
+        "insert" | "ins" => "Insert",
+        "home" => "Home",
+        "end" => "End",
+        "pageup" | "pgup" => "PageUp",
+        "pagedown" | "pgdn" => "PageDown",
+        "up" | "arrowup" => "Up",
+        "down" | "arrowdown" => "Down",
+        "left" | "arrowleft" => "Left",
+        "right" | "arrowright" => "Right",
+        _ => return None,
+    };
+    Some(named.to_string())
+}
+
+fn register_hotkey(app: &AppHandle, settings: &Settings) -> Result<(), String> {
+    let plain_paste_hotkey = &settings.plain_paste_hotkey;
+    let pinned_modifier = &settings.pinned_hotkey_modifier;
+    let hk = parse_hotkey(&settings.hotkey)?;
+    let mut gsm = app.global_shortcut_manager();
+    let _ = gsm.unregister_all();
+    let app_handle = app.clone();
+    gsm.register(&hk, move || on_hotkey(&app_handle))
+        .map_err(|e| format!("快捷键 {hk} 注册失败（可能已被其他程序占用）：{e}"))?;
+    if !plain_paste_hotkey.trim().is_empty() {
+        let plain_hk = parse_hotkey(plain_paste_hotkey)?;
+        let app_handle = app.clone();
+        gsm.register(&plain_hk, move || on_plain_paste_hotkey(&app_handle))
+            .map_err(|e| format!("快捷键 {plain_hk} 注册失败（可能已被其他程序占用）：{e}"))?;
+    }
+    if !settings.paste_mode_hotkey.trim().is_empty() {
+        let mode_hk = parse_hotkey(&settings.paste_mode_hotkey)?;
+        let app_handle = app.clone();
+        gsm.register(&mode_hk, move || on_paste_mode_hotkey(&app_handle))
+            .map_err(|e| format!("快捷键 {mode_hk} 注册失败（可能已被其他程序占用）：{e}"))?;
+    }
+    if !pinned_modifier.is_empty() {
+        // 个别数字被占用不影响其余快捷键
+        for slot in 1..=9usize {
+            let hk = format!("{pinned_modifier}+{slot}");
+            let app_handle = app.clone();
+            if let Err(err) = gsm.register(&hk, move || on_pinned_hotkey(&app_handle, slot)) {
+                log::warn!("register {hk} failed: {err}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 启动时注册快捷键；失败不影响启动，改用默认呼出快捷键再试一次，并通过 pastify://hotkey-error 告知前端
+fn register_startup_hotkey(app: &AppHandle, settings: &Settings) {
+    let Err(message) = register_hotkey(app, settings) else {
+        return;
+    };
+    log::error!("register hotkey failed: {message}");
+    let default_hotkey = &SETTINGS_DEFAULT.hotkey;
+    let mut fallback = None;
+    if !settings.hotkey.eq_ignore_ascii_case(default_hotkey) {
+        let fallback_settings = Settings {
+            hotkey: default_hotkey.clone(),
+            ..settings.clone()
+        };
+        match register_hotkey(app, &fallback_settings) {
+            Ok(()) => {
+                log::warn!("fell back to default hotkey {default_hotkey}");
+                fallback = Some(default_hotkey.clone());
+            }
+            Err(err) => log::error!("register default hotkey failed: {err}"),
+        }
+    }
+    let error = HotkeyError { message, fallback };
+    *HOTKEY_ERROR.lock() = Some(error.clone());
+    let _ = app.emit_all("pastify://hotkey-error", error);
+}
+
+#[tauri::command]
+fn get_hotkey_error() -> Option<HotkeyError> {
+    HOTKEY_ERROR.lock().clone()
+}
+
+fn on_paste_mode_hotkey(app: &AppHandle) {
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    let plain = !state.settings.lock().default_plain_paste;
+    if let Err(err) = store_paste_mode(app, &state, plain) {
+        log::error!("toggle paste mode error: {err}");
     }
+}
+
+/// 保存默认粘贴方式并通知前端，快捷键与 set_paste_mode 共用
+fn store_paste_mode(app: &AppHandle, state: &AppState, plain: bool) -> Result<(), String> {
+    ensure_not_read_only()?;
+    let mut settings = state.settings.lock();
+    settings.default_plain_paste = plain;
+    save_settings(&state.db_path, &settings).map_err(|e| e.to_string())?;
+    let _ = app.emit_all("pastify://paste-mode", plain);
     Ok(())
 }
 
+/// 默认粘贴方式：true 为纯文本
 #[tauri::command]
-fn get_settings(state: State<AppState>) -> Result<Settings, String> {
-    load_settings(&state.db_path).map_err(|e| e.to_string())
+fn get_paste_mode(state: State<AppState>) -> bool {
+    state.settings.lock().default_plain_paste
 }
 
 #[tauri::command]
-fn update_settings(app: AppHandle, state: State<AppState>, settings: Settings) -> Result<Settings, String> {
-    let mut normalized = settings;
-    normalized.record_images = true;
-    save_settings(&state.db_path, &normalized).map_err(|e| e.to_string())?;
-    *state.settings.lock() = normalized.clone();
-    register_hotkey(&app, &normalized.hotkey)?;
-    Ok(normalized)
+fn set_paste_mode(app: AppHandle, state: State<AppState>, plain: bool) -> Result<(), String> {
+    store_paste_mode(&app, &state, plain)
 }
 
-fn spawn_clipboard_watcher(app: AppHandle, state: AppState) {
-    let db_path = state.db_path.clone();
-    let settings = state.settings.clone();
+/// 按下时再查第 slot 个置顶条目（与列表顺序一致），置顶变化后无需重新注册
+fn on_pinned_hotkey(app: &AppHandle, slot: usize) {
+    let app = app.clone();
     thread::spawn(move || {
-        let mut last_seq = unsafe { GetClipboardSequenceNumber() };
-        loop {
-            thread::sleep(Duration::from_millis(250));
-            let seq = unsafe { GetClipboardSequenceNumber() };
-            if seq == last_seq {
-                continue;
-            }
-            last_seq = seq;
-            let now_ms = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_millis() as u64;
-            if now_ms < SKIP_UNTIL_MS.load(Ordering::SeqCst) {
-                continue;
+        wait_modifiers_released();
+        let Some(state) = app.try_state::<AppState>() else {
+            return;
+        };
+        let result = open_db(&state.db_path)
+            .and_then(|conn| {
+                conn.query_row(
+                    "SELECT id FROM clipboard_items WHERE is_pinned = 1 AND deleted_at IS NULL
+                     ORDER BY created_at DESC LIMIT 1 OFFSET ?1",
+                    params![slot as i64 - 1],
+                    |row| row.get::<_, i64>(0),
+                )
+                .optional()
+            })
+            .map_err(|e| e.to_string())
+            .and_then(|id| match id {
+                Some(id) => paste_item(&state, id, false),
+                None => Ok(()),
+            });
+        if let Err(err) = result {
+            log::error!("pinned paste {slot} error: {err}");
+        }
+    });
+}
+
+/// 不打开窗口，直接把最新一条按纯文本粘贴到当前应用
+fn on_plain_paste_hotkey(app: &AppHandle) {
+    let app = app.clone();
+    thread::spawn(move || {
+        // 用户仍按着快捷键的修饰键时模拟 Ctrl+V 会变成别的组合
+        wait_modifiers_released();
+        if let Some(state) = app.try_state::<AppState>() {
+            if let Err(err) = quick_paste_latest(&state, true) {
+                log::error!("plain paste error: {err}");
             }
-            let snapshot = AppState {
-                db_path: db_path.clone(),
-                settings: settings.clone(),
-            };
-            match read_clipboard(&db_path, &snapshot) {
-                Ok(Some(dto)) => {
-                    let _ = app.emit_all("clipboard://new", dto);
+        }
+    });
+}
+
+fn wait_modifiers_released() {
+    let modifiers = [VK_SHIFT, VK_CONTROL, VK_MENU, VK_LWIN, VK_RWIN];
+    for _ in 0..50 {
+        let held = modifiers
+            .iter()
+            .any(|vk| unsafe { GetAsyncKeyState(vk.0 as i32) } < 0);
+        if !held {
+            return;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn on_hotkey(app: &AppHandle) {
+    let Some(win) = app.get_window("main") else {
+        return;
+    };
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let last_ms = LAST_HOTKEY_MS.swap(now_ms, Ordering::SeqCst);
+    let focused = win.is_visible().unwrap_or(false) && win.is_focused().unwrap_or(false);
+    let quick_paste = app
+        .try_state::<AppState>()
+        .map(|state| state.settings.lock().quick_paste_on_double_press)
+        .unwrap_or(false);
+    if quick_paste && focused && now_ms.saturating_sub(last_ms) < QUICK_PASTE_WINDOW_MS {
+        LAST_HOTKEY_MS.store(0, Ordering::SeqCst);
+        let _ = win.hide();
+        let app = app.clone();
+        thread::spawn(move || {
+            // 等焦点回到上一个应用再模拟粘贴
+            thread::sleep(Duration::from_millis(80));
+            if let Some(state) = app.try_state::<AppState>() {
+                let plain = state.settings.lock().default_plain_paste;
+                if let Err(err) = quick_paste_latest(&state, plain) {
+                    log::error!("quick paste error: {err}");
                 }
-                Ok(None) => {}
-                Err(err) => {
-                    log::error!("clipboard watch error: {err}");
+            }
+        });
+        return;
+    }
+    if !focused {
+        *PREVIOUS_APP_PATH.lock() = process_info_from_foreground(false).map(|info| info.path);
+    }
+    let _ = win.show();
+    let _ = win.set_focus();
+}
+
+/// 当前前台应用；前台是 Pastify 自己时取呼出窗口前记下的应用
+fn current_app_path() -> Option<String> {
+    let own = std::env::current_exe()
+        .ok()
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match process_info_from_foreground(false) {
+        Some(info) if !info.path.eq_ignore_ascii_case(&own) => Some(info.path),
+        _ => PREVIOUS_APP_PATH.lock().clone(),
+    }
+}
+
+fn quick_paste_latest(state: &AppState, plain: bool) -> Result<(), String> {
+    let conn = open_db(&state.db_path).map_err(|e| e.to_string())?;
+    let latest: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM clipboard_items WHERE deleted_at IS NULL ORDER BY created_at DESC LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match latest {
+        Some(id) => paste_item(state, id, plain),
+        None => Ok(()),
+    }
+}
+
+/// 依次尝试 app_data_dir、app_local_data_dir 与临时目录，返回第一个可写的目录
+fn resolve_data_dir(app: &AppHandle) -> Option<PathBuf> {
+    let resolver = app.path_resolver();
+    let candidates = [
+        ("app_data_dir", resolver.app_data_dir()),
+        ("app_local_data_dir", resolver.app_local_data_dir()),
+        (
+            "temp_dir",
+            Some(std::env::temp_dir().join("com.pastify.app")),
+        ),
+    ];
+    for (idx, (label, dir)) in candidates.into_iter().enumerate() {
+        let Some(dir) = dir else {
+            log::warn!("{label} unavailable");
+            continue;
+        };
+        match ensure_writable(&dir) {
+            Ok(()) => {
+                if idx == 0 {
+                    log::info!("data dir: {}", dir.display());
+                } else {
+                    log::warn!("falling back to {label}: {}", dir.display());
                 }
+                return Some(dir);
             }
+            Err(err) => log::warn!("{label} not writable ({}): {err}", dir.display()),
         }
-    });
+    }
+    None
 }
 
-fn register_hotkey(app: &AppHandle, hotkey: &str) -> Result<(), String> {
-    let mut gsm = app.global_shortcut_manager();
-    let _ = gsm.unregister_all();
-    let hk = if hotkey.is_empty() { "Ctrl+Shift+V" } else { hotkey };
-    let app_handle = app.clone();
-    gsm
-        .register(hk, move || {
-            if let Some(win) = app_handle.get_window("main") {
-                let _ = win.show();
-                let _ = win.set_focus();
-            }
-        })
-        .map_err(|e| e.to_string())
+fn ensure_writable(dir: &Path) -> io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let probe = dir.join(".write_test");
+    std::fs::write(&probe, b"ok")?;
+    std::fs::remove_file(probe)
+}
+
+#[tauri::command]
+fn get_data_path(state: State<AppState>) -> String {
+    state.db_path.display().to_string()
 }
 
 fn main() {
     init_logger();
     tauri::Builder::default()
         .setup(|app| {
-            let app_dir = app
-                .path_resolver()
-                .app_data_dir()
+            let app_dir = resolve_data_dir(&app.app_handle())
                 .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "无法获取数据目录"))?;
-            std::fs::create_dir_all(&app_dir)?;
             let db_path = app_dir.join("pastify.db");
             ensure_db(&db_path).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-            let settings = load_settings(&db_path).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+            let settings = load_settings(&db_path)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+            if let Err(err) = purge_trash(&db_path, Some(settings.trash_retention_days)) {
+                log::warn!("purge trash failed: {err}");
+            }
+            if let Err(err) = load_app_name_map(&settings.app_name_map_path) {
+                log::warn!("{err}");
+            }
             let state = AppState {
                 db_path: db_path.clone(),
                 settings: Arc::new(Mutex::new(settings.clone())),
+                stats: Arc::default(),
+                recent: Arc::default(),
             };
+            match open_db(&db_path) {
+                Ok(conn) => state.reload_recent(&conn),
+                Err(err) => log::warn!("load recent cache failed: {err}"),
+            }
             app.manage(state);
-            register_hotkey(&app.app_handle(), &settings.hotkey).ok();
+            let _ = APP_HANDLE.set(app.app_handle());
+            register_startup_hotkey(&app.app_handle(), &settings);
+            set_copy_hook(settings.capture_on_copy_only || settings.classify_capture_source);
             if let Some(state) = app.try_state::<AppState>() {
-                spawn_clipboard_watcher(app.app_handle(), state.inner().clone());
+                *WATCHER_THREAD.lock() = Some(spawn_clipboard_watcher(
+                    app.app_handle(),
+                    state.inner().clone(),
+                ));
+                spawn_session_end_watcher(state.inner().clone());
             }
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             get_history,
+            copy_search_results,
+            is_read_only,
+            get_largest,
+            list_sources,
+            get_db_size,
+            get_diagnostics,
+            get_table,
+            get_image_data,
+            copy_as_data_uri,
+            get_raw_text,
+            regenerate_thumbnails,
+            diff_entries,
+            combine_images,
+            extract_text_from_image,
+            pin_current,
+            preview_pruning,
+            split_entry,
             delete_entry,
+            restore_entry,
+            get_trash,
+            empty_trash,
             toggle_pin,
+            promote_entry,
+            set_label,
+            refresh_icon,
+            open_path,
+            reveal_in_explorer,
             paste_entry,
+            set_buffer,
+            paste_buffer,
+            list_buffers,
+            get_scratch,
+            set_scratch,
+            paste_scratch,
             copy_entry,
+            set_clipboard_text,
+            set_clipboard_image,
             get_settings,
-            update_settings
+            update_settings,
+            get_paste_mode,
+            set_paste_mode,
+            export_settings,
+            import_settings,
+            get_data_path,
+            import_plaintext,
+            import_images_from_dir,
+            import_history,
+            export_history,
+            export_entry,
+            add_blacklist_entry,
+            remove_blacklist_entry,
+            begin_drag,
+            find_similar,
+            set_autodelete,
+            replace_history,
+            get_history_compact,
+            get_entries,
+            export_markdown,
+            recapture_now,
+            stream_history,
+            collapse_consecutive,
+            set_expiry,
+            get_hotkey_error,
+            count_history,
+            import_windows_history,
+            get_filter_suggestions,
+            secure_delete_entry,
+            get_capture_health,
+            restart_watcher,
+            externalize_images,
+            get_recent,
+            verify_history,
+            get_recent_cached
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                stop_copy_hook();
+                if let Some(state) = app.try_state::<AppState>() {
+                    shutdown(&state);
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // LIVE_ITEM_COUNT 是全局计数，用到测试库的用例逐个执行
+    static DB_TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    /// 临时目录下的测试库，结束时连同 WAL 文件一起删除
+    struct TempDb {
+        path: PathBuf,
+        _lock: parking_lot::MutexGuard<'static, ()>,
+    }
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            let guard = DB_TEST_LOCK.lock();
+            let path =
+                std::env::temp_dir().join(format!("pastify-test-{}-{name}.db", std::process::id()));
+            let db = TempDb { path, _lock: guard };
+            db.cleanup();
+            ensure_db(&db.path).unwrap();
+            LIVE_ITEM_COUNT.store(-1, Ordering::SeqCst);
+            db
+        }
+
+        fn cleanup(&self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = std::fs::remove_file(format!("{}{suffix}", self.path.display()));
+            }
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            self.cleanup();
+        }
+    }
+
+    fn insert_text(db_path: &Path, text: &str) -> i64 {
+        let item = imported_item(
+            "text",
+            Some(text.into()),
+            None,
+            chrono::Utc::now().timestamp_millis(),
+        );
+        insert_item(db_path, item, &SETTINGS_DEFAULT).unwrap().id
+    }
+
+    // 与 copy_entry 相同：每次粘贴各开一个连接再计数，同时有读者在查询
+    #[test]
+    fn concurrent_bump_usage_has_no_lock_errors() {
+        const THREADS: i64 = 8;
+        const BUMPS: i64 = 50;
+        let db = TempDb::new("bump");
+        let id = insert_text(&db.path, "hello");
+        let mut handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let path = db.path.clone();
+                thread::spawn(move || {
+                    for _ in 0..BUMPS {
+                        let mut conn = open_db(&path).unwrap();
+                        bump_usage(&mut conn, id).unwrap();
+                    }
+                })
+            })
+            .collect();
+        let path = db.path.clone();
+        handles.push(thread::spawn(move || {
+            for _ in 0..BUMPS {
+                let conn = open_db(&path).unwrap();
+                fetch_item(&conn, id).unwrap();
+            }
+        }));
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let conn = open_db(&db.path).unwrap();
+        assert_eq!(fetch_item(&conn, id).unwrap().usage_count, THREADS * BUMPS);
+    }
+
+    #[test]
+    fn checked_image_dims_rejects_byte_count_overflow() {
+        let side = u32::MAX as usize;
+        assert_eq!(
+            checked_image_dims(side, side, 0, u64::MAX),
+            Err("字节数溢出".to_string())
+        );
+    }
+
+    #[test]
+    fn checked_image_dims_rejects_mismatched_length() {
+        assert_eq!(checked_image_dims(2, 2, 16, 100), Ok((2, 2)));
+        assert!(checked_image_dims(2, 2, 15, 100).is_err());
+        assert!(checked_image_dims(2, 2, 17, 100).is_err());
+    }
+
+    #[test]
+    fn checked_image_dims_rejects_too_many_pixels() {
+        assert_eq!(checked_image_dims(10, 10, 400, 100), Ok((10, 10)));
+        assert!(checked_image_dims(10, 11, 440, 100).is_err());
+        assert!(checked_image_dims(0, 10, 0, 100).is_err());
+    }
+
+    #[test]
+    fn normalize_whitespace_collapses_tabs_and_nbsp() {
+        assert_eq!(normalize_whitespace("a\t\tb"), "a b");
+        assert_eq!(normalize_whitespace("a\u{a0}\u{a0}b\u{3000}c"), "a b c");
+        assert_eq!(normalize_whitespace("a \t\u{202f}b  \n"), "a b\n");
+    }
+
+    #[test]
+    fn normalize_whitespace_keeps_single_blank_line() {
+        assert_eq!(normalize_whitespace("a\n\n\n\nb"), "a\n\nb");
+        assert_eq!(normalize_whitespace("a\r\n\r\n\r\nb\r\n"), "a\r\n\r\nb\r\n");
+        assert_eq!(normalize_whitespace("a\n \t\n\nb"), "a\n\nb");
+    }
+
+    /// 加入迁移之前的表结构（还没有 source_path/source_icon），应能一路升到最新
+    const V0_SCHEMA: &str = "
+        CREATE TABLE clipboard_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            content_type TEXT NOT NULL,
+            text_content TEXT,
+            image_data BLOB,
+            source_app TEXT,
+            created_at INTEGER NOT NULL,
+            is_pinned INTEGER DEFAULT 0,
+            usage_count INTEGER DEFAULT 0
+        );
+        CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+    ";
+
+    fn item_columns(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn.prepare("PRAGMA table_info(clipboard_items)").unwrap();
+        let columns = stmt
+            .query_map([], |row| row.get::<_, String>(1))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap();
+        columns
+    }
+
+    #[test]
+    fn schema_updates_from_v0_are_idempotent() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(V0_SCHEMA).unwrap();
+        ensure_schema_updates(&conn).unwrap();
+        let first = item_columns(&conn);
+        ensure_schema_updates(&conn).unwrap();
+        assert_eq!(item_columns(&conn), first);
+
+        for column in [
+            "source_path",
+            "source_icon",
+            "deleted_at",
+            "image_path",
+            "checksum",
+            "image_size",
+        ] {
+            assert!(first.iter().any(|c| c == column), "缺少列 {column}");
+        }
+        let version: String = conn
+            .query_row(
+                "SELECT value FROM settings WHERE key = 'schema_version'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len().to_string());
+        // 迁移后的表能被正常读写
+        conn.execute(
+            "INSERT INTO clipboard_items(content_type, text_content, created_at) VALUES('text', 'x', 0)",
+            [],
+        )
+        .unwrap();
+        fetch_item(&conn, conn.last_insert_rowid()).unwrap();
+    }
+
+    #[test]
+    fn enforce_limit_skips_delete_under_cap() {
+        let db = TempDb::new("limit");
+        let settings = Settings {
+            max_history: 3,
+            ..SETTINGS_DEFAULT.clone()
+        };
+        let conn = open_db(&db.path).unwrap();
+        let now = chrono::Utc::now().timestamp_millis();
+        let insert = |i: i64| {
+            let item = imported_item("text", Some(format!("item {i}")), None, now + i);
+            insert_row(&conn, item, &settings).unwrap();
+            enforce_limit_if_needed(&conn, &db.path, &settings).unwrap()
+        };
+        for i in 0..3 {
+            assert!(!insert(i), "未超上限不应执行清理");
+        }
+        assert!(insert(3));
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM clipboard_items", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn detect_entity_recognizes_each_type() {
+        assert_eq!(detect_entity(" alice@example.com "), Some("email"));
+        assert_eq!(detect_entity("GB82 WEST 1234 5698 7654 32"), Some("iban"));
+        assert_eq!(detect_entity("de89370400440532013000"), Some("iban"));
+        assert_eq!(detect_entity("+86 138-0013-8000"), Some("phone"));
+        assert_eq!(detect_entity("(020) 7946 0958"), Some("phone"));
+        assert_eq!(detect_entity("2024-01-15"), None);
+        assert_eq!(detect_entity("12345"), None);
+    }
+
+    #[test]
+    fn detect_entity_ignores_entities_inside_prose() {
+        assert_eq!(detect_entity("请发邮件到 alice@example.com 联系我"), None);
+        assert_eq!(detect_entity("call me at +86 138-0013-8000"), None);
+        assert_eq!(detect_entity("IBAN: GB82WEST12345698765432"), None);
+        assert_eq!(detect_entity("alice@example.com\nbob@example.com"), None);
+    }
+
+    #[test]
+    fn iban_with_bad_checksum_is_rejected() {
+        assert!(iban_checksum_ok("GB82WEST12345698765432"));
+        assert!(!iban_checksum_ok("GB82WEST12345698765433"));
+        assert!(!iban_checksum_ok("GB28WEST12345698765432"));
+        assert_eq!(detect_entity("GB82 WEST 1234 5698 7654 33"), None);
+    }
+
+    #[test]
+    fn blank_image_detection() {
+        let white = ImageBuffer::from_pixel(64, 64, Rgba([255u8, 255, 255, 255]));
+        assert!(is_blank_image(&white, 0));
+
+        let gradient =
+            ImageBuffer::from_fn(64, 64, |x, y| Rgba([x as u8 * 4, y as u8 * 4, 0, 255]));
+        assert!(!is_blank_image(&gradient, 8));
+
+        // 完全透明时颜色通道是什么都不影响
+        let transparent = ImageBuffer::from_fn(64, 64, |x, y| Rgba([x as u8, y as u8, 7, 0]));
+        assert!(is_blank_image(&transparent, 0));
+    }
+
+    #[test]
+    fn blank_image_tolerance_is_inclusive() {
+        let mut img = ImageBuffer::from_pixel(16, 16, Rgba([200u8, 200, 200, 255]));
+        img.put_pixel(15, 15, Rgba([210, 200, 200, 255]));
+        assert!(is_blank_image(&img, 10));
+        assert!(!is_blank_image(&img, 9));
+    }
+
+    #[test]
+    fn same_text_ignores_normalization_form() {
+        // 阿拉伯文 alef + hamza above：组合序列与预组合字符
+        assert!(same_text(
+            "\u{0627}\u{0654}\u{0644}\u{0641}",
+            "\u{0623}\u{0644}\u{0641}"
+        ));
+        // 希伯来文 shin + shin dot + qamats：两种附加符号顺序规范化后相同
+        assert!(same_text(
+            "\u{05E9}\u{05C1}\u{05B8}\u{05DC}\u{05D5}\u{05DD}",
+            "\u{05E9}\u{05B8}\u{05C1}\u{05DC}\u{05D5}\u{05DD}"
+        ));
+        // NFD 的 é、ố 与 NFC 的预组合字符
+        assert!(same_text(
+            "Cafe\u{0301} pho\u{0302}\u{0301}",
+            "Caf\u{00E9} ph\u{1ED1}"
+        ));
+        assert!(!same_text("Cafe", "Caf\u{00E9}"));
+        assert!(!same_text("\u{05E9}\u{05C1}", "\u{05E9}\u{05C2}"));
+    }
+
+    #[test]
+    fn char_count_counts_graphemes() {
+        assert_eq!(char_count("Cafe\u{0301}"), 4);
+        assert_eq!(char_count("Caf\u{00E9}"), 4);
+        assert_eq!(char_count("\u{05E9}\u{05C1}\u{05B8}\u{05DC}"), 2);
+        assert_eq!(char_count("\u{1F469}\u{200D}\u{1F4BB}"), 1);
+        assert!(normalize_buffer_name(&"e\u{0301}".repeat(32)).is_ok());
+        assert!(normalize_buffer_name(&"e\u{0301}".repeat(33)).is_err());
+    }
+
+    fn wide_path(parts: &[&str], bad: u16) -> Vec<u16> {
+        let mut wide: Vec<u16> = parts[0].encode_utf16().collect();
+        for part in &parts[1..] {
+            wide.push(bad);
+            wide.extend(part.encode_utf16());
+        }
+        wide
+    }
+
+    #[test]
+    fn unpaired_surrogate_in_dir_falls_back_to_exe_stem() {
+        let names = std::collections::HashMap::from([("tool".to_string(), "My Tool".to_string())]);
+        let wide = wide_path(&[r"C:\Users\", r"\Edge\msedge.exe"], 0xD800);
+        assert!(String::from_utf16(&wide).is_err());
+        assert_eq!(exe_stem_from_wide(&wide).as_deref(), Some("msedge"));
+        assert_eq!(display_name_from_wide(&wide, &names), "Microsoft Edge");
+
+        let wide = wide_path(&[r"D:\", r"\Tool.exe"], 0xDC00);
+        assert_eq!(display_name_from_wide(&wide, &names), "My Tool");
+    }
+
+    #[test]
+    fn unpaired_surrogate_in_file_name_shows_placeholder() {
+        let wide = wide_path(&[r"C:\apps\ab", ".exe"], 0xD800);
+        assert_eq!(exe_stem_from_wide(&wide), None);
+        assert_eq!(
+            display_name_from_wide(&wide, &std::collections::HashMap::new()),
+            "?"
+        );
+    }
+
+    #[test]
+    fn capture_choice_matrix() {
+        use CaptureKind::{Image, Text};
+        for preference in ["text", "image", "both"] {
+            assert_eq!(capture_choice(false, false, preference), None);
+            assert_eq!(capture_choice(true, false, preference), Some(Text));
+            assert_eq!(capture_choice(false, true, preference), Some(Image));
+        }
+        assert_eq!(capture_choice(true, true, "text"), Some(Text));
+        assert_eq!(capture_choice(true, true, "image"), Some(Image));
+        // both 的图片由 read_clipboard 另存，这里返回的文本是最新一条
+        assert_eq!(capture_choice(true, true, "both"), Some(Text));
+    }
+
+    fn dto_at(id: i64, created_at: i64) -> ClipboardDto {
+        let mut item = imported_item("text", Some(format!("item {id}")), None, created_at);
+        item.id = id;
+        to_dto(item)
+    }
+
+    fn cached_ids(recent: &std::collections::VecDeque<ClipboardDto>) -> Vec<i64> {
+        recent.iter().map(|dto| dto.id).collect()
+    }
+
+    #[test]
+    fn place_recent_keeps_newest_prefix() {
+        let mut recent = std::collections::VecDeque::new();
+        place_recent(&mut recent, dto_at(1, 10), false);
+        assert!(recent.is_empty(), "不确定是否最新时空缓存不放");
+        place_recent(&mut recent, dto_at(1, 10), true);
+        place_recent(&mut recent, dto_at(3, 30), false);
+        place_recent(&mut recent, dto_at(2, 20), false);
+        assert_eq!(cached_ids(&recent), [3, 2, 1]);
+
+        // 比最旧的还旧：放进去会在中间留下缺口
+        place_recent(&mut recent, dto_at(4, 5), false);
+        assert_eq!(cached_ids(&recent), [3, 2, 1]);
+        // 时间改早的旧副本一起移出，改晚的移到前面
+        place_recent(&mut recent, dto_at(2, 1), false);
+        place_recent(&mut recent, dto_at(1, 40), false);
+        assert_eq!(cached_ids(&recent), [1, 3]);
+    }
+
+    #[test]
+    fn place_recent_is_bounded() {
+        let mut recent = std::collections::VecDeque::new();
+        for id in 0..RECENT_CACHE_CAPACITY as i64 + 5 {
+            place_recent(&mut recent, dto_at(id, id * 10), true);
+        }
+        assert_eq!(recent.len(), RECENT_CACHE_CAPACITY);
+        assert_eq!(
+            recent.front().map(|dto| dto.id),
+            Some(RECENT_CACHE_CAPACITY as i64 + 4)
+        );
+        assert_eq!(recent.back().map(|dto| dto.id), Some(5));
+    }
+
+    fn exported(content_type: &str, text: Option<&str>, image: Option<String>) -> ExportedItem {
+        ExportedItem {
+            content_type: content_type.into(),
+            text_content: text.map(Into::into),
+            image_data: image,
+            source_app: None,
+            source_url: None,
+            created_at: chrono::Utc::now().timestamp_millis(),
+            created_at_iso: None,
+            is_pinned: false,
+            usage_count: 0,
+        }
+    }
+
+    fn live_rows(db_path: &Path) -> Vec<(String, Option<String>, bool)> {
+        let conn = open_db(db_path).unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT content_type, text_content, is_pinned FROM clipboard_items ORDER BY id",
+            )
+            .unwrap();
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap();
+        rows.collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn replace_history_rejects_malformed_item_without_changes() {
+        let db = TempDb::new("replace-invalid");
+        insert_text(&db.path, "keep me");
+        let before = live_rows(&db.path);
+        let items = vec![
+            exported("text", Some("new"), None),
+            exported("image", None, Some(BASE64.encode(b"not an image"))),
+        ];
+        let err = replace_history_at(&db.path, &SETTINGS_DEFAULT, items).unwrap_err();
+        assert!(err.contains("第 2 条"), "{err}");
+        assert_eq!(live_rows(&db.path), before);
+    }
+
+    #[test]
+    fn replace_history_keeps_pin_for_same_content() {
+        let db = TempDb::new("replace-pin");
+        let settings = Settings {
+            store_images_on_disk: true,
+            ..SETTINGS_DEFAULT.clone()
+        };
+        let img =
+            ImageBuffer::from_fn(8, 8, |x, y| Rgba([(x * 30) as u8, (y * 30) as u8, 90, 255]));
+        let mut png = Cursor::new(Vec::new());
+        img.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let png = png.into_inner();
+
+        let text_id = insert_text(&db.path, "pinned note");
+        let image = imported_item("image", None, Some(png.clone()), 1);
+        let image_id = insert_item(&db.path, image, &settings).unwrap().id;
+        let conn = open_db(&db.path).unwrap();
+        conn.execute(
+            "UPDATE clipboard_items SET is_pinned = 1 WHERE id IN (?1, ?2)",
+            params![text_id, image_id],
+        )
+        .unwrap();
+        let old_path: Option<String> = conn
+            .query_row(
+                "SELECT image_path FROM clipboard_items WHERE id = ?1",
+                params![image_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(old_path.is_some(), "原图应已迁出到文件");
+
+        let items = vec![
+            exported("text", Some("pinned note"), None),
+            exported("image", None, Some(BASE64.encode(&png))),
+            exported("text", Some("other"), None),
+        ];
+        assert_eq!(replace_history_at(&db.path, &settings, items).unwrap(), 3);
+
+        let mut stmt = conn
+            .prepare("SELECT text_content, is_pinned, image_path FROM clipboard_items ORDER BY id")
+            .unwrap();
+        let rows: Vec<(Option<String>, bool, Option<String>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            (rows[0].0.as_deref(), rows[0].1),
+            (Some("pinned note"), true)
+        );
+        assert!(rows[1].1, "内容相同的外存图片保留置顶");
+        assert_eq!((rows[2].0.as_deref(), rows[2].1), (Some("other"), false));
+
+        let new_path = rows[1].2.clone().expect("提交后新图片迁出到文件");
+        assert_eq!(read_image_file(&new_path), Some(png));
+        remove_image_file(&new_path, false);
+    }
+
+    /// 按 BITMAPINFOHEADER 解出 32 位 BI_RGB 的 DIB，高度为正时行序自下而上
+    fn decode_dib(dib: &[u8]) -> Option<image::RgbaImage> {
+        let u32_at = |at: usize| Some(u32::from_le_bytes(dib.get(at..at + 4)?.try_into().ok()?));
+        let u16_at = |at: usize| Some(u16::from_le_bytes(dib.get(at..at + 2)?.try_into().ok()?));
+        let header = u32_at(0)? as usize;
+        let width = u32_at(4)? as i32;
+        let height = u32_at(8)? as i32;
+        if u16_at(14)? != 32 || u32_at(16)? != 0 || width <= 0 || height == 0 {
+            return None;
+        }
+        let (w, h) = (width as u32, height.unsigned_abs());
+        let pixels = dib.get(header..header + (w * h * 4) as usize)?;
+        Some(ImageBuffer::from_fn(w, h, |x, y| {
+            let row = if height > 0 { h - 1 - y } else { y };
+            let at = ((row * w + x) * 4) as usize;
+            let [b, g, r, a] = pixels[at..at + 4].try_into().unwrap();
+            Rgba([r, g, b, a])
+        }))
+    }
+
+    #[test]
+    fn encode_dib_round_trips_partial_alpha() {
+        // 宽高不同、各行内容不同，行序或通道顺序弄反都会不相等
+        let img = ImageBuffer::from_fn(3, 2, |x, y| {
+            Rgba([
+                (x * 80) as u8,
+                (y * 120 + 10) as u8,
+                200,
+                [0, 64, 128, 255][(x + y) as usize],
+            ])
+        });
+        let dib = encode_dib(&img);
+        assert_eq!(dib.len(), 40 + 3 * 2 * 4);
+        let decoded = decode_dib(&dib).expect("DIB 头部不合法");
+        assert_eq!(decoded.dimensions(), img.dimensions());
+        assert_eq!(decoded.as_raw(), img.as_raw());
+    }
+
+    #[test]
+    fn bgra_to_rgba_treats_non_32_bit_as_opaque() {
+        // 24 位原图经 GetDIBits 扩成 32 位，第四个字节可能是任意值
+        let mut pixels = vec![1, 2, 3, 0, 4, 5, 6, 77];
+        assert!(!bgra_to_rgba(&mut pixels, 24));
+        assert_eq!(pixels, [3, 2, 1, 255, 6, 5, 4, 255]);
+
+        let mut pixels = vec![1, 2, 3, 0, 4, 5, 6, 0];
+        assert!(
+            !bgra_to_rgba(&mut pixels, 32),
+            "alpha 全为 0 的 32 位位图按不透明处理"
+        );
+        assert_eq!(pixels, [3, 2, 1, 255, 6, 5, 4, 255]);
+
+        let mut pixels = vec![1, 2, 3, 0, 4, 5, 6, 128];
+        assert!(bgra_to_rgba(&mut pixels, 32));
+        assert_eq!(pixels, [3, 2, 1, 0, 6, 5, 4, 128]);
+    }
+
+    #[test]
+    fn mask_bits_make_set_pixels_transparent() {
+        // 宽 9：每行 2 字节有效位，补齐到 4 字节，填充字节置满以确认不会被读成下一行
+        let width = 9;
+        assert_eq!(mono_stride(width), 4);
+        // 第 0 行 x=0、x=8 透明，第 1 行 x=1、x=7 透明
+        let mask = [
+            [0b1000_0000, 0b1000_0000, 0xff, 0xff],
+            [0b0100_0001, 0, 0xff, 0xff],
+        ]
+        .concat();
+        let mut pixels = vec![9u8; 9 * 2 * 4];
+        apply_mask_bits(&mask, width, &mut pixels);
+        let transparent: Vec<(usize, usize)> = pixels
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|(_, px)| px[3] == 0)
+            .map(|(i, _)| (i % 9, i / 9))
+            .collect();
+        assert_eq!(transparent, [(0, 0), (8, 0), (1, 1), (7, 1)]);
+        assert!(pixels.chunks_exact(4).all(|px| px[..3] == [9, 9, 9]));
+    }
+
+    #[test]
+    fn monochrome_mask_splits_and_and_xor_halves() {
+        // 宽 3、高 1：上半 AND 掩码，下半 XOR 位图，各占一行 4 字节
+        let raw = [0b0100_0000, 0xff, 0xff, 0xff, 0b1010_0000, 0, 0, 0];
+        let pixels = monochrome_mask_rgba(&raw, 3, 1).unwrap();
+        assert_eq!(pixels, [255, 255, 255, 255, 0, 0, 0, 0, 255, 255, 255, 255]);
+        assert!(
+            monochrome_mask_rgba(&raw[..6], 3, 1).is_none(),
+            "数据不足两半时失败"
+        );
+    }
 }
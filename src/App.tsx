@@ -5,6 +5,7 @@ import PreviewModal from './components/PreviewModal';
 import { useClipboardStore } from './store/clipboardStore';
 import { ClipboardEntry } from './types';
 import { appWindow } from '@tauri-apps/api/window';
+import { invoke } from '@tauri-apps/api/tauri';
 
 const App: React.FC = () => {
   const {
@@ -15,9 +16,18 @@ const App: React.FC = () => {
     togglePin,
     loadSettings,
     entries,
+    totalCount,
+    ioProgress,
+    watchError,
+    watcherPanic,
   } = useClipboardStore();
   const [previewEntry, setPreviewEntry] = React.useState<ClipboardEntry | null>(null);
   const [viewportHeight, setViewportHeight] = React.useState(() => window.innerHeight);
+  const [readOnly, setReadOnly] = React.useState(false);
+
+  React.useEffect(() => {
+    invoke<boolean>('is_read_only').then(setReadOnly).catch(() => {});
+  }, []);
 
   React.useEffect(() => {
     const onResize = () => setViewportHeight(window.innerHeight);
@@ -40,7 +50,8 @@ const App: React.FC = () => {
         moveSelection(-1);
       } else if (e.key === 'Enter' && !e.isComposing) {
         e.preventDefault();
-        const plain = e.ctrlKey || e.metaKey;
+        // Ctrl+Enter 使用与默认相反的粘贴方式
+        const plain = (e.ctrlKey || e.metaKey) !== Boolean(useClipboardStore.getState().settings?.default_plain_paste);
         appWindow.hide().then(() => {
           // give focus a beat to return to上一个应用，再执行模拟粘贴
           window.setTimeout(() => pasteSelected(plain), 80);
@@ -124,7 +135,32 @@ const App: React.FC = () => {
             borderTop: '1px solid var(--border)',
           }}
         >
-          <span>{`共 ${entries.length} 条记录 · 单击预览`}</span>
+          <span>{`共 ${totalCount ?? entries.length} 条记录 · 单击预览${readOnly ? ' · 只读模式' : ''}`}</span>
+          {watchError && (
+            <span title={watchError.message} style={{ color: '#dc2626' }}>
+              {watchError.kind === 'clipboard' ? '无法读取剪贴板（可能被其他程序占用）' : '保存剪贴板记录失败'}
+            </span>
+          )}
+          {watcherPanic && (
+            <span title={watcherPanic.message} style={{ color: '#b45309' }}>
+              {`剪贴板监听异常，已自动重启（${watcherPanic.restarts} 次）`}
+            </span>
+          )}
+          {ioProgress && (
+            <span style={{ display: 'flex', alignItems: 'center', gap: 8 }}>
+              {`${{ import: '导入', export: '导出', thumbnails: '重建缩略图' }[ioProgress.kind]} ${ioProgress.processed}/${ioProgress.total}`}
+              <span style={{ width: 120, height: 6, borderRadius: 999, background: 'var(--border)', overflow: 'hidden' }}>
+                <span
+                  style={{
+                    display: 'block',
+                    height: '100%',
+                    width: `${ioProgress.total ? (ioProgress.processed / ioProgress.total) * 100 : 0}%`,
+                    background: 'var(--accent)',
+                  }}
+                />
+              </span>
+            </span>
+          )}
           <span>Enter 粘贴</span>
         </div>
       </div>
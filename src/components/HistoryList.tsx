@@ -1,6 +1,8 @@
 import React, { useMemo, useRef } from 'react';
 import { useVirtualizer } from '@tanstack/react-virtual';
 import { format, isToday, isYesterday } from 'date-fns';
+import { invoke } from '@tauri-apps/api/tauri';
+import { save } from '@tauri-apps/api/dialog';
 import { ClipboardEntry } from '../types';
 import { useClipboardStore } from '../store/clipboardStore';
 
@@ -18,7 +20,9 @@ interface RowItem {
 type Row = RowGroup | RowItem;
 
 const summarize = (entry: ClipboardEntry) => {
+  if (entry.label) return entry.label;
   if (entry.content_type === 'image') return '[图片]';
+  if (entry.content_type === 'unsupported') return `[无法识别的内容：${entry.text_content ?? ''}]`;
   return (entry.text_content ?? '').replace(/\s+/g, ' ').slice(0, 120) || '[空文本]';
 };
 
@@ -55,7 +59,7 @@ interface Props {
 }
 
 const HistoryList: React.FC<Props> = ({ height, onEntryClick }) => {
-  const { entries, selectedIndex, hoveredIndex, query, moveSelection, setHovered, copyEntry, deleteEntry } = useClipboardStore();
+  const { entries, pinnedCount, selectedIndex, hoveredIndex, query, moveSelection, setHovered, copyEntry, deleteEntry, splitEntry, collapseConsecutive, setLabel, setAutodelete, setExpiry, secureDeleteEntry } = useClipboardStore();
   const parentRef = useRef<HTMLDivElement>(null);
   const [openActionId, setOpenActionId] = React.useState<number | null>(null);
 
@@ -77,7 +81,8 @@ const HistoryList: React.FC<Props> = ({ height, onEntryClick }) => {
     const result: Row[] = [];
     let currentGroup = '';
     entries.forEach((entry, idx) => {
-      const g = groupTitle(entry.created_at);
+      // 分组显示时置顶条目单独归到“置顶”下
+      const g = pinnedCount && idx < pinnedCount ? '置顶' : groupTitle(entry.created_at);
       if (g !== currentGroup) {
         result.push({ type: 'group', label: g });
         currentGroup = g;
@@ -85,7 +90,7 @@ const HistoryList: React.FC<Props> = ({ height, onEntryClick }) => {
       result.push({ type: 'item', entry, entryIndex: idx });
     });
     return result;
-  }, [entries]);
+  }, [entries, pinnedCount]);
 
   const rowVirtualizer = useVirtualizer({
     count: rows.length,
@@ -167,6 +172,60 @@ const HistoryList: React.FC<Props> = ({ height, onEntryClick }) => {
             await copyEntry(entry.id);
             setOpenActionId(null);
           };
+          const handleLabel = async (e: React.MouseEvent) => {
+            e.stopPropagation();
+            setOpenActionId(null);
+            const label = window.prompt('列表中显示的标题（留空恢复显示内容）', entry.label ?? '');
+            if (label !== null) await setLabel(entry.id, label);
+          };
+          const handleAutodelete = async (e: React.MouseEvent) => {
+            e.stopPropagation();
+            setOpenActionId(null);
+            const input = window.prompt('再使用几次后自动删除（留空取消）', '1');
+            if (input === null) return;
+            const uses = input.trim() ? Number(input) : null;
+            await setAutodelete(entry.id, uses).catch((err) => window.alert(String(err)));
+          };
+          const handleExpiry = async (e: React.MouseEvent) => {
+            e.stopPropagation();
+            setOpenActionId(null);
+            const input = window.prompt('多少分钟后删除（留空取消）', '5');
+            if (input === null) return;
+            const expiresAt = input.trim() ? Date.now() + Number(input) * 60_000 : null;
+            await setExpiry(entry.id, expiresAt).catch((err) => window.alert(String(err)));
+          };
+          const handleExport = async (e: React.MouseEvent) => {
+            e.stopPropagation();
+            setOpenActionId(null);
+            const path = await save({ defaultPath: entry.content_type === 'image' ? 'clipboard.png' : 'clipboard.txt' });
+            if (path) await invoke('export_entry', { id: entry.id, path }).catch((err) => window.alert(String(err)));
+          };
+          const handleSplit = async (e: React.MouseEvent) => {
+            e.stopPropagation();
+            await splitEntry(entry.id, false);
+            setOpenActionId(null);
+          };
+          const canSplit = entry.content_type !== 'image' && /\r?\n/.test((entry.text_content ?? '').trim());
+          const handleCollapse = async (e: React.MouseEvent) => {
+            e.stopPropagation();
+            setOpenActionId(null);
+            if (!entry.source_app) return;
+            const merged = await collapseConsecutive(entry.source_app).catch((err) => {
+              window.alert(String(err));
+              return null;
+            });
+            if (merged === 0) window.alert('没有可合并的连续条目');
+          };
+          const handleSecureDelete = async (e: React.MouseEvent) => {
+            e.stopPropagation();
+            setOpenActionId(null);
+            if (!window.confirm('彻底粉碎后无法从回收站恢复，确定继续？')) return;
+            const clean = await secureDeleteEntry(entry.id).catch((err) => {
+              window.alert(String(err));
+              return true;
+            });
+            if (!clean) window.alert('条目已删除，但数据库日志正被占用，稍后才会清除');
+          };
           const handleDelete = async (e: React.MouseEvent) => {
             e.stopPropagation();
             await deleteEntry(entry.id);
@@ -188,6 +247,12 @@ const HistoryList: React.FC<Props> = ({ height, onEntryClick }) => {
             >
               <div
                 onClick={handleClick}
+                draggable
+                onDragStart={(e) => {
+                  // 交给系统 OLE 拖放，才能把内容放进其他程序
+                  e.preventDefault();
+                  invoke('begin_drag', { id: entry.id }).catch(() => {});
+                }}
                 onMouseEnter={() => setHovered(entryIndex)}
                 onMouseLeave={() => setHovered(undefined)}
                 role="button"
@@ -260,6 +325,9 @@ const HistoryList: React.FC<Props> = ({ height, onEntryClick }) => {
                     <span>{humanTime(entry.created_at)}</span>
                     <span>· {entry.source_app || '未知来源'}</span>
                     {entry.usage_count > 0 && <span>· 使用 {entry.usage_count} 次</span>}
+                    {entry.expires_at && (
+                      <span>· {Math.max(1, Math.ceil((entry.expires_at - Date.now()) / 60_000))} 分钟后删除</span>
+                    )}
                   </div>
                 </div>
                 <div style={{ display: 'flex', gap: 10, alignItems: 'center' }}>
@@ -272,7 +340,13 @@ const HistoryList: React.FC<Props> = ({ height, onEntryClick }) => {
                       fontSize: 12,
                     }}
                   >
-                    {entry.content_type === 'image' ? '图片' : '文本'}
+                    {entry.content_type === 'image'
+                      ? '图片'
+                      : entry.content_type === 'path'
+                        ? '路径'
+                        : entry.content_type === 'unsupported'
+                          ? '其他格式'
+                          : '文本'}
                   </span>
                   {entry.is_pinned && <span style={{ color: 'var(--accent)' }}>📌</span>}
                   <button
@@ -347,6 +421,94 @@ const HistoryList: React.FC<Props> = ({ height, onEntryClick }) => {
                     >
                       复制
                     </button>
+                    <button
+                      type="button"
+                      onClick={handleLabel}
+                      style={{
+                        textAlign: 'left',
+                        padding: '9px 10px',
+                        borderRadius: 10,
+                        border: '1px solid transparent',
+                        background: 'transparent',
+                        cursor: 'pointer',
+                      }}
+                    >
+                      设置标题
+                    </button>
+                    <button
+                      type="button"
+                      onClick={handleAutodelete}
+                      style={{
+                        textAlign: 'left',
+                        padding: '9px 10px',
+                        borderRadius: 10,
+                        border: '1px solid transparent',
+                        background: 'transparent',
+                        cursor: 'pointer',
+                      }}
+                    >
+                      {entry.auto_delete_after_uses ? `阅后即焚（剩 ${entry.auto_delete_after_uses - entry.usage_count} 次）` : '阅后即焚'}
+                    </button>
+                    <button
+                      type="button"
+                      onClick={handleExpiry}
+                      style={{
+                        textAlign: 'left',
+                        padding: '9px 10px',
+                        borderRadius: 10,
+                        border: '1px solid transparent',
+                        background: 'transparent',
+                        cursor: 'pointer',
+                      }}
+                    >
+                      {entry.expires_at ? '修改定时删除' : '定时删除'}
+                    </button>
+                    <button
+                      type="button"
+                      onClick={handleExport}
+                      style={{
+                        textAlign: 'left',
+                        padding: '9px 10px',
+                        borderRadius: 10,
+                        border: '1px solid transparent',
+                        background: 'transparent',
+                        cursor: 'pointer',
+                      }}
+                    >
+                      另存为文件
+                    </button>
+                    {canSplit && (
+                      <button
+                        type="button"
+                        onClick={handleSplit}
+                        style={{
+                          textAlign: 'left',
+                          padding: '9px 10px',
+                          borderRadius: 10,
+                          border: '1px solid transparent',
+                          background: 'transparent',
+                          cursor: 'pointer',
+                        }}
+                      >
+                        按行拆分
+                      </button>
+                    )}
+                    {entry.content_type === 'text' && entry.source_app && !entry.is_pinned && (
+                      <button
+                        type="button"
+                        onClick={handleCollapse}
+                        style={{
+                          textAlign: 'left',
+                          padding: '9px 10px',
+                          borderRadius: 10,
+                          border: '1px solid transparent',
+                          background: 'transparent',
+                          cursor: 'pointer',
+                        }}
+                      >
+                        合并同来源的连续条目
+                      </button>
+                    )}
                     <button
                       type="button"
                       onClick={handleDelete}
@@ -362,6 +524,21 @@ const HistoryList: React.FC<Props> = ({ height, onEntryClick }) => {
                     >
                       删除
                     </button>
+                    <button
+                      type="button"
+                      onClick={handleSecureDelete}
+                      style={{
+                        textAlign: 'left',
+                        padding: '9px 10px',
+                        borderRadius: 10,
+                        border: '1px solid transparent',
+                        background: 'transparent',
+                        color: '#dc2626',
+                        cursor: 'pointer',
+                      }}
+                    >
+                      彻底粉碎
+                    </button>
                   </div>
                 )}
               </div>
@@ -1,16 +1,60 @@
 import React from 'react';
 import { format } from 'date-fns';
-import { ClipboardEntry } from '../types';
+import { invoke } from '@tauri-apps/api/tauri';
+import { open } from '@tauri-apps/api/shell';
+import { ClipboardEntry, SimilarEntry } from '../types';
 
 interface Props {
   entry: ClipboardEntry;
   onClose: () => void;
 }
 
+const formatSize = (bytes: number) => {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+  return `${(bytes / 1024 / 1024).toFixed(1)} MB`;
+};
+
 const PreviewModal: React.FC<Props> = ({ entry, onClose }) => {
   const timestamp = format(new Date(entry.created_at), 'yyyy/MM/dd HH:mm:ss');
   const hasText = Boolean(entry.text_content);
   const hasImage = Boolean(entry.image_thumb);
+  const [fullImage, setFullImage] = React.useState<string | null>(null);
+  const [extracting, setExtracting] = React.useState(false);
+  const [similar, setSimilar] = React.useState<SimilarEntry[] | null>(null);
+  const findSimilar = () => {
+    invoke<SimilarEntry[]>('find_similar', { id: entry.id, limit: 10 })
+      .then(setSimilar)
+      .catch((err) => window.alert(String(err)));
+  };
+  const extractText = () => {
+    setExtracting(true);
+    invoke('extract_text_from_image', { id: entry.id })
+      .catch((err) => window.alert(String(err)))
+      .finally(() => setExtracting(false));
+  };
+  const entityText = entry.text_content?.trim() ?? '';
+  const entityAction =
+    entry.detected_entity === 'email'
+      ? { label: '发送邮件', url: `mailto:${entityText}` }
+      : entry.detected_entity === 'phone'
+        ? { label: '拨打电话', url: `tel:${entityText.replace(/[^\d+]/g, '')}` }
+        : null;
+
+  React.useEffect(() => {
+    setFullImage(null);
+    setSimilar(null);
+    if (entry.content_type !== 'image') return;
+    let cancelled = false;
+    invoke<string | null>('get_image_data', { id: entry.id })
+      .then((data) => {
+        if (!cancelled) setFullImage(data);
+      })
+      .catch(() => {});
+    return () => {
+      cancelled = true;
+    };
+  }, [entry.id, entry.content_type]);
 
   return (
     <div
@@ -84,7 +128,15 @@ const PreviewModal: React.FC<Props> = ({ entry, onClose }) => {
               <div style={{ fontSize: 15, whiteSpace: 'nowrap', overflow: 'hidden', textOverflow: 'ellipsis' }}>
                 {entry.source_app || '未知来源'}
               </div>
-              <div style={{ color: '#6b7280', fontSize: 12 }}>{timestamp}</div>
+              <div style={{ color: '#6b7280', fontSize: 12 }}>{`${timestamp} · ${formatSize(entry.size_bytes)}`}</div>
+              {entry.source_url && (
+                <div
+                  title={entry.source_url}
+                  style={{ color: '#6b7280', fontSize: 12, whiteSpace: 'nowrap', overflow: 'hidden', textOverflow: 'ellipsis' }}
+                >
+                  {entry.source_url}
+                </div>
+              )}
             </div>
             <span
               style={{
@@ -98,7 +150,13 @@ const PreviewModal: React.FC<Props> = ({ entry, onClose }) => {
                 textTransform: 'uppercase',
               }}
             >
-              {entry.content_type === 'image' ? '图片' : '文本'}
+              {entry.content_type === 'image'
+                ? '图片'
+                : entry.content_type === 'path'
+                  ? '路径'
+                  : entry.content_type === 'unsupported'
+                    ? '其他格式'
+                    : '文本'}
             </span>
           </div>
           <button
@@ -132,7 +190,25 @@ const PreviewModal: React.FC<Props> = ({ entry, onClose }) => {
         >
           {hasText && (
             <div style={{ background: '#fff', borderRadius: 16, border: '1px solid #e5e7eb', padding: 16, boxShadow: 'var(--shadow-card)' }}>
-              <div style={{ fontSize: 13, color: '#374151', marginBottom: 10, fontWeight: 600 }}>文本内容</div>
+              <div style={{ display: 'flex', alignItems: 'center', justifyContent: 'space-between', marginBottom: 10 }}>
+                <div style={{ fontSize: 13, color: '#374151', fontWeight: 600 }}>文本内容</div>
+                {entityAction && (
+                  <button
+                    type="button"
+                    onClick={() => open(entityAction.url).catch(() => {})}
+                    style={{
+                      padding: '4px 10px',
+                      borderRadius: 8,
+                      border: '1px solid var(--border)',
+                      background: '#fff',
+                      fontSize: 12,
+                      cursor: 'pointer',
+                    }}
+                  >
+                    {entityAction.label}
+                  </button>
+                )}
+              </div>
               <div
                 style={{
                   whiteSpace: 'pre-wrap',
@@ -154,7 +230,44 @@ const PreviewModal: React.FC<Props> = ({ entry, onClose }) => {
           )}
           {hasImage && (
             <div style={{ background: '#fff', borderRadius: 16, border: '1px solid #e5e7eb', padding: 16, boxShadow: 'var(--shadow-card)' }}>
-              <div style={{ fontSize: 13, color: '#374151', marginBottom: 10, fontWeight: 600 }}>图片预览</div>
+              <div style={{ display: 'flex', alignItems: 'baseline', gap: 8, marginBottom: 10 }}>
+                <div style={{ fontSize: 13, color: '#374151', fontWeight: 600 }}>图片预览</div>
+                {entry.source_dpi && (
+                  <div style={{ fontSize: 12, color: '#6b7280' }} title={entry.source_monitor ?? undefined}>
+                    {`截图时缩放 ${Math.round((entry.source_dpi / 96) * 100)}%`}
+                  </div>
+                )}
+                <button
+                  type="button"
+                  onClick={() => invoke('copy_as_data_uri', { id: entry.id }).catch((err) => window.alert(String(err)))}
+                  style={{
+                    marginLeft: 'auto',
+                    padding: '4px 10px',
+                    borderRadius: 8,
+                    border: '1px solid var(--border)',
+                    background: '#fff',
+                    fontSize: 12,
+                    cursor: 'pointer',
+                  }}
+                >
+                  复制为 Data URI
+                </button>
+                <button
+                  type="button"
+                  disabled={extracting}
+                  onClick={extractText}
+                  style={{
+                    padding: '4px 10px',
+                    borderRadius: 8,
+                    border: '1px solid var(--border)',
+                    background: '#fff',
+                    fontSize: 12,
+                    cursor: extracting ? 'default' : 'pointer',
+                  }}
+                >
+                  {extracting ? '识别中…' : '提取文字'}
+                </button>
+              </div>
               <div
                 style={{
                   border: '1px solid #e5e7eb',
@@ -168,7 +281,7 @@ const PreviewModal: React.FC<Props> = ({ entry, onClose }) => {
                 }}
               >
                 <img
-                  src={entry.image_thumb}
+                  src={fullImage ?? entry.image_thumb}
                   alt="剪贴板图片预览"
                   style={{
                     width: '100%',
@@ -181,6 +294,40 @@ const PreviewModal: React.FC<Props> = ({ entry, onClose }) => {
               </div>
             </div>
           )}
+          {(hasText || hasImage) && (
+            <div style={{ background: '#fff', borderRadius: 16, border: '1px solid #e5e7eb', padding: 16, boxShadow: 'var(--shadow-card)' }}>
+              <div style={{ display: 'flex', alignItems: 'center', justifyContent: 'space-between' }}>
+                <div style={{ fontSize: 13, color: '#374151', fontWeight: 600 }}>相似条目</div>
+                <button
+                  type="button"
+                  onClick={findSimilar}
+                  style={{
+                    padding: '4px 10px',
+                    borderRadius: 8,
+                    border: '1px solid var(--border)',
+                    background: '#fff',
+                    fontSize: 12,
+                    cursor: 'pointer',
+                  }}
+                >
+                  查找
+                </button>
+              </div>
+              {similar && (
+                <div style={{ marginTop: 10, display: 'grid', gap: 6, fontSize: 12.5, color: '#374151' }}>
+                  {similar.length === 0 && <div style={{ color: '#6b7280' }}>没有找到相似条目</div>}
+                  {similar.map(({ entry: other, score }) => (
+                    <div key={other.id} style={{ display: 'flex', gap: 8 }}>
+                      <span style={{ color: '#6b7280', flexShrink: 0 }}>{`${Math.round(score * 100)}%`}</span>
+                      <span style={{ whiteSpace: 'nowrap', overflow: 'hidden', textOverflow: 'ellipsis' }}>
+                        {other.label || (other.content_type === 'image' ? `[图片] #${other.id}` : other.text_content)}
+                      </span>
+                    </div>
+                  ))}
+                </div>
+              )}
+            </div>
+          )}
           {!hasText && !hasImage && (
             <div
               style={{
@@ -1,8 +1,9 @@
 import React from 'react';
 import { useClipboardStore } from '../store/clipboardStore';
-import { TypeFilter, TimeFilter } from '../types';
+import { SourceApp, TypeFilter, TimeFilter } from '../types';
 import SettingsPanel from './SettingsPanel';
 import { listen } from '@tauri-apps/api/event';
+import { invoke } from '@tauri-apps/api/tauri';
 
 const segmentedBtn = (active: boolean) => ({
   minWidth: 68,
@@ -17,7 +18,23 @@ const segmentedBtn = (active: boolean) => ({
 });
 
 const SearchBar: React.FC = () => {
-  const { query, setQuery, typeFilter, setTypeFilter, timeFilter, setTimeFilter, fetchHistory } = useClipboardStore();
+  const {
+    query,
+    setQuery,
+    typeFilter,
+    setTypeFilter,
+    timeFilter,
+    setTimeFilter,
+    currentAppOnly,
+    setCurrentAppOnly,
+    userCopiesOnly,
+    setUserCopiesOnly,
+    settings,
+    sourceFilter,
+    setSourceFilter,
+    copySearchResults,
+    fetchHistory,
+  } = useClipboardStore();
   const inputRef = React.useRef<HTMLInputElement>(null);
   const [settingsOpen, setSettingsOpen] = React.useState(false);
   const settingsBtnRef = React.useRef<HTMLButtonElement | null>(null);
@@ -45,6 +62,27 @@ const SearchBar: React.FC = () => {
     fetchHistory();
   };
 
+  const toggleCurrentApp = () => {
+    setCurrentAppOnly(!currentAppOnly);
+    fetchHistory();
+  };
+
+  const [sources, setSources] = React.useState<SourceApp[]>([]);
+  const loadSources = () => {
+    invoke<SourceApp[]>('list_sources').then(setSources).catch(() => {});
+  };
+  React.useEffect(loadSources, []);
+
+  const updateSource = (app: string) => {
+    setSourceFilter(app || undefined);
+    fetchHistory();
+  };
+
+  const toggleUserCopies = () => {
+    setUserCopiesOnly(!userCopiesOnly);
+    fetchHistory();
+  };
+
   const updateTime = (t: TimeFilter) => {
     setTimeFilter(t);
     fetchHistory();
@@ -163,7 +201,9 @@ const SearchBar: React.FC = () => {
         </div>
 
         <div style={{ display: 'flex', alignItems: 'center', gap: 12, justifyContent: 'flex-end', minWidth: 230 }}>
-          <div style={{ color: 'var(--text-sub)', fontSize: 12 }}>Enter 粘贴 · Ctrl+Enter 纯文本 · ESC 关闭</div>
+          <div style={{ color: 'var(--text-sub)', fontSize: 12 }}>
+            {settings?.default_plain_paste ? 'Enter 纯文本 · Ctrl+Enter 原格式' : 'Enter 粘贴 · Ctrl+Enter 纯文本'} · ESC 关闭
+          </div>
           <div style={{ position: 'relative' }}>
             <button
               ref={settingsBtnRef}
@@ -229,6 +269,19 @@ const SearchBar: React.FC = () => {
               {t === 'all' ? '全部' : t === 'text' ? '文本' : '图片'}
             </button>
           ))}
+          <button style={segmentedBtn(currentAppOnly)} onClick={toggleCurrentApp} title="只看从当前应用复制的内容">
+            当前应用
+          </button>
+          {query.trim() && (
+            <button style={segmentedBtn(false)} onClick={() => copySearchResults().catch(() => {})} title="把所有匹配的文本连接后复制到剪贴板">
+              复制全部结果
+            </button>
+          )}
+          {settings?.classify_capture_source && (
+            <button style={segmentedBtn(userCopiesOnly)} onClick={toggleUserCopies} title="隐藏后台程序自动改写剪贴板产生的记录">
+              手动复制
+            </button>
+          )}
         </div>
         <div style={{ display: 'flex', gap: 6, background: '#fff', borderRadius: '12px', padding: 4, border: '1px solid var(--border)' }}>
           {(['all', 'today', 'yesterday', 'earlier'] as TimeFilter[]).map((t) => (
@@ -237,6 +290,17 @@ const SearchBar: React.FC = () => {
             </button>
           ))}
         </div>
+        <select
+          value={sourceFilter ?? ''}
+          onFocus={loadSources}
+          onChange={(e) => updateSource(e.target.value)}
+          style={{ padding: '6px 8px', borderRadius: 12, border: '1px solid var(--border)', background: '#fff', fontSize: 12 }}
+        >
+          <option value="">全部来源</option>
+          {sources.map((s) => (
+            <option key={s.app} value={s.app}>{`${s.app} (${s.count})`}</option>
+          ))}
+        </select>
       </div>
     </div>
   );
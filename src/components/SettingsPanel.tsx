@@ -1,8 +1,29 @@
 import React from 'react';
+import { invoke } from '@tauri-apps/api/tauri';
 import { useClipboardStore } from '../store/clipboardStore';
 
 const SettingsPanel: React.FC = () => {
-  const { settings, updateSettings } = useClipboardStore();
+  const { settings, updateSettings, hotkeyError } = useClipboardStore();
+  const [maxDraft, setMaxDraft] = React.useState<number | null>(null);
+  const [pruneIds, setPruneIds] = React.useState<number[]>([]);
+
+  // 调整上限时先预演会被清理的条目，失焦后才真正保存
+  React.useEffect(() => {
+    if (maxDraft === null || maxDraft < 1) {
+      setPruneIds([]);
+      return;
+    }
+    let cancelled = false;
+    invoke<number[]>('preview_pruning', { max: maxDraft, maxAgeDays: null })
+      .then((ids) => {
+        if (!cancelled) setPruneIds(ids);
+      })
+      .catch(() => {});
+    return () => {
+      cancelled = true;
+    };
+  }, [maxDraft]);
+
   if (!settings) return null;
 
   return (
@@ -23,14 +44,129 @@ const SettingsPanel: React.FC = () => {
             }}
           />
         </div>
+        {hotkeyError && (
+          <div title={hotkeyError.message} style={{ color: '#dc2626', fontSize: 12 }}>
+            {hotkeyError.fallback
+              ? `快捷键注册失败，暂时改用 ${hotkeyError.fallback}，请换一个组合`
+              : '快捷键注册失败，请换一个组合'}
+          </div>
+        )}
+        <div style={{ display: 'flex', alignItems: 'center', justifyContent: 'space-between', gap: 12 }}>
+          <div style={{ color: 'var(--text-sub)', fontSize: 13 }}>纯文本粘贴快捷键</div>
+          <input
+            value={settings.plain_paste_hotkey}
+            placeholder="未设置"
+            onChange={(e) => updateSettings({ plain_paste_hotkey: e.target.value })}
+            style={{
+              padding: '8px 10px',
+              borderRadius: 10,
+              border: '1px solid var(--border)',
+              background: '#f9fafb',
+              minWidth: 140,
+            }}
+          />
+        </div>
+        <div style={{ display: 'flex', alignItems: 'center', justifyContent: 'space-between', gap: 12 }}>
+          <div style={{ color: 'var(--text-sub)', fontSize: 13 }} title="修饰键 + 1~9 粘贴第 N 个置顶条目">
+            置顶快捷键修饰键
+          </div>
+          <input
+            value={settings.pinned_hotkey_modifier}
+            placeholder="如 Ctrl+Alt"
+            onChange={(e) => updateSettings({ pinned_hotkey_modifier: e.target.value })}
+            style={{
+              padding: '8px 10px',
+              borderRadius: 10,
+              border: '1px solid var(--border)',
+              background: '#f9fafb',
+              minWidth: 140,
+            }}
+          />
+        </div>
+        <div style={{ display: 'flex', alignItems: 'center', justifyContent: 'space-between', gap: 12 }}>
+          <div style={{ color: 'var(--text-sub)', fontSize: 13 }}>置顶条目</div>
+          <select
+            value={settings.pinned_display}
+            onChange={(e) => updateSettings({ pinned_display: e.target.value as typeof settings.pinned_display })}
+            style={{
+              padding: '8px 10px',
+              borderRadius: 10,
+              border: '1px solid var(--border)',
+              background: '#f9fafb',
+              minWidth: 140,
+            }}
+          >
+            <option value="top">始终在最前</option>
+            <option value="separate">单独分组</option>
+            <option value="inline">按时间混排</option>
+          </select>
+        </div>
+        <div style={{ display: 'flex', alignItems: 'center', justifyContent: 'space-between', gap: 12 }}>
+          <div style={{ color: 'var(--text-sub)', fontSize: 13 }}>文本和图片同时存在时</div>
+          <select
+            value={settings.capture_preference}
+            onChange={(e) => updateSettings({ capture_preference: e.target.value as typeof settings.capture_preference })}
+            style={{
+              padding: '8px 10px',
+              borderRadius: 10,
+              border: '1px solid var(--border)',
+              background: '#f9fafb',
+              minWidth: 140,
+            }}
+          >
+            <option value="text">只记录文本</option>
+            <option value="image">只记录图片</option>
+            <option value="both">各记录一条</option>
+          </select>
+        </div>
+        <div style={{ display: 'flex', alignItems: 'center', justifyContent: 'space-between', gap: 12 }}>
+          <div style={{ color: 'var(--text-sub)', fontSize: 13 }}>粘贴回来源应用时</div>
+          <select
+            value={settings.same_app_paste}
+            onChange={(e) => updateSettings({ same_app_paste: e.target.value as typeof settings.same_app_paste })}
+            style={{
+              padding: '8px 10px',
+              borderRadius: 10,
+              border: '1px solid var(--border)',
+              background: '#f9fafb',
+              minWidth: 140,
+            }}
+          >
+            <option value="off">不处理</option>
+            <option value="plain">改为纯文本粘贴</option>
+            <option value="record">仅记录</option>
+          </select>
+        </div>
         <div style={{ display: 'flex', alignItems: 'center', justifyContent: 'space-between', gap: 12 }}>
           <div style={{ color: 'var(--text-sub)', fontSize: 13 }}>最大历史条数</div>
           <input
             type="number"
             min={100}
             max={5000}
-            value={settings.max_history}
-            onChange={(e) => updateSettings({ max_history: Number(e.target.value) })}
+            value={maxDraft ?? settings.max_history}
+            onChange={(e) => setMaxDraft(Number(e.target.value))}
+            onBlur={() => {
+              if (maxDraft !== null && maxDraft !== settings.max_history) updateSettings({ max_history: maxDraft });
+              setMaxDraft(null);
+            }}
+            style={{
+              padding: '8px 10px',
+              borderRadius: 10,
+              border: '1px solid var(--border)',
+              background: '#f9fafb',
+              width: 120,
+            }}
+          />
+        </div>
+        <div style={{ display: 'flex', alignItems: 'center', justifyContent: 'space-between', gap: 12 }}>
+          <div style={{ color: 'var(--text-sub)', fontSize: 13 }} title="0 表示不单独限制">
+            图片最多保留
+          </div>
+          <input
+            type="number"
+            min={0}
+            value={settings.max_image_items}
+            onChange={(e) => updateSettings({ max_image_items: Math.max(0, Number(e.target.value) || 0) })}
             style={{
               padding: '8px 10px',
               borderRadius: 10,
@@ -40,6 +176,11 @@ const SettingsPanel: React.FC = () => {
             }}
           />
         </div>
+        {pruneIds.length > 0 && (
+          <div style={{ color: '#b45309', fontSize: 12, lineHeight: 1.6 }}>
+            {`保存后将清理 ${pruneIds.length} 条记录（如 #${pruneIds.slice(0, 5).join('、#')}${pruneIds.length > 5 ? ' …' : ''}）`}
+          </div>
+        )}
       </div>
       <div style={{ marginTop: 12, color: 'var(--text-sub)', fontSize: 12, lineHeight: 1.6 }}>
         所有数据仅保存在本地，不会上传到服务器。
@@ -1,35 +1,56 @@
 import { create } from 'zustand';
 import { invoke } from '@tauri-apps/api/tauri';
 import { listen } from '@tauri-apps/api/event';
-import { ClipboardEntry, Settings, TimeFilter, TypeFilter } from '../types';
+import { ClipboardEntry, HistoryResponse, HotkeyError, IoDone, IoProgress, Settings, TimeFilter, TypeFilter, WatchError, WatcherPanic } from '../types';
 import { subDays, startOfDay } from 'date-fns';
 
 interface State {
   entries: ClipboardEntry[];
+  /** pinned_display 为 separate 时前多少条属于置顶分组 */
+  pinnedCount?: number;
   selectedIndex: number;
   hoveredIndex?: number;
   query: string;
   typeFilter: TypeFilter;
   timeFilter: TimeFilter;
   sourceFilter?: string;
+  currentAppOnly: boolean;
+  userCopiesOnly: boolean;
   settings?: Settings;
   loading: boolean;
   error?: string;
   ready: boolean;
+  ioProgress?: IoProgress;
+  watchError?: WatchError;
+  watcherPanic?: WatcherPanic;
+  hotkeyError?: HotkeyError;
+  totalCount?: number;
   fetchHistory: () => Promise<void>;
   setQuery: (q: string) => void;
   setTypeFilter: (t: TypeFilter) => void;
   setTimeFilter: (t: TimeFilter) => void;
   setSourceFilter: (s?: string) => void;
+  setCurrentAppOnly: (v: boolean) => void;
+  setUserCopiesOnly: (v: boolean) => void;
   moveSelection: (delta: number) => void;
   setHovered: (idx?: number) => void;
   copyEntry: (id: number) => Promise<void>;
   deleteEntry: (id: number) => Promise<void>;
+  splitEntry: (id: number, deleteSource: boolean) => Promise<void>;
+  collapseConsecutive: (sourceApp: string) => Promise<number>;
+  setLabel: (id: number, label: string) => Promise<void>;
+  setAutodelete: (id: number, uses: number | null) => Promise<void>;
+  setExpiry: (id: number, expiresAt: number | null) => Promise<void>;
+  secureDeleteEntry: (id: number) => Promise<boolean>;
+  copySearchResults: () => Promise<number>;
   deleteSelected: () => Promise<void>;
   pasteSelected: (plain: boolean) => Promise<void>;
   togglePin: () => Promise<void>;
+  promoteEntry: (id: number) => Promise<void>;
   loadSettings: () => Promise<void>;
   updateSettings: (settings: Partial<Settings>) => Promise<void>;
+  addBlacklistEntry: (name: string) => Promise<void>;
+  removeBlacklistEntry: (name: string) => Promise<void>;
 }
 
 const withinTime = (timestamp: number, filter: TimeFilter) => {
@@ -57,19 +78,29 @@ export const useClipboardStore = create<State>((set, get) => ({
   typeFilter: 'all',
   timeFilter: 'all',
   sourceFilter: undefined,
+  currentAppOnly: false,
+  userCopiesOnly: false,
   loading: false,
   ready: false,
   async fetchHistory() {
     set({ loading: true, error: undefined });
     try {
-      const { query, typeFilter, timeFilter, sourceFilter } = get();
-      const rows: ClipboardEntry[] = await invoke('get_history', {
+      const { query, typeFilter, timeFilter, sourceFilter, currentAppOnly, userCopiesOnly } = get();
+      const filters = {
         query,
         typeFilter,
         timeFilter,
         sourceFilter,
-      });
-      set({ entries: rows, loading: false, ready: true, selectedIndex: 0, hoveredIndex: undefined });
+        currentAppOnly,
+        captureSource: userCopiesOnly ? 'user' : null,
+      };
+      const [res, totalCount] = await Promise.all([
+        invoke<HistoryResponse>('get_history', filters),
+        invoke<number>('count_history', filters),
+      ]);
+      const rows = Array.isArray(res) ? res : [...res.pinned, ...res.recent];
+      const pinnedCount = Array.isArray(res) ? undefined : res.pinned.length;
+      set({ entries: rows, pinnedCount, totalCount, loading: false, ready: true, selectedIndex: 0, hoveredIndex: undefined });
     } catch (error: any) {
       set({ error: error?.message ?? '加载失败', loading: false, ready: true });
     }
@@ -87,6 +118,12 @@ export const useClipboardStore = create<State>((set, get) => ({
   setSourceFilter(s) {
     set({ sourceFilter: s, selectedIndex: 0 });
   },
+  setCurrentAppOnly(v) {
+    set({ currentAppOnly: v, selectedIndex: 0 });
+  },
+  setUserCopiesOnly(v) {
+    set({ userCopiesOnly: v, selectedIndex: 0 });
+  },
   setHovered(idx) {
     set({ hoveredIndex: idx });
   },
@@ -120,6 +157,45 @@ export const useClipboardStore = create<State>((set, get) => ({
     const nextIndex = Math.min(fresh.length - 1, Math.max(0, prevIndex));
     set({ selectedIndex: nextIndex, hoveredIndex: undefined });
   },
+  async splitEntry(id, deleteSource) {
+    await invoke('split_entry', { id, delimiter: null, deleteSource });
+    await get().fetchHistory();
+    set({ selectedIndex: 0, hoveredIndex: undefined });
+  },
+  async collapseConsecutive(sourceApp) {
+    const merged: ClipboardEntry[] = await invoke('collapse_consecutive', { sourceApp });
+    await get().fetchHistory();
+    set({ selectedIndex: 0, hoveredIndex: undefined });
+    return merged.length;
+  },
+  async setLabel(id, label) {
+    const updated: ClipboardEntry = await invoke('set_label', { id, label });
+    set({ entries: get().entries.map((e) => (e.id === id ? updated : e)) });
+  },
+  async setAutodelete(id, uses) {
+    const updated: ClipboardEntry = await invoke('set_autodelete', { id, uses });
+    set({ entries: get().entries.map((e) => (e.id === id ? updated : e)) });
+  },
+  async secureDeleteEntry(id) {
+    const clean: boolean = await invoke('secure_delete_entry', { id });
+    set({ entries: get().entries.filter((e) => e.id !== id) });
+    return clean;
+  },
+  async setExpiry(id, expiresAt) {
+    const updated: ClipboardEntry = await invoke('set_expiry', { id, expiresAt });
+    set({ entries: get().entries.map((e) => (e.id === id ? updated : e)) });
+  },
+  async copySearchResults() {
+    const { query, typeFilter, timeFilter, sourceFilter, currentAppOnly, userCopiesOnly } = get();
+    return invoke<number>('copy_search_results', {
+      query,
+      typeFilter,
+      timeFilter,
+      sourceFilter,
+      currentAppOnly,
+      captureSource: userCopiesOnly ? 'user' : null,
+    });
+  },
   async deleteSelected() {
     const { entries, selectedIndex, fetchHistory } = get();
     const entry = entries[selectedIndex];
@@ -140,9 +216,14 @@ export const useClipboardStore = create<State>((set, get) => ({
     await invoke('toggle_pin', { id: entry.id });
     await fetchHistory();
   },
+  async promoteEntry(id) {
+    await invoke('promote_entry', { id });
+    set({ selectedIndex: 0, hoveredIndex: undefined });
+  },
   async loadSettings() {
     const settings: Settings = await invoke('get_settings');
-    set({ settings: { ...settings, record_images: true } });
+    const hotkeyError: HotkeyError | null = await invoke('get_hotkey_error');
+    set({ settings: { ...settings, record_images: true }, hotkeyError: hotkeyError ?? undefined });
   },
   async updateSettings(partial) {
     const current = get().settings ?? {
@@ -150,16 +231,115 @@ export const useClipboardStore = create<State>((set, get) => ({
       record_images: true,
       hotkey: 'Ctrl+Shift+V',
       blacklist: [],
+      quick_paste_on_double_press: false,
+      restore_clipboard_after_paste: false,
+      strip_trailing_newline_on_paste: false,
+      thumb_max_dim: 256,
+      ignore_private_windows: false,
+      max_image_pixels: 50_000_000,
+      trash_retention_days: 7,
+      capture_on_copy_only: false,
+      list_limit: 500,
+      normalize_whitespace_on_capture: false,
+      lossless_images: false,
+      plain_paste_hotkey: '',
+      webp_thumbnails: false,
+      capture_window_titles: false,
+      detect_entities: false,
+      clear_on_exit: false,
+      clear_pinned_on_exit: false,
+      classify_capture_source: false,
+      paste_image_format: 'preserve',
+      min_capture_interval_ms: 0,
+      pinned_hotkey_modifier: '',
+      skip_blank_images: false,
+      blank_image_tolerance: 8,
+      default_plain_paste: false,
+      paste_mode_hotkey: '',
+      duplicate_policy: 'bump',
+      pinned_display: 'top',
+      record_unknown_formats: false,
+      max_text_items: 0,
+      max_image_items: 0,
+      app_name_map_path: '',
+      rdp_clipboard_retry: true,
+      ignored_formats: [],
+      capture_preference: 'text',
+      prefer_clipboard_owner: false,
+      capture_transforms: [],
+      store_images_on_disk: false,
+      same_app_paste: 'off',
     };
     const merged = { ...current, ...partial, record_images: true } as Settings;
     const saved: Settings = await invoke('update_settings', { settings: merged });
-    set({ settings: saved });
+    set({ settings: saved, hotkeyError: undefined });
+  },
+  async addBlacklistEntry(name) {
+    const blacklist: string[] = await invoke('add_blacklist_entry', { name });
+    const { settings } = get();
+    if (settings) set({ settings: { ...settings, blacklist } });
+  },
+  async removeBlacklistEntry(name) {
+    const blacklist: string[] = await invoke('remove_blacklist_entry', { name });
+    const { settings } = get();
+    if (settings) set({ settings: { ...settings, blacklist } });
   },
 }));
 
 // start listening for backend new item events once
 listen<ClipboardEntry>('clipboard://new', (event) => {
   const entry = event.payload;
-  const { entries } = useClipboardStore.getState();
-useClipboardStore.setState({ entries: [entry, ...entries], selectedIndex: 0, hoveredIndex: undefined });
+  const { entries, pinnedCount } = useClipboardStore.getState();
+  const rest = entries.filter((e) => e.id !== entry.id);
+  if (pinnedCount !== undefined) {
+    // 分组显示时新条目放到所属分组的最前面
+    const pinned = rest.filter((e) => e.is_pinned);
+    const recent = rest.filter((e) => !e.is_pinned);
+    if (entry.is_pinned) pinned.unshift(entry);
+    else recent.unshift(entry);
+    useClipboardStore.setState({
+      entries: [...pinned, ...recent],
+      pinnedCount: pinned.length,
+      selectedIndex: 0,
+      hoveredIndex: undefined,
+      watchError: undefined,
+      watcherPanic: undefined,
+    });
+    return;
+  }
+  useClipboardStore.setState({ entries: [entry, ...rest], selectedIndex: 0, hoveredIndex: undefined, watchError: undefined, watcherPanic: undefined });
+});
+
+// 阅后即焚条目用满次数后由后端删除
+listen<number>('clipboard://removed', (event) => {
+  const { entries, selectedIndex } = useClipboardStore.getState();
+  const next = entries.filter((e) => e.id !== event.payload);
+  useClipboardStore.setState({ entries: next, selectedIndex: Math.min(selectedIndex, Math.max(0, next.length - 1)) });
+});
+
+listen<IoProgress>('pastify://io-progress', (event) => {
+  useClipboardStore.setState({ ioProgress: event.payload });
+});
+
+listen<IoDone>('pastify://io-done', (event) => {
+  const { kind, error } = event.payload;
+  useClipboardStore.setState({ ioProgress: undefined, error });
+  if (kind !== 'export' && !error) useClipboardStore.getState().fetchHistory();
+});
+
+listen<boolean>('pastify://paste-mode', (event) => {
+  const { settings } = useClipboardStore.getState();
+  if (settings) useClipboardStore.setState({ settings: { ...settings, default_plain_paste: event.payload } });
+});
+
+listen<HotkeyError>('pastify://hotkey-error', (event) => {
+  useClipboardStore.setState({ hotkeyError: event.payload });
+});
+
+listen<WatchError>('clipboard://error', (event) => {
+  useClipboardStore.setState({ watchError: event.payload });
+});
+
+listen<WatcherPanic>('clipboard://watcher-panic', (event) => {
+  useClipboardStore.setState({ watcherPanic: event.payload });
 });
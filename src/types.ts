@@ -1,4 +1,4 @@
-export type ContentType = 'text' | 'image';
+export type ContentType = 'text' | 'image' | 'path' | 'unsupported';
 
 export interface ClipboardEntry {
   id: number;
@@ -6,10 +6,24 @@ export interface ClipboardEntry {
   text_content?: string;
   image_thumb?: string; // base64 preview
   created_at: number; // unix ms
+  created_at_iso?: string | null; // RFC3339, local time zone
   source_app?: string;
   source_icon?: string; // data url of app icon
+  source_url?: string; // page url when copied from a browser
   is_pinned: boolean;
   usage_count: number;
+  size_bytes: number;
+  detected_format?: string; // e.g. 'table'
+  table_rows?: number;
+  table_cols?: number;
+  detected_entity?: 'email' | 'phone' | 'iban';
+  source_monitor?: string | null;
+  source_dpi?: number | null;
+  capture_source?: 'user' | 'auto' | null;
+  label?: string | null;
+  derived_from?: number | null;
+  auto_delete_after_uses?: number | null;
+  expires_at?: number | null; // unix ms, deleted once reached
 }
 
 export type TimeFilter = 'all' | 'today' | 'yesterday' | 'earlier';
@@ -20,4 +34,143 @@ export interface Settings {
   record_images: boolean;
   hotkey: string;
   blacklist: string[];
+  quick_paste_on_double_press: boolean;
+  restore_clipboard_after_paste: boolean;
+  strip_trailing_newline_on_paste: boolean;
+  thumb_max_dim: number;
+  ignore_private_windows: boolean;
+  max_image_pixels: number;
+  trash_retention_days: number;
+  capture_on_copy_only: boolean;
+  list_limit: number;
+  normalize_whitespace_on_capture: boolean;
+  lossless_images: boolean;
+  plain_paste_hotkey: string;
+  webp_thumbnails: boolean;
+  capture_window_titles: boolean;
+  detect_entities: boolean;
+  clear_on_exit: boolean;
+  clear_pinned_on_exit: boolean;
+  classify_capture_source: boolean;
+  paste_image_format: 'preserve' | 'png' | 'bmp';
+  min_capture_interval_ms: number;
+  pinned_hotkey_modifier: string;
+  skip_blank_images: boolean;
+  blank_image_tolerance: number;
+  default_plain_paste: boolean;
+  paste_mode_hotkey: string;
+  duplicate_policy: 'ignore' | 'bump' | 'always_new';
+  pinned_display: 'top' | 'separate' | 'inline';
+  record_unknown_formats: boolean;
+  max_text_items: number;
+  max_image_items: number;
+  app_name_map_path: string;
+  rdp_clipboard_retry: boolean;
+  ignored_formats: string[];
+  capture_preference: 'text' | 'image' | 'both';
+  prefer_clipboard_owner: boolean;
+  capture_transforms: string[];
+  store_images_on_disk: boolean;
+  same_app_paste: 'off' | 'plain' | 'record';
+}
+
+export type HistoryResponse = ClipboardEntry[] | { pinned: ClipboardEntry[]; recent: ClipboardEntry[] };
+
+export interface IoProgress {
+  op_id: number;
+  kind: 'import' | 'export' | 'thumbnails';
+  processed: number;
+  total: number;
+}
+
+export interface IoDone {
+  op_id: number;
+  kind: 'import' | 'export' | 'thumbnails';
+  count: number;
+  skipped: number;
+  error?: string;
+}
+
+/** 启动时快捷键注册失败；fallback 为本次运行改用的默认快捷键 */
+export interface HotkeyError {
+  message: string;
+  fallback?: string | null;
+}
+
+export interface WatchError {
+  kind: 'db' | 'io' | 'clipboard' | 'other';
+  message: string;
+  failures: number;
+}
+
+export interface Diagnostics {
+  reads: number;
+  min_ms: number;
+  avg_ms: number;
+  max_ms: number;
+  saved: number;
+  repeated_images: number;
+  skipped_own_write: number;
+  skipped_not_copy: number;
+  skipped_ignored_format: number;
+  skipped_blacklist: number;
+  skipped_private: number;
+  skipped_empty: number;
+  skipped_duplicate: number;
+  errors: number;
+  source_from_foreground: number;
+  source_from_owner: number;
+  watcher_restarts: number;
+  same_app_pastes: number;
+}
+
+/** 监听循环 panic 后自动重启时推送的 clipboard://watcher-panic 事件 */
+export interface WatcherPanic {
+  message: string;
+  restarts: number;
+}
+
+/** get_history_compact 返回的精简条目，完整数据用 get_entries 按需获取 */
+export interface CompactEntry {
+  id: number;
+  content_type: ContentType;
+  preview: string;
+  created_at: number;
+  is_pinned: boolean;
+  label?: string | null;
+}
+
+/** stream_history 分批推送的 clipboard://history-chunk 事件 */
+export interface HistoryChunk {
+  stream_id: number;
+  items: ClipboardEntry[];
+  done: boolean;
+  error?: string | null;
+}
+
+export interface SimilarEntry {
+  entry: ClipboardEntry;
+  score: number;
+}
+
+export interface SourceApp {
+  app: string;
+  icon?: string | null;
+  count: number;
+}
+
+/** get_filter_suggestions：筛选快捷标签所需的数据 */
+export interface FilterSuggestions {
+  top_sources: SourceApp[];
+  content_types_present: ContentType[];
+  has_today: boolean;
+}
+
+/** get_capture_health：监听线程是否存活、心跳与最近一次成功记录 */
+export interface CaptureHealth {
+  watcher_alive: boolean;
+  heartbeat_age_ms?: number | null;
+  last_capture_at?: number | null; // unix ms
+  last_seen_seq: number;
+  current_seq: number;
 }